@@ -1,5 +1,6 @@
 use lsp_types::TextEdit;
 use maple_rs::ast::ASTType;
+use maple_rs::ast::FieldKey;
 use maple_rs::ast::AST;
 use maple_rs::lexer::TokenType;
 use maple_rs::parser::{ObjectKey, Parser};
@@ -53,6 +54,20 @@ fn format(
             format(&l, indent, indent_size, log_file).join("\n"),
             format(&r, indent, indent_size, log_file).join("\n")
         )],
+        ASTType::Slice(v, start, end) => {
+            let mut format_end = |end: &Option<Box<AST>>| match end {
+                Some(end) => format(&end, indent, indent_size, log_file).join("\n"),
+                None => "".to_string(),
+            };
+            let start = format_end(start);
+            let end = format_end(end);
+            vec![format!(
+                "{}[{}:{}]",
+                format(&v, indent, indent_size, log_file).join("\n"),
+                start,
+                end
+            )]
+        }
         ASTType::ArrayLiteral(a) => vec![format!(
             "[{}]",
             a.iter()
@@ -67,10 +82,13 @@ fn format(
                     "{}{} = {}",
                     " ".repeat(indent_size),
                     match &(*x).0 {
-                        ObjectKey::String(str) => format!("{}", str),
-                        ObjectKey::Number(num) => {
+                        FieldKey::Static(ObjectKey::String(str)) => format!("{}", str),
+                        FieldKey::Static(ObjectKey::Number(num)) => {
                             format!("{}", num)
                         }
+                        FieldKey::Computed(key) => {
+                            format!("[{}]", format(&key, indent, indent_size, log_file).join("\n"))
+                        }
                     },
                     format(&(*x).1, indent + indent_size, indent_size, log_file)
                         .iter()
@@ -102,7 +120,8 @@ fn format(
                 .replace("\t", "\\t")
                 .replace("\0", "\\0")
         )],
-        ASTType::NumberLiteral(n) => vec![format!("{}", n)],
+        ASTType::NumberLiteral(n) => vec![format!("{:?}", n)],
+        ASTType::IntLiteral(n) => vec![format!("{}", n)],
         ASTType::BooleanLiteral(b) => vec![format!("{}", b)],
         ASTType::Paren(l) => vec![format!(
             "({})",
@@ -114,7 +133,8 @@ fn format(
             name
         )],
         ASTType::FunctionLiteral(f) => vec![format!(
-            "fn ({}) {{\n{}\n{}}}",
+            "{}fn ({}) {{\n{}\n{}}}",
+            if f.is_async { "async " } else { "" },
             f.params
                 .iter()
                 .map(|v| v.name.clone())
@@ -155,11 +175,35 @@ fn format(
                 None => "".to_string(),
             }
         )],
-        ASTType::While(cond, block) => vec![format!(
-            "while {} {{\n{}\n{}}}",
-            format(&cond, indent, indent_size, log_file).join("\n"),
-            format_block(&block, indent, indent_size, log_file).join("\n"),
-            "".to_string()
+        ASTType::While(lit) => vec![format!(
+            "{}{}{}",
+            match &lit.label {
+                Some(label) => format!("{}: ", label),
+                None => "".to_string(),
+            },
+            match &lit.cond {
+                Some(cond) if lit.is_do_while => format!(
+                    "do {{\n{}\n}} while {}",
+                    format_block(&lit.body, indent, indent_size, log_file).join("\n"),
+                    format(&cond, indent, indent_size, log_file).join("\n")
+                ),
+                Some(cond) => format!(
+                    "while {} {{\n{}\n}}",
+                    format(&cond, indent, indent_size, log_file).join("\n"),
+                    format_block(&lit.body, indent, indent_size, log_file).join("\n")
+                ),
+                None => format!(
+                    "loop {{\n{}\n}}",
+                    format_block(&lit.body, indent, indent_size, log_file).join("\n")
+                ),
+            },
+            match lit.else_body {
+                Some(ref x) => format!(
+                    " else {{\n{}\n}}",
+                    format_block(&x, indent, indent_size, log_file).join("\n")
+                ),
+                None => "".to_string(),
+            }
         )],
         ASTType::OpPls(l, r) => format_operator(l, r, "+", indent, indent_size, log_file),
         ASTType::OpMns(l, r) => format_operator(l, r, "-", indent, indent_size, log_file),
@@ -176,7 +220,8 @@ fn format(
             if let ASTType::VariableDeclaration(name, true) = &l.t {
                 if let ASTType::FunctionLiteral(f) = &r.t {
                     ret = Some(vec![format!(
-                        "fn {} ({}) {{\n{}\n{}}}",
+                        "{}fn {} ({}) {{\n{}\n{}}}",
+                        if f.is_async { "async " } else { "" },
                         name,
                         f.params
                             .iter()
@@ -208,13 +253,136 @@ fn format(
         ASTType::OpLt(l, r) => format_operator(l, r, "<", indent, indent_size, log_file),    // <
         ASTType::OpGtEq(l, r) => format_operator(l, r, ">=", indent, indent_size, log_file), // >=
         ASTType::OpLtEq(l, r) => format_operator(l, r, "<=", indent, indent_size, log_file), // <=
-        ASTType::VariableAccess(s) => vec![format!("{}", s)],
+        ASTType::OpIn(l, r) => format_operator(l, r, "in", indent, indent_size, log_file),
+        ASTType::Delete(field) => vec![format!(
+            "delete {}",
+            format(&field, indent, indent_size, log_file).join("\n")
+        )],
+        ASTType::VariableAccess(s, _) => vec![format!("{}", s)],
         ASTType::Return(ret) => vec![format!(
             "return {}",
             format(&ret, indent, indent_size, log_file).join("\n")
         )],
-        ASTType::Break => vec![format!("break")],
-        ASTType::Continue => vec![format!("continue")],
+        ASTType::Break(label) => match label {
+            Some(label) => vec![format!("break {}", label)],
+            None => vec![format!("break")],
+        },
+        ASTType::Continue(label) => match label {
+            Some(label) => vec![format!("continue {}", label)],
+            None => vec![format!("continue")],
+        },
+        ASTType::Quote(block) => vec![format!(
+            "quote {{\n{}\n}}",
+            format_block(&block, indent, indent_size, log_file).join("\n")
+        )],
+        ASTType::Unquote(l) => vec![format!(
+            "unquote({})",
+            format(&l, indent, indent_size, log_file).join("\n")
+        )],
+        ASTType::For(lit) => vec![format!(
+            "{}for {} in {} {{\n{}\n}}",
+            match &lit.label {
+                Some(label) => format!("{}: ", label),
+                None => "".to_string(),
+            },
+            lit.var,
+            format(&lit.iter, indent, indent_size, log_file).join("\n"),
+            format_block(&lit.body, indent, indent_size, log_file).join("\n")
+        )],
+        ASTType::Range(lit) => {
+            let mut ret = format!(
+                "{}{}{}",
+                format(&lit.start, indent, indent_size, log_file).join("\n"),
+                if lit.inclusive { "..=" } else { ".." },
+                format(&lit.end, indent, indent_size, log_file).join("\n")
+            );
+            if let Some(step) = &lit.step {
+                ret += &format!(" by {}", format(&step, indent, indent_size, log_file).join("\n"));
+            }
+            vec![ret]
+        }
+        ASTType::Await(l) => vec![format!(
+            "await {}",
+            format(&l, indent, indent_size, log_file).join("\n")
+        )],
+        ASTType::Splice(block) => block
+            .iter()
+            .map(|x| format(&x, indent, indent_size, log_file))
+            .flatten()
+            .collect(),
+        ASTType::Macro(name, f) => vec![format!(
+            "macro {}({}) {{\n{}\n}}",
+            name,
+            f.params
+                .iter()
+                .map(|v| v.name.clone())
+                .collect::<Vec<String>>()
+                .join(", "),
+            format_block(&f.body, indent, indent_size, log_file).join("\n")
+        )],
+        // only the call is formatted, the expansion isn't part of the source
+        ASTType::MacroCall(call, _) => format(&call, indent, indent_size, log_file),
+        ASTType::Comptime(lit) if lit.is_block => vec![format!(
+            "comptime {{\n{}\n}}",
+            format_block(&lit.code, indent, indent_size, log_file).join("\n")
+        )],
+        ASTType::Comptime(lit) => vec![format!(
+            "comptime {}",
+            format(&lit.code[0], indent, indent_size, log_file).join("\n")
+        )],
+        ASTType::Enum(lit) => {
+            let mut ret = vec![format!("enum {} {{", lit.name)];
+            for (name, fields) in lit.variants.iter() {
+                if fields.is_empty() {
+                    ret.push(format!("{}{}", " ".repeat(indent_size), name));
+                } else {
+                    ret.push(format!(
+                        "{}{}({})",
+                        " ".repeat(indent_size),
+                        name,
+                        fields.join(", ")
+                    ));
+                }
+            }
+            ret.push("}".to_string());
+            vec![ret.join("\n")]
+        }
+        ASTType::Struct(lit) => {
+            let mut ret = vec![format!("struct {} {{", lit.name)];
+            for field in lit.fields.iter() {
+                ret.push(format!("{}{}", " ".repeat(indent_size), field));
+            }
+            ret.push("}".to_string());
+            vec![ret.join("\n")]
+        }
+        ASTType::Match(lit) => {
+            let mut arms = vec![];
+            for arm in lit.arms.iter() {
+                let pattern = format(&arm.pattern, indent, indent_size, log_file).join("\n");
+                if arm.is_block {
+                    arms.push(format!(
+                        "{} => {{\n{}\n}}",
+                        pattern,
+                        format_block(&arm.body, indent, indent_size, log_file).join("\n")
+                    ));
+                } else {
+                    arms.push(format!(
+                        "{} => {}",
+                        pattern,
+                        format(&arm.body[0], indent, indent_size, log_file).join("\n")
+                    ));
+                }
+            }
+            vec![format!(
+                "match {} {{\n{}\n}}",
+                format(&lit.value, indent, indent_size, log_file).join("\n"),
+                arms.join("\n")
+                    .split("\n")
+                    .map(|x| format!("{}{}", " ".repeat(indent_size), x))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            )]
+        }
     };
     let mut ret = vec![];
     for line in x {
@@ -2,7 +2,7 @@ use std::io::Write;
 
 use lsp_types::{Position, Range};
 use maple_rs::{
-    ast::{ASTType, Block, AST},
+    ast::{ASTType, Block, FieldKey, AST},
     parser::Parser,
 };
 #[derive(Debug)]
@@ -114,10 +114,12 @@ fn get_later_line(l: &Box<AST>, r: &Box<AST>) -> usize {
 fn get_last_line(ast: &Box<AST>) -> usize {
     match &ast.t {
         ASTType::OpLtEq(l, r) => get_later_line(l, r),
-        ASTType::VariableAccess(_) => ast.token.line,
+        ASTType::OpIn(l, r) => get_later_line(l, r),
+        ASTType::Delete(l) => get_last_line(&l),
+        ASTType::VariableAccess(_, _) => ast.token.line,
         ASTType::Return(l) => get_last_line(&l),
-        ASTType::Break => ast.token.line,
-        ASTType::Continue => ast.token.line,
+        ASTType::Break(_) => ast.token.line,
+        ASTType::Continue(_) => ast.token.line,
         ASTType::If(lit) => {
             if lit.else_body.is_some() && lit.else_body.as_ref().unwrap().len() > 0 {
                 let else_body = lit.else_body.as_ref().unwrap();
@@ -140,11 +142,16 @@ fn get_last_line(ast: &Box<AST>) -> usize {
             }
         }
         ASTType::OpEq(l, r) => get_later_line(l, r),
-        ASTType::While(_, block) => {
-            if block.len() == 0 {
+        ASTType::While(lit) => {
+            if lit.else_body.is_some() && lit.else_body.as_ref().unwrap().len() > 0 {
+                let else_body = lit.else_body.as_ref().unwrap();
+                get_last_line(&else_body[else_body.len() - 1])
+            } else if lit.is_do_while {
+                get_last_line(lit.cond.as_ref().unwrap())
+            } else if lit.body.len() == 0 {
                 ast.token.line
             } else {
-                get_last_line(&block[block.len() - 1])
+                get_last_line(&lit.body[lit.body.len() - 1])
             }
         }
         ASTType::OpMnsPrefix(l) => get_last_line(&l),
@@ -161,7 +168,7 @@ fn get_last_line(ast: &Box<AST>) -> usize {
         ASTType::DotAccess(_, _) => ast.token.line,
         ASTType::StringLiteral(_) => ast.token.line,
         ASTType::OpLt(l, r) => get_later_line(l, r),
-        ASTType::BracketAccess(_, _) => ast.token.line,
+        ASTType::BracketAccess(_, _) | ASTType::Slice(_, _, _) => ast.token.line,
         ASTType::ObjectLiteral(o) => {
             if o.len() == 0 {
                 ast.token.line
@@ -179,6 +186,7 @@ fn get_last_line(ast: &Box<AST>) -> usize {
         ASTType::OpGt(l, r) => get_later_line(l, r),
         ASTType::CharacterLiteral(_) => ast.token.line,
         ASTType::NumberLiteral(_) => ast.token.line,
+        ASTType::IntLiteral(_) => ast.token.line,
         ASTType::BooleanLiteral(_) => ast.token.line,
         ASTType::Paren(l) => get_last_line(&l),
         ASTType::OpPls(l, r) => get_later_line(l, r),
@@ -198,6 +206,33 @@ fn get_last_line(ast: &Box<AST>) -> usize {
                 get_last_line(&r[r.len() - 1])
             }
         }
+        ASTType::Quote(b) | ASTType::Splice(b) => match b.last() {
+            Some(last) => get_last_line(last),
+            None => ast.token.line,
+        },
+        ASTType::Unquote(l) | ASTType::Await(l) => get_last_line(&l),
+        ASTType::Macro(_, f) => match f.body.last() {
+            Some(last) => get_last_line(last),
+            None => ast.token.line,
+        },
+        ASTType::MacroCall(call, _) => get_last_line(&call),
+        ASTType::Comptime(lit) => match lit.code.last() {
+            Some(last) => get_last_line(last),
+            None => ast.token.line,
+        },
+        ASTType::Enum(_) | ASTType::Struct(_) => ast.token.line,
+        ASTType::For(lit) => match lit.body.last() {
+            Some(last) => get_last_line(last),
+            None => get_last_line(&lit.iter),
+        },
+        ASTType::Range(lit) => match &lit.step {
+            Some(step) => get_last_line(step),
+            None => get_later_line(&lit.start, &lit.end),
+        },
+        ASTType::Match(lit) => match lit.arms.last() {
+            Some(arm) if arm.body.len() > 0 => get_last_line(arm.body.last().unwrap()),
+            _ => get_last_line(&lit.value),
+        },
     }
 }
 
@@ -212,6 +247,50 @@ fn parse_binop(
     ret.extend(parse_ast(r, variables, scope_level, block_end));
     ret
 }
+// names inside of a variant pattern like Shape.Circle(r) are bindings instead of references
+fn parse_pattern(
+    pattern: &Box<AST>,
+    variables: &mut Variables,
+    scope_level: u32,
+    block_end: Position,
+    is_field: bool,
+) -> Vec<ReferenceError> {
+    match &pattern.t {
+        ASTType::VariableAccess(name, _) if name == "_" => vec![],
+        ASTType::VariableAccess(name, _) if is_field => {
+            variables.add_variable(
+                name.to_string(),
+                Range {
+                    start: Position {
+                        line: pattern.token.line as u32,
+                        character: pattern.token.char_start as u32,
+                    },
+                    end: block_end,
+                },
+                Range {
+                    start: Position {
+                        line: pattern.token.line as u32,
+                        character: pattern.token.char_start as u32,
+                    },
+                    end: Position {
+                        line: pattern.token.line as u32,
+                        character: pattern.token.char_end as u32,
+                    },
+                },
+                scope_level,
+            );
+            vec![]
+        }
+        ASTType::FunctionCall(l, r) => {
+            let mut ret = parse_ast(&l, variables, scope_level, block_end);
+            for v in r {
+                ret.extend(parse_pattern(&v, variables, scope_level, block_end, true));
+            }
+            ret
+        }
+        _ => parse_ast(pattern, variables, scope_level, block_end),
+    }
+}
 fn parse_ast(
     ast: &Box<AST>,
     variables: &mut Variables,
@@ -219,24 +298,29 @@ fn parse_ast(
     block_end: Position,
 ) -> Vec<ReferenceError> {
     match &ast.t {
-        ASTType::While(l, block) => {
-            let mut ret = parse_ast(&l, variables, scope_level, block_end);
+        ASTType::While(lit) => {
+            let mut ret = match &lit.cond {
+                Some(cond) => parse_ast(&cond, variables, scope_level, block_end),
+                None => vec![],
+            };
             let new_scope_level = scope_level + 1;
-            if block.len() > 0 {
-                let last_line = get_last_line(block.last().unwrap());
-                ret.extend(parse_block(
-                    &block,
-                    variables,
-                    new_scope_level,
-                    Position {
-                        line: last_line as u32,
-                        character: 0,
-                    },
-                ));
+            for block in [Some(&lit.body), lit.else_body.as_ref()].into_iter().flatten() {
+                if block.len() > 0 {
+                    let last_line = get_last_line(block.last().unwrap());
+                    ret.extend(parse_block(
+                        &block,
+                        variables,
+                        new_scope_level,
+                        Position {
+                            line: last_line as u32,
+                            character: 0,
+                        },
+                    ));
+                }
             }
             ret
         }
-        ASTType::VariableAccess(name) => {
+        ASTType::VariableAccess(name, _) => {
             if !variables.variable_exists(&name, ast.token.line as u32) {
                 vec![ReferenceError {
                     name: name.clone(),
@@ -260,13 +344,15 @@ fn parse_ast(
         ASTType::OpMnsPrefix(l) => parse_ast(&l, variables, scope_level, block_end),
         ASTType::OpEq(l, r) => parse_binop(&l, &r, variables, scope_level, block_end),
         ASTType::OpLtEq(l, r) => parse_binop(&l, &r, variables, scope_level, block_end),
+        ASTType::OpIn(l, r) => parse_binop(&l, &r, variables, scope_level, block_end),
+        ASTType::Delete(l) => parse_ast(&l, variables, scope_level, block_end),
         ASTType::Return(l) => parse_ast(&l, variables, scope_level, block_end),
         ASTType::OpPls(l, r) => parse_binop(&l, &r, variables, scope_level, block_end),
         ASTType::OpMns(l, r) => parse_binop(&l, &r, variables, scope_level, block_end),
         ASTType::OpTimes(l, r) => parse_binop(&l, &r, variables, scope_level, block_end),
         ASTType::OpDiv(l, r) => parse_binop(&l, &r, variables, scope_level, block_end),
-        ASTType::Continue => vec![],
-        ASTType::Break => vec![],
+        ASTType::Continue(_) => vec![],
+        ASTType::Break(_) => vec![],
         ASTType::If(lit) => {
             let mut ret = parse_ast(&lit.cond, variables, scope_level, block_end);
             let new_scope_level = scope_level + 1;
@@ -317,9 +403,111 @@ fn parse_ast(
             .map(|x| parse_ast(x, variables, scope_level, block_end))
             .flatten()
             .collect(),
+        // quoted code only means something once it's been expanded, so that's where it's checked
+        ASTType::Quote(_) => vec![],
+        ASTType::Macro(_, _) => vec![],
+        // comptime code runs on its own while parsing, it can't see any of these variables
+        ASTType::Comptime(_) => vec![],
+        ASTType::Enum(_) | ASTType::Struct(_) => {
+            let name = match &ast.t {
+                ASTType::Enum(lit) => &lit.name,
+                ASTType::Struct(lit) => &lit.name,
+                _ => unreachable!(),
+            };
+            variables.add_variable(
+                name.to_string(),
+                Range {
+                    start: Position {
+                        line: ast.token.line as u32,
+                        character: ast.token.char_start as u32,
+                    },
+                    end: block_end,
+                },
+                Range {
+                    start: Position {
+                        line: ast.token.line as u32,
+                        character: ast.token.char_start as u32,
+                    },
+                    end: Position {
+                        line: ast.token.line as u32,
+                        character: ast.token.char_end as u32,
+                    },
+                },
+                scope_level,
+            );
+            vec![]
+        }
+        ASTType::For(lit) => {
+            let mut ret = parse_ast(&lit.iter, variables, scope_level, block_end);
+            let new_scope_level = scope_level + 1;
+            let loop_end = Position {
+                line: get_last_line(&ast) as u32,
+                character: 0,
+            };
+            variables.add_variable(
+                lit.var.to_string(),
+                Range {
+                    start: Position {
+                        line: ast.token.line as u32,
+                        character: ast.token.char_start as u32,
+                    },
+                    end: loop_end,
+                },
+                Range {
+                    start: Position {
+                        line: ast.token.line as u32,
+                        character: ast.token.char_start as u32,
+                    },
+                    end: Position {
+                        line: ast.token.line as u32,
+                        character: ast.token.char_end as u32,
+                    },
+                },
+                new_scope_level,
+            );
+            ret.extend(parse_block(&lit.body, variables, new_scope_level, loop_end));
+            ret
+        }
+        ASTType::Range(lit) => {
+            let mut ret = parse_binop(&lit.start, &lit.end, variables, scope_level, block_end);
+            if let Some(step) = &lit.step {
+                ret.extend(parse_ast(&step, variables, scope_level, block_end));
+            }
+            ret
+        }
+        ASTType::Match(lit) => {
+            let mut ret = parse_ast(&lit.value, variables, scope_level, block_end);
+            let new_scope_level = scope_level + 1;
+            for arm in &lit.arms {
+                let arm_end = Position {
+                    line: match arm.body.last() {
+                        Some(last) => get_last_line(last),
+                        None => arm.pattern.token.line,
+                    } as u32,
+                    character: 0,
+                };
+                ret.extend(parse_pattern(
+                    &arm.pattern,
+                    variables,
+                    new_scope_level,
+                    arm_end,
+                    false,
+                ));
+                ret.extend(parse_block(&arm.body, variables, new_scope_level, arm_end));
+            }
+            ret
+        }
+        ASTType::Unquote(l) | ASTType::Await(l) => parse_ast(&l, variables, scope_level, block_end),
+        ASTType::Splice(b) => b
+            .iter()
+            .map(|x| parse_ast(x, variables, scope_level, block_end))
+            .flatten()
+            .collect(),
+        ASTType::MacroCall(_, expansion) => parse_ast(&expansion, variables, scope_level, block_end),
         ASTType::CharacterLiteral(_) => vec![],
         ASTType::StringLiteral(_) => vec![],
         ASTType::NumberLiteral(_) => vec![],
+        ASTType::IntLiteral(_) => vec![],
         ASTType::BooleanLiteral(_) => vec![],
         ASTType::Paren(l) => parse_ast(&l, variables, scope_level, block_end),
         ASTType::FunctionLiteral(f) => {
@@ -389,11 +577,23 @@ fn parse_ast(
         ASTType::Import(_) => vec![],
         ASTType::DotAccess(l, _) => parse_ast(&l, variables, scope_level, block_end),
         ASTType::BracketAccess(l, r) => parse_binop(&l, &r, variables, scope_level, block_end),
-        ASTType::ObjectLiteral(o) => o
-            .iter()
-            .map(|(_, v)| parse_ast(&v, variables, scope_level, block_end))
-            .flatten()
-            .collect(),
+        ASTType::Slice(v, start, end) => {
+            let mut ret = parse_ast(&v, variables, scope_level, block_end);
+            for bound in [start, end].into_iter().flatten() {
+                ret.extend(parse_ast(&bound, variables, scope_level, block_end));
+            }
+            ret
+        }
+        ASTType::ObjectLiteral(o) => {
+            let mut ret = vec![];
+            for (k, v) in o {
+                if let FieldKey::Computed(k) = k {
+                    ret.extend(parse_ast(&k, variables, scope_level, block_end));
+                }
+                ret.extend(parse_ast(&v, variables, scope_level, block_end));
+            }
+            ret
+        }
     }
 }
 
@@ -1,10 +1,13 @@
 #![allow(dead_code)]
 use crate::error::{RuntimeError, ScopeError};
+use crate::event_loop::{Future, FutureState};
 use crate::lexer::Token;
 use crate::parser::{Object, ObjectKey, Unpack, Value};
 use crate::runtime::*;
 use crate::scopechain::ReturnType;
 use crate::scopechain::ScopeChain;
+use crate::scopechain::{Address, Engine};
+use crate::vm::ChunkCache;
 
 use std::rc::Rc;
 
@@ -43,6 +46,10 @@ pub struct FunctionLiteral {
     pub params: Vec<FnParam>,
     pub body: Block,
     pub closure: ScopeChain,
+    // calling an async fn gives back a future, the body runs on the event loop as a task
+    pub is_async: bool,
+    // the body compiled for the vm, once something calls it there
+    pub chunk: ChunkCache,
 }
 impl FunctionLiteral {
     pub fn new(params: Vec<FnParam>, body: Block, scope_chain: &ScopeChain) -> FunctionLiteral {
@@ -50,6 +57,8 @@ impl FunctionLiteral {
             params,
             body,
             closure: scope_chain.get_closure(),
+            is_async: false,
+            chunk: ChunkCache::default(),
         }
     }
     pub fn basic(params: Vec<FnParam>, body: Block) -> FunctionLiteral {
@@ -57,6 +66,8 @@ impl FunctionLiteral {
             params,
             body,
             closure: ScopeChain::new(),
+            is_async: false,
+            chunk: ChunkCache::default(),
         }
     }
     pub fn make_real(&self, scope_chain: &ScopeChain) -> FunctionLiteral {
@@ -64,12 +75,15 @@ impl FunctionLiteral {
             params: self.params.clone(),
             body: self.body.clone(),
             closure: scope_chain.get_closure(),
+            is_async: self.is_async,
+            chunk: self.chunk.share(),
         }
     }
 
     pub fn pretty_print(&self) -> String {
         format!(
-            "fn ({}) {{\n{}\n}}",
+            "{}fn ({}) {{\n{}\n}}",
+            if self.is_async { "async " } else { "" },
             self.params
                 .iter()
                 .map(|p| p.name.clone())
@@ -94,7 +108,99 @@ impl FunctionLiteral {
                 ast.get_value(scope_chain)
                     .unpack_and_transform(scope_chain, line, ast)
             })
-            .collect::<Vec<Result<Rc<Value>, Box<RuntimeError>>>>();
+            .collect::<Result<Vec<Rc<Value>>, Box<RuntimeError>>>()?;
+        self.invoke(scope_chain, params_value, line)
+    }
+    // calls the function with arguments that have already been evaluated, an async fn
+    //  gives back a future instead of running
+    pub fn invoke(
+        &self,
+        scope_chain: &mut ScopeChain,
+        params_value: Vec<Rc<Value>>,
+        line: usize,
+    ) -> Result<Rc<Value>, Box<RuntimeError>> {
+        if self.is_async {
+            // the body only starts once the event loop gets to it
+            let task = Future::new(FutureState::Pending(self.clone(), params_value), line);
+            scope_chain.get_event_loop().spawn(task.clone());
+            return Ok(Rc::new(Value::Future(task)));
+        }
+        self.call_values(scope_chain, params_value, line)
+    }
+    // calls the function with arguments that have already been evaluated. a `return f(x)`
+    //  in the body leaves the call in the return register, and it runs here after the
+    //  frame is gone so tail calls don't grow the stack
+    pub fn call_values(
+        &self,
+        scope_chain: &mut ScopeChain,
+        params: Vec<Rc<Value>>,
+        line: usize,
+    ) -> Result<Rc<Value>, Box<RuntimeError>> {
+        let mut tail_call: Option<Rc<Value>> = None;
+        let mut params = params;
+        loop {
+            let func = match tail_call.as_deref() {
+                Some(Value::Function(func)) => func,
+                _ => self,
+            };
+            func.run_frame(scope_chain, params, line)?;
+            match take_returned(scope_chain, line)? {
+                Returned::Value(value) => return Ok(value),
+                Returned::TailCall(next, args) => {
+                    tail_call = Some(next);
+                    params = args;
+                }
+            }
+        }
+    }
+    // leaves the frame a task's body ran in and gives back what the body returned, a tail
+    //  call it left behind runs here
+    pub fn finish_frame(
+        &self,
+        scope_chain: &mut ScopeChain,
+        line: usize,
+    ) -> Result<Rc<Value>, Box<RuntimeError>> {
+        scope_chain.pop_fn_scope(line).to_runtime_error()?;
+        match take_returned(scope_chain, line)? {
+            Returned::Value(value) => Ok(value),
+            Returned::TailCall(next, args) => match next.as_ref() {
+                Value::Function(f) => f.call_values(scope_chain, args, line),
+                _ => Ok(Rc::new(Value::Undefined)),
+            },
+        }
+    }
+    // runs the body in a new frame, leaving whatever it returned in the return register
+    fn run_frame(
+        &self,
+        scope_chain: &mut ScopeChain,
+        params: Vec<Rc<Value>>,
+        line: usize,
+    ) -> Result<(), Box<RuntimeError>> {
+        self.enter_frame(scope_chain, params, line)?;
+        match scope_chain.get_engine() {
+            Engine::Tree => {
+                for ast in self.body.iter() {
+                    ast.get_value(scope_chain)?;
+                    match scope_chain.get_return_register() {
+                        ReturnType::None => (),
+                        _ => break,
+                    }
+                }
+            }
+            Engine::Vm => self.chunk.get(&self.body, false).run_body(scope_chain)?,
+        }
+        match scope_chain.pop_fn_scope(line) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Box::new(e.to_runtime_error())),
+        }
+    }
+    // adds the scope the body runs in along with the parameters
+    pub fn enter_frame(
+        &self,
+        scope_chain: &mut ScopeChain,
+        params: Vec<Rc<Value>>,
+        line: usize,
+    ) -> Result<(), Box<RuntimeError>> {
         scope_chain.add_fn_scope(&self.closure);
         if params.len() != self.params.len() {
             return Err(Box::new(RuntimeError::new(
@@ -106,41 +212,242 @@ impl FunctionLiteral {
                 line,
             )));
         }
-        for (i, param) in self.params.iter().enumerate() {
+        for (param, param_value) in self.params.iter().zip(params) {
             scope_chain
                 .add_variable(&param.name, false, line)
                 .to_runtime_error()?;
-            let param_value = params_value[i].clone()?;
             scope_chain
                 .set_variable(&param.name, param_value, line)
                 .to_runtime_error()?;
         }
-        for ast in self.body.iter() {
-            ast.get_value(scope_chain)?;
-            match scope_chain.get_return_register() {
-                ReturnType::None => (),
-                _ => break,
-            }
+        Ok(())
+    }
+}
+// what a function body left in the return register once it finished
+enum Returned {
+    Value(Rc<Value>),
+    TailCall(Rc<Value>, Vec<Rc<Value>>),
+}
+fn take_returned(scope_chain: &mut ScopeChain, line: usize) -> Result<Returned, Box<RuntimeError>> {
+    match scope_chain.get_return_register() {
+        ReturnType::None => Ok(Returned::Value(Rc::new(Value::Undefined))),
+        ReturnType::Return(value) => {
+            scope_chain.eat_return_register();
+            Ok(Returned::Value(value))
         }
-        match scope_chain.pop_fn_scope(line) {
-            Ok(_) => (),
-            Err(e) => return Err(Box::new(e.to_runtime_error())),
+        ReturnType::TailCall(next, args) => {
+            scope_chain.eat_return_register();
+            Ok(Returned::TailCall(next, args))
+        }
+        ReturnType::Break(_) => Err(Box::new(RuntimeError::new(
+            "Cannot call 'break' inside a function".into(),
+            line,
+        ))),
+        ReturnType::Continue(_) => Err(Box::new(RuntimeError::new(
+            "Cannot call 'continue' inside a function".into(),
+            line,
+        ))),
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct WhileLiteral {
+    // None for loop { }, which only ends with a break or return
+    pub cond: Option<Box<AST>>,
+    // do { } while cond runs the body once before checking cond
+    pub is_do_while: bool,
+    pub body: Block,
+    // runs when the condition stops the loop, but not after a break
+    pub else_body: Option<Block>,
+    pub label: Option<String>,
+}
+impl WhileLiteral {
+    pub fn pretty_print(&self) -> String {
+        let print_block = |block: &Block| {
+            block
+                .iter()
+                .map(|ast| format!("    {}\n", ast.pretty_print()))
+                .collect::<Vec<String>>()
+                .join("")
         };
-        match scope_chain.get_return_register() {
-            ReturnType::None => Ok(Rc::new(Value::Undefined)),
-            ReturnType::Return(value) => {
-                scope_chain.eat_return_register();
-                Ok(value)
+        let mut ret = match &self.label {
+            Some(label) => format!("{}: ", label),
+            None => "".to_string(),
+        };
+        ret += &match &self.cond {
+            Some(cond) if self.is_do_while => format!(
+                "do {{\n{}\n}} while {}",
+                print_block(&self.body),
+                cond.pretty_print()
+            ),
+            Some(cond) => format!(
+                "while {} {{\n{}\n}}",
+                cond.pretty_print(),
+                print_block(&self.body)
+            ),
+            None => format!("loop {{\n{}\n}}", print_block(&self.body)),
+        };
+        if let Some(else_body) = &self.else_body {
+            ret += &format!(" else {{\n{}\n}}", print_block(else_body));
+        }
+        ret
+    }
+}
+// for x in iterable { }
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForLiteral {
+    pub var: String,
+    pub iter: Box<AST>,
+    pub body: Block,
+    pub label: Option<String>,
+}
+impl ForLiteral {
+    pub fn pretty_print(&self) -> String {
+        format!(
+            "{}for {} in {} {{\n{}\n}}",
+            match &self.label {
+                Some(label) => format!("{}: ", label),
+                None => "".to_string(),
+            },
+            self.var,
+            self.iter.pretty_print(),
+            self.body
+                .iter()
+                .map(|ast| format!("    {}\n", ast.pretty_print()))
+                .collect::<Vec<String>>()
+                .join("")
+        )
+    }
+}
+// the key of a field in an object literal, [expr] = v works the key out when the object
+//  is made
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldKey {
+    Static(ObjectKey),
+    Computed(Box<AST>),
+}
+// start..end, start..=end and either of them with by step after
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeLiteral {
+    pub start: Box<AST>,
+    pub end: Box<AST>,
+    pub step: Option<Box<AST>>,
+    pub inclusive: bool,
+}
+impl RangeLiteral {
+    pub fn pretty_print(&self) -> String {
+        let mut ret = format!(
+            "{}{}{}",
+            self.start.pretty_print(),
+            if self.inclusive { "..=" } else { ".." },
+            self.end.pretty_print()
+        );
+        if let Some(step) = &self.step {
+            ret += &format!(" by {}", step.pretty_print());
+        }
+        ret
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComptimeLiteral {
+    // the code as it was written, only kept for printing
+    pub code: Block,
+    // comptime { } instead of comptime expr
+    pub is_block: bool,
+    // what the code evaluated to while parsing, this is what actually runs
+    pub value: Box<AST>,
+}
+impl ComptimeLiteral {
+    pub fn pretty_print(&self) -> String {
+        if self.is_block {
+            format!(
+                "comptime {{\n{}\n}}",
+                self.code
+                    .iter()
+                    .map(|ast| format!("    {}\n", ast.pretty_print()))
+                    .collect::<Vec<String>>()
+                    .join("")
+            )
+        } else {
+            let code = self.code.iter().map(|ast| ast.pretty_print());
+            format!("comptime {}", code.collect::<Vec<String>>().join(""))
+        }
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumLiteral {
+    pub name: String,
+    // each variant with the names of its fields, variants without data have no fields
+    pub variants: Vec<(String, Vec<String>)>,
+}
+impl EnumLiteral {
+    pub fn pretty_print(&self) -> String {
+        let variants = self.variants.iter().map(|(name, fields)| {
+            if fields.is_empty() {
+                format!("    {}\n", name)
+            } else {
+                format!("    {}({})\n", name, fields.join(", "))
+            }
+        });
+        format!(
+            "enum {} {{\n{}}}",
+            self.name,
+            variants.collect::<Vec<String>>().join("")
+        )
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructLiteral {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+impl StructLiteral {
+    pub fn pretty_print(&self) -> String {
+        let fields = self.fields.iter().map(|field| format!("    {}\n", field));
+        format!(
+            "struct {} {{\n{}}}",
+            self.name,
+            fields.collect::<Vec<String>>().join("")
+        )
+    }
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    // _ matches anything, Shape.Circle(r) matches a variant and binds its fields, anything
+    //  else gets evaluated and compared with ==
+    pub pattern: Box<AST>,
+    pub body: Block,
+    // pattern => { } instead of pattern => expr
+    pub is_block: bool,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchLiteral {
+    pub value: Box<AST>,
+    pub arms: Vec<MatchArm>,
+}
+impl MatchLiteral {
+    pub fn pretty_print(&self) -> String {
+        let mut ret = format!("match {} {{\n", self.value.pretty_print());
+        for arm in self.arms.iter() {
+            if arm.is_block {
+                ret += &format!(
+                    "    {} => {{\n{}    }}\n",
+                    arm.pattern.pretty_print(),
+                    arm.body
+                        .iter()
+                        .map(|ast| format!("        {}\n", ast.pretty_print()))
+                        .collect::<Vec<String>>()
+                        .join("")
+                );
+            } else {
+                ret += &format!(
+                    "    {} => {}\n",
+                    arm.pattern.pretty_print(),
+                    arm.body[0].pretty_print()
+                );
             }
-            ReturnType::Break => Err(Box::new(RuntimeError::new(
-                "Cannot call 'break' inside a function".into(),
-                line,
-            ))),
-            ReturnType::Continue => Err(Box::new(RuntimeError::new(
-                "Cannot call 'continue' inside a function".into(),
-                line,
-            ))),
         }
+        ret += "}";
+        ret
     }
 }
 #[derive(Debug, Clone, PartialEq)]
@@ -204,18 +511,23 @@ pub enum ASTType {
     Import(String),
     DotAccess(Box<AST>, String),
     BracketAccess(Box<AST>, Box<AST>),
-    ObjectLiteral(Vec<(ObjectKey, Box<AST>)>),
+    // value[start:end], either end can be left out
+    Slice(Box<AST>, Option<Box<AST>>, Option<Box<AST>>),
+    ObjectLiteral(Vec<(FieldKey, Box<AST>)>),
     ArrayLiteral(Vec<Box<AST>>),
     CharacterLiteral(char),
     StringLiteral(String),
     NumberLiteral(f64),
+    IntLiteral(i64),
     BooleanLiteral(bool),
     Paren(Box<AST>),
     VariableDeclaration(String, bool),
     FunctionLiteral(FunctionLiteral),
     FunctionCall(Box<AST>, Block),
     If(IfLiteral),
-    While(Box<AST>, Block),
+    While(WhileLiteral),
+    For(ForLiteral),
+    Range(RangeLiteral),
     OpPls(Box<AST>, Box<AST>),    // +
     OpMns(Box<AST>, Box<AST>),    // -
     OpTimes(Box<AST>, Box<AST>),  // *
@@ -232,10 +544,27 @@ pub enum ASTType {
     OpLt(Box<AST>, Box<AST>),     // <
     OpGtEq(Box<AST>, Box<AST>),   // >=
     OpLtEq(Box<AST>, Box<AST>),   // <=
-    VariableAccess(String),
+    OpIn(Box<AST>, Box<AST>),     // in
+    // delete obj.key or delete obj[key]
+    Delete(Box<AST>),
+    // the name and where the resolver found it
+    VariableAccess(String, Address),
     Return(Box<AST>),
-    Break,
-    Continue,
+    Break(Option<String>),
+    Continue(Option<String>),
+    // quote { } gives back its code as a value, unquote(x) splices a value into that code
+    Quote(Block),
+    Unquote(Box<AST>),
+    // the statements an expanded macro turned into, run in the scope of the call
+    Splice(Block),
+    // macros are expanded while parsing, these are kept so that tools can still see the source
+    Macro(String, FunctionLiteral),
+    MacroCall(Box<AST>, Box<AST>), // call, expansion
+    Comptime(ComptimeLiteral),
+    Enum(EnumLiteral),
+    Struct(StructLiteral),
+    Match(MatchLiteral),
+    Await(Box<AST>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -248,11 +577,127 @@ impl AST {
     pub fn get_line(&self) -> usize {
         self.token.line + 1
     }
+    // every direct child of this node, used by macros to walk and rewrite code
+    pub fn children_mut(&mut self) -> Vec<&mut Box<AST>> {
+        match &mut self.t {
+            ASTType::Import(_)
+            | ASTType::CharacterLiteral(_)
+            | ASTType::StringLiteral(_)
+            | ASTType::NumberLiteral(_)
+            | ASTType::IntLiteral(_)
+            | ASTType::BooleanLiteral(_)
+            | ASTType::VariableDeclaration(_, _)
+            | ASTType::VariableAccess(_, _)
+            | ASTType::Break(_)
+            | ASTType::Continue(_)
+            | ASTType::Enum(_)
+            | ASTType::Struct(_) => vec![],
+            ASTType::DotAccess(v, _)
+            | ASTType::Paren(v)
+            | ASTType::OpMnsPrefix(v)
+            | ASTType::OpNot(v)
+            | ASTType::Return(v)
+            | ASTType::Unquote(v)
+            | ASTType::Await(v)
+            | ASTType::Delete(v)
+            | ASTType::MacroCall(_, v) => vec![v],
+            ASTType::Comptime(lit) => vec![&mut lit.value],
+            ASTType::BracketAccess(l, r)
+            | ASTType::OpPls(l, r)
+            | ASTType::OpMns(l, r)
+            | ASTType::OpTimes(l, r)
+            | ASTType::OpDiv(l, r)
+            | ASTType::OpEq(l, r)
+            | ASTType::OpEqEq(l, r)
+            | ASTType::OpPlsEq(l, r)
+            | ASTType::OpNotEq(l, r)
+            | ASTType::OpAndAnd(l, r)
+            | ASTType::OpOrOr(l, r)
+            | ASTType::OpGt(l, r)
+            | ASTType::OpLt(l, r)
+            | ASTType::OpGtEq(l, r)
+            | ASTType::OpLtEq(l, r)
+            | ASTType::OpIn(l, r) => vec![l, r],
+            ASTType::ObjectLiteral(fields) => {
+                let mut ret = vec![];
+                for (key, value) in fields.iter_mut() {
+                    if let FieldKey::Computed(key) = key {
+                        ret.push(key);
+                    }
+                    ret.push(value);
+                }
+                ret
+            }
+            ASTType::ArrayLiteral(block) | ASTType::Quote(block) | ASTType::Splice(block) => {
+                block.iter_mut().collect()
+            }
+            ASTType::FunctionLiteral(func) | ASTType::Macro(_, func) => {
+                func.body.iter_mut().collect()
+            }
+            ASTType::FunctionCall(func, params) => {
+                let mut ret = vec![func];
+                ret.extend(params.iter_mut());
+                ret
+            }
+            ASTType::If(if_lit) => {
+                let mut ret = vec![&mut if_lit.cond];
+                ret.extend(if_lit.body.iter_mut());
+                for (cond, body) in if_lit.elseifs.iter_mut() {
+                    ret.push(cond);
+                    ret.extend(body.iter_mut());
+                }
+                if let Some(else_body) = &mut if_lit.else_body {
+                    ret.extend(else_body.iter_mut());
+                }
+                ret
+            }
+            ASTType::While(while_lit) => {
+                let mut ret: Vec<&mut Box<AST>> = while_lit.cond.iter_mut().collect();
+                ret.extend(while_lit.body.iter_mut());
+                if let Some(else_body) = &mut while_lit.else_body {
+                    ret.extend(else_body.iter_mut());
+                }
+                ret
+            }
+            ASTType::For(for_lit) => {
+                let mut ret = vec![&mut for_lit.iter];
+                ret.extend(for_lit.body.iter_mut());
+                ret
+            }
+            ASTType::Slice(v, start, end) => {
+                let mut ret = vec![v];
+                ret.extend(start.iter_mut());
+                ret.extend(end.iter_mut());
+                ret
+            }
+            ASTType::Range(range_lit) => {
+                let mut ret = vec![&mut range_lit.start, &mut range_lit.end];
+                ret.extend(range_lit.step.iter_mut());
+                ret
+            }
+            ASTType::Match(match_lit) => {
+                let mut ret = vec![&mut match_lit.value];
+                for arm in match_lit.arms.iter_mut() {
+                    ret.push(&mut arm.pattern);
+                    ret.extend(arm.body.iter_mut());
+                }
+                ret
+            }
+        }
+    }
+    // points this node and everything under it at the given token, so that errors in
+    //  expanded macros show up at the call
+    pub fn set_token(&mut self, token: &Token) {
+        self.token = token.clone();
+        for child in self.children_mut() {
+            child.set_token(token);
+        }
+    }
 
     pub fn get_value(&self, scope_chain: &mut ScopeChain) -> Result<Rc<Value>, Box<RuntimeError>> {
         let line = self.get_line();
         let ret = match &self.t {
-            ASTType::Import(s) => eval_import(s.clone()),
+            ASTType::Import(s) => eval_import(s.clone(), scope_chain, line),
             ASTType::ArrayLiteral(arr) => {
                 let mut obj = Object::new();
                 let mut i = 0;
@@ -270,12 +715,23 @@ impl AST {
             ASTType::ObjectLiteral(arr) => {
                 let mut obj = Object::new();
                 for (key, value) in arr.iter() {
+                    let key = match key {
+                        FieldKey::Static(key) => key.clone(),
+                        FieldKey::Computed(key) => {
+                            let key_val = key.get_value(scope_chain)?.unpack_and_transform(
+                                scope_chain,
+                                key.get_line(),
+                                key,
+                            )?;
+                            value_to_key(&key_val, scope_chain, key.get_line())?
+                        }
+                    };
                     let value = value.get_value(scope_chain)?.unpack_and_transform(
                         scope_chain,
                         value.get_line(),
                         value,
                     )?;
-                    obj.set(key.clone(), value);
+                    obj.set(key, value);
                 }
                 Ok(Rc::new(Value::Object(obj)))
             }
@@ -290,29 +746,22 @@ impl AST {
                     value.get_line(),
                     value,
                 )?;
-                let key = match val.as_ref() {
-                    Value::Number(n) => ObjectKey::Number(*n),
-                    Value::String(s) => ObjectKey::String(s.clone()),
-                    Value::Char(c) => ObjectKey::String(c.to_string()),
-                    v => return Err(Box::new(RuntimeError::new(format!("Cannot use type {} as an object key, can only use char, string, or number types", v.pretty_type(scope_chain, line)), line))),
-                };
-                Ok(Rc::new(Value::ObjectAccess(
+                let left_val =
                     left.get_value(scope_chain)?
-                        .unpack_and_transform(scope_chain, line, self)?,
-                    key,
-                )))
+                        .unpack_and_transform(scope_chain, line, self)?;
+                index_value(left_val, &val, false, scope_chain, line)
             }
             ASTType::Return(v) => eval_return(v, scope_chain),
-            ASTType::Break => match scope_chain
-                .set_return_register(ReturnType::Break)
+            ASTType::Break(label) => match scope_chain
+                .set_return_register(ReturnType::Break(label.clone()))
                 .to_runtime_error()
             {
                 Ok(_) => Ok(Rc::new(Value::Undefined)),
                 Err(e) => return Err(e),
             },
-            ASTType::Continue => {
+            ASTType::Continue(label) => {
                 scope_chain
-                    .set_return_register(ReturnType::Continue)
+                    .set_return_register(ReturnType::Continue(label.clone()))
                     .to_runtime_error()?;
                 Ok(Rc::new(Value::Undefined))
             }
@@ -320,57 +769,30 @@ impl AST {
                 let func =
                     func.get_value(scope_chain)
                         .unpack_and_transform(scope_chain, line, self)?;
-                match func.as_ref() {
-                    Value::Function(func) => func.call(scope_chain, params, line),
-                    Value::BuiltinFunction(f, arg_len) => {
-                        if arg_len != &params.len() {
-                            return Err(Box::new(RuntimeError::new(
-                                format!("Expected {} arguments, got {}", arg_len, params.len()),
-                                line,
-                            )));
-                        }
-                        // convert params to values
-                        let mut params = params
-                            .iter()
-                            .map(|ast| {
-                                ast.get_value(scope_chain).unpack_and_transform(
-                                    scope_chain,
-                                    ast.get_line(),
-                                    ast,
-                                )
-                            })
-                            .collect::<Vec<Result<Rc<Value>, Box<RuntimeError>>>>();
-
-                        let mut actual_params = vec![];
-                        for param in params.iter_mut() {
-                            match param {
-                                Ok(v) => actual_params.push(v.clone()),
-                                Err(e) => return Err(e.clone()),
-                            }
-                        }
-                        Ok(f(actual_params, self, scope_chain, self.get_line())?)
-                    }
-
-                    _ => {
-                        return Err(Box::new(RuntimeError::new(
-                            "Cannot call a non-function".into(),
-                            line,
-                        )))
-                    }
-                }
+                call_value(func, params, self, scope_chain, line)
             }
             ASTType::BooleanLiteral(b) => Ok(Rc::new(Value::Boolean(*b))),
             ASTType::If(if_lit) => eval_if(if_lit, &self, scope_chain),
-            ASTType::While(cond, block) => eval_while(cond, block, scope_chain),
+            ASTType::While(while_lit) => eval_while(while_lit, scope_chain),
+            ASTType::For(for_lit) => eval_for(for_lit, scope_chain, line),
+            ASTType::Range(range_lit) => eval_range(range_lit, scope_chain, line),
+            ASTType::Slice(v, start, end) => eval_slice(v, start, end, scope_chain, line),
             ASTType::FunctionLiteral(f) => Ok(Rc::new(Value::Function(f.make_real(scope_chain)))),
             ASTType::StringLiteral(str) => Ok(Rc::new(Value::String(str.to_string()))),
             ASTType::NumberLiteral(num) => Ok(Rc::new(Value::Number(*num))),
+            ASTType::IntLiteral(num) => Ok(Rc::new(Value::Int(*num))),
             ASTType::VariableDeclaration(name, is_const) => {
                 match scope_chain
                     .add_variable(name, *is_const, self.get_line())
                     .to_runtime_error()
                 {
-                    Ok(_) => Ok(Rc::new(Value::Variable(name.clone()))),
+                    Ok(slot) => Ok(Rc::new(Value::Variable(
+                        name.clone(),
+                        Address::Local {
+                            depth: 0,
+                            slot: *slot,
+                        },
+                    ))),
                     Err(e) => return Err(e),
                 }
             }
@@ -389,10 +811,27 @@ impl AST {
             ASTType::OpLt(left, right) => eval_op_lt(left, right, scope_chain),
             ASTType::OpGtEq(left, right) => eval_op_gteq(left, right, scope_chain),
             ASTType::OpLtEq(left, right) => eval_op_lteq(left, right, scope_chain),
+            ASTType::OpIn(left, right) => eval_op_in(left, right, scope_chain),
+            ASTType::Delete(v) => eval_delete(v, scope_chain),
             ASTType::CharacterLiteral(c) => Ok(Rc::new(Value::Char(*c))),
             ASTType::OpEqEq(left, right) => eval_op_eqeq(left, right, scope_chain),
-            ASTType::VariableAccess(name) => Ok(Value::Variable(name.clone()).into()),
+            ASTType::VariableAccess(name, address) => {
+                Ok(Value::Variable(name.clone(), *address).into())
+            }
             ASTType::Paren(ast) => ast.get_value(scope_chain),
+            ASTType::Quote(block) => eval_quote(block, scope_chain),
+            ASTType::Unquote(_) => Err(Box::new(RuntimeError::new(
+                "unquote can only be used inside of quote".into(),
+                line,
+            ))),
+            ASTType::Splice(block) => eval_splice(block, scope_chain),
+            ASTType::Macro(_, _) => Ok(Rc::new(Value::Undefined)),
+            ASTType::MacroCall(_, expansion) => expansion.get_value(scope_chain),
+            ASTType::Comptime(lit) => lit.value.get_value(scope_chain),
+            ASTType::Enum(enum_lit) => eval_enum(enum_lit, scope_chain, line),
+            ASTType::Struct(struct_lit) => eval_struct(struct_lit, scope_chain, line),
+            ASTType::Match(match_lit) => eval_match(match_lit, self, scope_chain),
+            ASTType::Await(v) => eval_await(v, scope_chain),
         };
 
         match ret {
@@ -404,6 +843,26 @@ impl AST {
         }
     }
 
+    // the left side of an = or +=, evaluated like any other value except that indexing it
+    //  doesn't go through __index
+    pub fn get_target(&self, scope_chain: &mut ScopeChain) -> Result<Rc<Value>, Box<RuntimeError>> {
+        let ASTType::BracketAccess(left, value) = &self.t else {
+            return self.get_value(scope_chain);
+        };
+        let line = self.get_line();
+        let val = value.get_value(scope_chain)?.unpack_and_transform(
+            scope_chain,
+            value.get_line(),
+            value,
+        )?;
+        let left_val =
+            left.get_value(scope_chain)?
+                .unpack_and_transform(scope_chain, line, self)?;
+        index_value(left_val, &val, true, scope_chain, line).map_err(|mut e| {
+            e.add_base_ast(self.clone());
+            e
+        })
+    }
     pub fn interpret(&self, scope_chain: &mut ScopeChain) -> Result<ReturnType, Box<RuntimeError>> {
         self.get_value(scope_chain)?;
         match scope_chain.get_return_register() {
@@ -411,15 +870,27 @@ impl AST {
             //     RuntimeError::new("Return statement at top level".into(), self.get_line())
             //         .add_base_ast(self.clone()),
             // )),
-            ReturnType::Continue => Err(Box::new(
+            ReturnType::Continue(_) => Err(Box::new(
                 RuntimeError::new("Continue statement at top level".into(), self.get_line())
                     .add_base_ast(self.clone()),
             )),
-            ReturnType::Break => Err(Box::new(
+            ReturnType::Break(_) => Err(Box::new(
                 RuntimeError::new("Break statement at top level".into(), self.get_line())
                     .add_base_ast(self.clone()),
             )),
             ReturnType::Return(v) => Ok(ReturnType::Return(v)),
+            // there's no frame to reuse at the top level, so the call just runs here
+            ReturnType::TailCall(func, args) => {
+                scope_chain.eat_return_register();
+                let v = match func.as_ref() {
+                    Value::Function(f) => f.call_values(scope_chain, args, self.get_line())?,
+                    _ => Rc::new(Value::Undefined),
+                };
+                scope_chain
+                    .set_return_register(ReturnType::Return(v.clone()))
+                    .to_runtime_error()?;
+                Ok(ReturnType::Return(v))
+            }
             ReturnType::None => Ok(ReturnType::None),
         }
     }
@@ -440,10 +911,22 @@ impl AST {
             ASTType::BracketAccess(l, v) => {
                 format!("{}[{}]", l.debug_pretty_print(), v.debug_pretty_print())
             }
+            ASTType::Slice(v, start, end) => format!(
+                "{}[{}:{}]",
+                v.debug_pretty_print(),
+                start.as_ref().map_or("".into(), |s| s.debug_pretty_print()),
+                end.as_ref().map_or("".into(), |e| e.debug_pretty_print())
+            ),
             ASTType::DotAccess(l, v) => format!("{}.{}", l.debug_pretty_print(), v),
             ASTType::Return(v) => format!("return {}", v.debug_pretty_print()),
-            ASTType::Break => "break".to_string(),
-            ASTType::Continue => "continue".to_string(),
+            ASTType::Break(label) => match label {
+                Some(label) => format!("break {}", label),
+                None => "break".to_string(),
+            },
+            ASTType::Continue(label) => match label {
+                Some(label) => format!("continue {}", label),
+                None => "continue".to_string(),
+            },
             ASTType::FunctionCall(func, params) => {
                 format!(
                     "({}({}))",
@@ -456,21 +939,14 @@ impl AST {
                 )
             }
             ASTType::BooleanLiteral(b) => b.to_string(),
-            ASTType::While(c, block) => {
-                format!(
-                    "while {} {{\n{}\n}}",
-                    c.debug_pretty_print(),
-                    block
-                        .iter()
-                        .map(|ast| format!("    {}\n", ast.debug_pretty_print()))
-                        .collect::<Vec<String>>()
-                        .join("")
-                )
-            }
+            ASTType::While(while_lit) => while_lit.pretty_print(),
+            ASTType::For(for_lit) => for_lit.pretty_print(),
+            ASTType::Range(range_lit) => range_lit.pretty_print(),
             ASTType::If(if_lit) => if_lit.pretty_print(),
             ASTType::FunctionLiteral(func) => func.pretty_print(),
             ASTType::StringLiteral(value) => format!("\"{}\"", value),
-            ASTType::NumberLiteral(value) => value.to_string(),
+            ASTType::NumberLiteral(value) => format!("{:?}", value),
+            ASTType::IntLiteral(value) => value.to_string(),
             ASTType::OpAndAnd(left, right) => {
                 format!(
                     "({} && {})",
@@ -546,6 +1022,14 @@ impl AST {
                     right.debug_pretty_print()
                 )
             }
+            ASTType::OpIn(left, right) => {
+                format!(
+                    "({} in {})",
+                    left.debug_pretty_print(),
+                    right.debug_pretty_print()
+                )
+            }
+            ASTType::Delete(v) => format!("delete {}", v.debug_pretty_print()),
             ASTType::VariableDeclaration(name, is_const) if !*is_const => format!("var {}", name),
             ASTType::VariableDeclaration(name, _) => format!("const {}", name),
             ASTType::OpEq(left, right) => {
@@ -570,8 +1054,33 @@ impl AST {
                     right.debug_pretty_print()
                 )
             }
-            ASTType::VariableAccess(name) => name.to_string(),
+            ASTType::VariableAccess(name, _) => name.to_string(),
             ASTType::Paren(ast) => format!("({})", ast.debug_pretty_print()),
+            ASTType::Quote(block) => format!(
+                "quote {{\n{}\n}}",
+                block
+                    .iter()
+                    .map(|ast| format!("    {}\n", ast.debug_pretty_print()))
+                    .collect::<Vec<String>>()
+                    .join("")
+            ),
+            ASTType::Unquote(ast) => format!("unquote({})", ast.debug_pretty_print()),
+            ASTType::Await(ast) => format!("(await {})", ast.debug_pretty_print()),
+            ASTType::Macro(name, func) => format!(
+                "macro {}{}",
+                name,
+                func.pretty_print().trim_start_matches("fn ")
+            ),
+            ASTType::MacroCall(call, _) => call.debug_pretty_print(),
+            ASTType::Comptime(lit) => lit.pretty_print(),
+            ASTType::Enum(enum_lit) => enum_lit.pretty_print(),
+            ASTType::Struct(struct_lit) => struct_lit.pretty_print(),
+            ASTType::Match(match_lit) => match_lit.pretty_print(),
+            ASTType::Splice(block) => block
+                .iter()
+                .map(|ast| ast.debug_pretty_print())
+                .collect::<Vec<String>>()
+                .join("\n"),
         }
     }
 
@@ -593,8 +1102,9 @@ impl AST {
                     ret += &format!(
                         "  {} = {},\n",
                         match key {
-                            ObjectKey::String(s) => format!("{}", s),
-                            ObjectKey::Number(n) => n.to_string(),
+                            FieldKey::Static(ObjectKey::String(s)) => format!("{}", s),
+                            FieldKey::Static(ObjectKey::Number(n)) => n.to_string(),
+                            FieldKey::Computed(key) => format!("[{}]", key.pretty_print()),
                         },
                         value.pretty_print()
                     );
@@ -606,10 +1116,22 @@ impl AST {
             ASTType::BracketAccess(l, v) => {
                 format!("{}[{}]", l.pretty_print(), v.pretty_print())
             }
+            ASTType::Slice(v, start, end) => format!(
+                "{}[{}:{}]",
+                v.pretty_print(),
+                start.as_ref().map_or("".into(), |s| s.pretty_print()),
+                end.as_ref().map_or("".into(), |e| e.pretty_print())
+            ),
             ASTType::DotAccess(l, v) => format!("{}.{}", l.pretty_print(), v),
             ASTType::Return(v) => format!("return {}", v.pretty_print()),
-            ASTType::Break => "break".to_string(),
-            ASTType::Continue => "continue".to_string(),
+            ASTType::Break(label) => match label {
+                Some(label) => format!("break {}", label),
+                None => "break".to_string(),
+            },
+            ASTType::Continue(label) => match label {
+                Some(label) => format!("continue {}", label),
+                None => "continue".to_string(),
+            },
             ASTType::BooleanLiteral(b) => b.to_string(),
             ASTType::FunctionCall(func, params) => {
                 format!(
@@ -622,21 +1144,14 @@ impl AST {
                         .join(", ")
                 )
             }
-            ASTType::While(c, block) => {
-                format!(
-                    "while {} {{\n{}\n}}",
-                    c.pretty_print(),
-                    block
-                        .iter()
-                        .map(|ast| format!("    {}\n", ast.pretty_print()))
-                        .collect::<Vec<String>>()
-                        .join("")
-                )
-            }
+            ASTType::While(while_lit) => while_lit.pretty_print(),
+            ASTType::For(for_lit) => for_lit.pretty_print(),
+            ASTType::Range(range_lit) => range_lit.pretty_print(),
             ASTType::If(if_lit) => if_lit.pretty_print(),
             ASTType::FunctionLiteral(func) => func.pretty_print(),
             ASTType::StringLiteral(value) => format!("\"{}\"", value),
-            ASTType::NumberLiteral(value) => value.to_string(),
+            ASTType::NumberLiteral(value) => format!("{:?}", value),
+            ASTType::IntLiteral(value) => value.to_string(),
             ASTType::OpAndAnd(left, right) => {
                 format!("{} && {}", left.pretty_print(), right.pretty_print())
             }
@@ -676,6 +1191,10 @@ impl AST {
             ASTType::OpLtEq(left, right) => {
                 format!("{} <= {}", left.pretty_print(), right.pretty_print())
             }
+            ASTType::OpIn(left, right) => {
+                format!("{} in {}", left.pretty_print(), right.pretty_print())
+            }
+            ASTType::Delete(v) => format!("delete {}", v.pretty_print()),
             ASTType::VariableDeclaration(name, is_const) if !is_const => format!("var {}", name),
             ASTType::VariableDeclaration(name, _) => format!("const {}", name),
             ASTType::OpEq(left, right) => {
@@ -688,8 +1207,33 @@ impl AST {
             ASTType::OpNotEq(left, right) => {
                 format!("{} != {}", left.pretty_print(), right.pretty_print())
             }
-            ASTType::VariableAccess(name) => name.to_string(),
+            ASTType::VariableAccess(name, _) => name.to_string(),
             ASTType::Paren(ast) => format!("({})", ast.pretty_print()),
+            ASTType::Quote(block) => format!(
+                "quote {{\n{}\n}}",
+                block
+                    .iter()
+                    .map(|ast| format!("    {}\n", ast.pretty_print()))
+                    .collect::<Vec<String>>()
+                    .join("")
+            ),
+            ASTType::Unquote(ast) => format!("unquote({})", ast.pretty_print()),
+            ASTType::Await(ast) => format!("await {}", ast.pretty_print()),
+            ASTType::Macro(name, func) => format!(
+                "macro {}{}",
+                name,
+                func.pretty_print().trim_start_matches("fn ")
+            ),
+            ASTType::MacroCall(call, _) => call.pretty_print(),
+            ASTType::Comptime(lit) => lit.pretty_print(),
+            ASTType::Enum(enum_lit) => enum_lit.pretty_print(),
+            ASTType::Struct(struct_lit) => struct_lit.pretty_print(),
+            ASTType::Match(match_lit) => match_lit.pretty_print(),
+            ASTType::Splice(block) => block
+                .iter()
+                .map(|ast| ast.pretty_print())
+                .collect::<Vec<String>>()
+                .join("\n"),
         }
     }
 }
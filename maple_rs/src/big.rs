@@ -0,0 +1,472 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::parser::Value;
+
+// each limb holds 9 decimal digits, which keeps printing and parsing simple
+const BASE: u64 = 1_000_000_000;
+const BASE_DIGITS: usize = 9;
+// digits kept after the decimal point when dividing decimals with /
+pub const DEFAULT_DIV_SCALE: u32 = 16;
+
+// arbitrary precision integer, limbs are little endian and never have trailing zeros
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+fn cmp_mag(a: &[u32], b: &[u32]) -> Ordering {
+    if a.len() != b.len() {
+        return a.len().cmp(&b.len());
+    }
+    for i in (0..a.len()).rev() {
+        if a[i] != b[i] {
+            return a[i].cmp(&b[i]);
+        }
+    }
+    Ordering::Equal
+}
+fn add_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut ret = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let sum = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        ret.push((sum % BASE) as u32);
+        carry = sum / BASE;
+    }
+    if carry > 0 {
+        ret.push(carry as u32);
+    }
+    ret
+}
+// a has to be at least as big as b
+fn sub_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut ret = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, x) in a.iter().enumerate() {
+        let mut diff = *x as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += BASE as i64;
+            borrow = 1;
+        }
+        ret.push(diff as u32);
+    }
+    trim(&mut ret);
+    ret
+}
+fn mul_mag(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let mut ret = vec![0u64; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, y) in b.iter().enumerate() {
+            let cur = ret[i + j] + *x as u64 * *y as u64 + carry;
+            ret[i + j] = cur % BASE;
+            carry = cur / BASE;
+        }
+        ret[i + b.len()] += carry;
+    }
+    let mut ret = ret.into_iter().map(|x| x as u32).collect();
+    trim(&mut ret);
+    ret
+}
+fn mul_small(a: &[u32], b: u32) -> Vec<u32> {
+    mul_mag(a, &[b])
+}
+// long division, each quotient limb is found with a binary search
+fn divmod_mag(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0; a.len()];
+    let mut rem: Vec<u32> = vec![];
+    for i in (0..a.len()).rev() {
+        rem.insert(0, a[i]);
+        trim(&mut rem);
+        let (mut lo, mut hi) = (0u32, (BASE - 1) as u32);
+        while lo < hi {
+            let mid = lo + (hi - lo).div_ceil(2);
+            if cmp_mag(&mul_small(b, mid), &rem) == Ordering::Greater {
+                hi = mid - 1;
+            } else {
+                lo = mid;
+            }
+        }
+        quotient[i] = lo;
+        rem = sub_mag(&rem, &mul_small(b, lo));
+    }
+    trim(&mut quotient);
+    (quotient, rem)
+}
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+        BigInt {
+            negative: false,
+            limbs: vec![],
+        }
+    }
+    fn from_parts(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        trim(&mut limbs);
+        BigInt {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+    pub fn from_i64(n: i64) -> BigInt {
+        let mut mag = n.unsigned_abs();
+        let mut limbs = vec![];
+        while mag > 0 {
+            limbs.push((mag % BASE) as u32);
+            mag /= BASE;
+        }
+        BigInt::from_parts(n < 0, limbs)
+    }
+    // accepts an optional sign followed by decimal digits
+    pub fn parse(s: &str) -> Option<BigInt> {
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let mut limbs = vec![];
+        let mut end = digits.len();
+        while end > 0 {
+            let start = end.saturating_sub(BASE_DIGITS);
+            limbs.push(digits[start..end].parse::<u32>().ok()?);
+            end = start;
+        }
+        Some(BigInt::from_parts(negative, limbs))
+    }
+    pub fn pow10(n: u32) -> BigInt {
+        let mut limbs = vec![0; n as usize / BASE_DIGITS];
+        limbs.push(10u32.pow(n % BASE_DIGITS as u32));
+        BigInt::from_parts(false, limbs)
+    }
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+    fn is_odd(&self) -> bool {
+        self.limbs.first().map(|l| l % 2 == 1).unwrap_or(false)
+    }
+    pub fn to_i64(&self) -> Option<i64> {
+        let mut mag: i128 = 0;
+        for limb in self.limbs.iter().rev() {
+            mag = mag * BASE as i128 + *limb as i128;
+            if mag > i64::MAX as i128 + 1 {
+                return None;
+            }
+        }
+        i64::try_from(if self.negative { -mag } else { mag }).ok()
+    }
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+    pub fn neg(&self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.limbs.clone())
+    }
+    pub fn abs(&self) -> BigInt {
+        BigInt::from_parts(false, self.limbs.clone())
+    }
+    pub fn add(&self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::from_parts(self.negative, add_mag(&self.limbs, &other.limbs));
+        }
+        match cmp_mag(&self.limbs, &other.limbs) {
+            Ordering::Less => {
+                BigInt::from_parts(other.negative, sub_mag(&other.limbs, &self.limbs))
+            }
+            _ => BigInt::from_parts(self.negative, sub_mag(&self.limbs, &other.limbs)),
+        }
+    }
+    pub fn sub(&self, other: &BigInt) -> BigInt {
+        self.add(&other.neg())
+    }
+    pub fn mul(&self, other: &BigInt) -> BigInt {
+        BigInt::from_parts(
+            self.negative != other.negative,
+            mul_mag(&self.limbs, &other.limbs),
+        )
+    }
+    // truncates towards zero, the remainder takes the sign of self
+    pub fn div_rem(&self, other: &BigInt) -> Option<(BigInt, BigInt)> {
+        if other.is_zero() {
+            return None;
+        }
+        let (q, r) = divmod_mag(&self.limbs, &other.limbs);
+        Some((
+            BigInt::from_parts(self.negative != other.negative, q),
+            BigInt::from_parts(self.negative, r),
+        ))
+    }
+    // divides and rounds half to even, which is what money wants
+    pub fn div_round(&self, other: &BigInt) -> Option<BigInt> {
+        let (q, r) = self.div_rem(other)?;
+        let one = BigInt::from_i64(if self.negative != other.negative { -1 } else { 1 });
+        match r.abs().mul(&BigInt::from_i64(2)).cmp(&other.abs()) {
+            Ordering::Greater => Some(q.add(&one)),
+            Ordering::Equal if q.is_odd() => Some(q.add(&one)),
+            _ => Some(q),
+        }
+    }
+}
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_mag(&self.limbs, &other.limbs),
+            (true, true) => cmp_mag(&other.limbs, &self.limbs),
+        }
+    }
+}
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        write!(f, "{}", self.limbs[self.limbs.len() - 1])?;
+        for limb in self.limbs.iter().rev().skip(1) {
+            write!(f, "{:09}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+// exact decimal, the value is unscaled / 10^scale
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BigDecimal {
+    unscaled: BigInt,
+    scale: u32,
+}
+
+impl BigDecimal {
+    pub fn new(unscaled: BigInt, scale: u32) -> BigDecimal {
+        BigDecimal { unscaled, scale }
+    }
+    pub fn from_int(n: &BigInt) -> BigDecimal {
+        BigDecimal::new(n.clone(), 0)
+    }
+    // accepts things like -12, 0.5 and 12.340, the scale is kept as written
+    pub fn parse(s: &str) -> Option<BigDecimal> {
+        match s.split_once('.') {
+            Some((whole, frac)) => {
+                if frac.is_empty() || !frac.bytes().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+                let whole = match whole {
+                    "" | "-" | "+" => format!("{}0", whole),
+                    _ => whole.to_string(),
+                };
+                let unscaled = BigInt::parse(&format!("{}{}", whole, frac))?;
+                Some(BigDecimal::new(unscaled, frac.len() as u32))
+            }
+            None => Some(BigDecimal::from_int(&BigInt::parse(s)?)),
+        }
+    }
+    pub fn get_scale(&self) -> u32 {
+        self.scale
+    }
+    fn rescale(&self, scale: u32) -> BigInt {
+        self.unscaled.mul(&BigInt::pow10(scale - self.scale))
+    }
+    fn align(&self, other: &BigDecimal) -> (BigInt, BigInt, u32) {
+        let scale = self.scale.max(other.scale);
+        (self.rescale(scale), other.rescale(scale), scale)
+    }
+    pub fn neg(&self) -> BigDecimal {
+        BigDecimal::new(self.unscaled.neg(), self.scale)
+    }
+    pub fn add(&self, other: &BigDecimal) -> BigDecimal {
+        let (a, b, scale) = self.align(other);
+        BigDecimal::new(a.add(&b), scale)
+    }
+    pub fn sub(&self, other: &BigDecimal) -> BigDecimal {
+        let (a, b, scale) = self.align(other);
+        BigDecimal::new(a.sub(&b), scale)
+    }
+    pub fn mul(&self, other: &BigDecimal) -> BigDecimal {
+        BigDecimal::new(self.unscaled.mul(&other.unscaled), self.scale + other.scale)
+    }
+    // the result has exactly scale digits after the point, rounded half to even
+    pub fn div(&self, other: &BigDecimal, scale: u32) -> Option<BigDecimal> {
+        let num = self.unscaled.mul(&BigInt::pow10(other.scale + scale));
+        let den = other.unscaled.mul(&BigInt::pow10(self.scale));
+        Some(BigDecimal::new(num.div_round(&den)?, scale))
+    }
+    pub fn round(&self, scale: u32) -> BigDecimal {
+        if scale >= self.scale {
+            return BigDecimal::new(self.rescale(scale), scale);
+        }
+        let den = BigInt::pow10(self.scale - scale);
+        BigDecimal::new(self.unscaled.div_round(&den).unwrap(), scale)
+    }
+    // drops everything after the point
+    pub fn trunc(&self) -> BigInt {
+        self.unscaled.div_rem(&BigInt::pow10(self.scale)).unwrap().0
+    }
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+}
+impl Ord for BigDecimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b, _) = self.align(other);
+        a.cmp(&b)
+    }
+}
+impl PartialOrd for BigDecimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl fmt::Display for BigDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.scale == 0 {
+            return write!(f, "{}", self.unscaled);
+        }
+        let digits = self.unscaled.abs().to_string();
+        let digits = format!("{:0>width$}", digits, width = self.scale as usize + 1);
+        let (whole, frac) = digits.split_at(digits.len() - self.scale as usize);
+        if self.unscaled.is_negative() {
+            write!(f, "-")?;
+        }
+        write!(f, "{}.{}", whole, frac)
+    }
+}
+
+// two operands brought to a common big type, ints get promoted and floats are refused
+//  because mixing them in would lose the exactness
+pub enum BigPair {
+    Int(BigInt, BigInt),
+    Decimal(BigDecimal, BigDecimal),
+}
+
+impl BigPair {
+    pub fn from_values(left: &Value, right: &Value) -> Option<BigPair> {
+        match (left, right) {
+            (Value::BigInt(l), Value::BigInt(r)) => Some(BigPair::Int(l.clone(), r.clone())),
+            (Value::BigInt(l), Value::Int(r)) => Some(BigPair::Int(l.clone(), BigInt::from_i64(*r))),
+            (Value::Int(l), Value::BigInt(r)) => Some(BigPair::Int(BigInt::from_i64(*l), r.clone())),
+            (Value::BigDecimal(l), r) => Some(BigPair::Decimal(l.clone(), BigPair::to_decimal(r)?)),
+            (l, Value::BigDecimal(r)) => Some(BigPair::Decimal(BigPair::to_decimal(l)?, r.clone())),
+            _ => None,
+        }
+    }
+    fn to_decimal(value: &Value) -> Option<BigDecimal> {
+        match value {
+            Value::BigDecimal(d) => Some(d.clone()),
+            Value::BigInt(n) => Some(BigDecimal::from_int(n)),
+            Value::Int(n) => Some(BigDecimal::from_int(&BigInt::from_i64(*n))),
+            _ => None,
+        }
+    }
+    pub fn add(&self) -> Value {
+        match self {
+            BigPair::Int(l, r) => Value::BigInt(l.add(r)),
+            BigPair::Decimal(l, r) => Value::BigDecimal(l.add(r)),
+        }
+    }
+    pub fn sub(&self) -> Value {
+        match self {
+            BigPair::Int(l, r) => Value::BigInt(l.sub(r)),
+            BigPair::Decimal(l, r) => Value::BigDecimal(l.sub(r)),
+        }
+    }
+    pub fn mul(&self) -> Value {
+        match self {
+            BigPair::Int(l, r) => Value::BigInt(l.mul(r)),
+            BigPair::Decimal(l, r) => Value::BigDecimal(l.mul(r)),
+        }
+    }
+    // big ints divide like integers, decimals keep at least DEFAULT_DIV_SCALE digits
+    pub fn div(&self) -> Option<Value> {
+        match self {
+            BigPair::Int(l, r) => Some(Value::BigInt(l.div_rem(r)?.0)),
+            BigPair::Decimal(l, r) => {
+                let scale = l.scale.max(r.scale).max(DEFAULT_DIV_SCALE);
+                Some(Value::BigDecimal(l.div(r, scale)?))
+            }
+        }
+    }
+    pub fn cmp(&self) -> Ordering {
+        match self {
+            BigPair::Int(l, r) => l.cmp(r),
+            BigPair::Decimal(l, r) => l.cmp(r),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn int(s: &str) -> BigInt {
+        BigInt::parse(s).unwrap()
+    }
+    fn dec(s: &str) -> BigDecimal {
+        BigDecimal::parse(s).unwrap()
+    }
+
+    #[test]
+    fn int_round_trip() {
+        for s in ["0", "7", "-7", "1000000000", "-123456789012345678901234567890"] {
+            assert_eq!(int(s).to_string(), s);
+        }
+        assert_eq!(int("-0").to_string(), "0");
+        assert!(BigInt::parse("12a").is_none());
+        assert!(BigInt::parse("").is_none());
+    }
+    #[test]
+    fn int_arithmetic() {
+        let a = int("123456789012345678901234567890");
+        let b = int("-987654321098765432109876543210");
+        assert_eq!(a.add(&b).to_string(), "-864197532086419753208641975320");
+        assert_eq!(a.sub(&b).to_string(), "1111111110111111111011111111100");
+        assert_eq!(
+            a.mul(&b).to_string(),
+            "-121932631137021795226185032733622923332237463801111263526900"
+        );
+        let (q, r) = b.div_rem(&a).unwrap();
+        assert_eq!(q.to_string(), "-8");
+        assert_eq!(r.to_string(), "-9000000000900000000090");
+        assert!(a.div_rem(&BigInt::zero()).is_none());
+        assert_eq!(int("9223372036854775807").to_i64(), Some(i64::MAX));
+        assert_eq!(int("-9223372036854775808").to_i64(), Some(i64::MIN));
+        assert_eq!(int("9223372036854775808").to_i64(), None);
+    }
+    #[test]
+    fn decimal_arithmetic() {
+        assert_eq!(dec("0.1").add(&dec("0.2")).to_string(), "0.3");
+        assert_eq!(dec("1.50").mul(&dec("3")).to_string(), "4.50");
+        assert_eq!(dec("-0.05").sub(&dec("0.5")).to_string(), "-0.55");
+        assert_eq!(dec("1").div(&dec("3"), 4).unwrap().to_string(), "0.3333");
+        assert_eq!(dec("2").div(&dec("3"), 2).unwrap().to_string(), "0.67");
+        // ties go to the even neighbour
+        assert_eq!(dec("0.125").round(2).to_string(), "0.12");
+        assert_eq!(dec("0.135").round(2).to_string(), "0.14");
+        assert_eq!(dec("-2.5").round(0).to_string(), "-2");
+        assert_eq!(dec("1.5").cmp(&dec("1.50")), Ordering::Equal);
+        assert_eq!(dec(".5").to_string(), "0.5");
+        assert_eq!(dec("-7.89").trunc().to_string(), "-7");
+    }
+}
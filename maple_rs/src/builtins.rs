@@ -1,22 +1,78 @@
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use crate::{
     ast::{ConvertScopeErrorResult, AST},
+    big::{BigDecimal, BigInt},
     error::RuntimeError,
-    parser::{Unpack, Value, Object, ObjectKey},
+    event_loop::{Future, FutureState, IoResult},
+    parser::{EnumVariant, StructDef, Unpack, Value, Object, ObjectKey},
+    runtime::order_values,
     scopechain::ScopeChain,
 };
 
+// a field inside of an enum or struct, strings are quoted so that they stand out
+fn field_to_str(
+    field: &Rc<Value>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<String, Box<RuntimeError>> {
+    Ok(match field.as_ref() {
+        Value::String(s) => format!("{:?}", s),
+        Value::Char(c) => format!("{:?}", c),
+        _ => match builtin_to_str(vec![field.clone()], ast, scopechain, line)?.as_ref() {
+            Value::String(s) => s.clone(),
+            _ => unreachable!(),
+        },
+    })
+}
+// Shape.Circle(2) or Shape.Empty
+fn enum_to_str(
+    variant: &EnumVariant,
+    fields: &[Rc<Value>],
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<String, Box<RuntimeError>> {
+    let name = format!("{}.{}", variant.enum_name, variant.name);
+    if fields.is_empty() {
+        return Ok(name);
+    }
+    let mut strs = vec![];
+    for field in fields.iter() {
+        strs.push(field_to_str(field, ast, scopechain, line)?);
+    }
+    Ok(format!("{}({})", name, strs.join(", ")))
+}
+// Point { x = 1, y = 2 }
+fn struct_to_str(
+    def: &StructDef,
+    obj: &Object,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<String, Box<RuntimeError>> {
+    let mut strs = vec![];
+    for (key, value) in obj.fields.iter() {
+        strs.push(format!("{} = {}", key, field_to_str(value, ast, scopechain, line)?));
+    }
+    Ok(format!("{} {{ {} }}", def.name, strs.join(", ")))
+}
 fn builtin_println(
     args: Vec<Rc<Value>>,
     ast: &AST,
-    scopechain: &ScopeChain,
+    scopechain: &mut ScopeChain,
     line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     match args[0]
         .unpack_and_transform(scopechain, line, ast)?
         .as_ref()
     {
+        Value::Object(obj) if obj.struct_def.is_some() => {
+            let def = obj.struct_def.as_ref().unwrap();
+            println!("{}", struct_to_str(def, obj, ast, scopechain, line)?);
+        }
         &Value::Object(_) => todo!(),
 
         &Value::String(ref s) => {
@@ -25,6 +81,15 @@ fn builtin_println(
         &Value::Number(ref n) => {
             println!("{}", n);
         }
+        Value::Int(n) => {
+            println!("{}", n);
+        }
+        Value::BigInt(n) => {
+            println!("{}", n);
+        }
+        Value::BigDecimal(n) => {
+            println!("{}", n);
+        }
         &Value::Boolean(ref b) => {
             println!("{}", b);
         }
@@ -37,10 +102,30 @@ fn builtin_println(
         &Value::Function(ref lit) => {
             println!("{}", lit.pretty_print());
         }
+        Value::Quote(block) => {
+            for ast in block.iter() {
+                println!("{}", ast.pretty_print());
+            }
+        }
         &Value::Char(ref c) => {
             println!("{}", c);
         }
-        &Value::Variable(_) => {
+        Value::Enum(variant, fields) => {
+            println!("{}", enum_to_str(variant, fields, ast, scopechain, line)?);
+        }
+        Value::EnumConstructor(variant) => {
+            println!("constructor({}.{})", variant.enum_name, variant.name);
+        }
+        Value::StructConstructor(def) => {
+            println!("constructor({})", def.name);
+        }
+        Value::Future(_) => {
+            println!("future");
+        }
+        Value::Range(range) => {
+            println!("{}", range);
+        }
+        &Value::Variable(_, _) => {
             return Err(Box::new(RuntimeError::new(
                 "Cannot println variable".to_string(),
                 line,
@@ -59,13 +144,17 @@ fn builtin_println(
 fn builtin_print(
     args: Vec<Rc<Value>>,
     ast: &AST,
-    scopechain: &ScopeChain,
+    scopechain: &mut ScopeChain,
     line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     match args[0]
         .unpack_and_transform(scopechain, line, ast)?
         .as_ref()
     {
+        Value::Object(obj) if obj.struct_def.is_some() => {
+            let def = obj.struct_def.as_ref().unwrap();
+            print!("{}", struct_to_str(def, obj, ast, scopechain, line)?);
+        }
         &Value::Object(_) => todo!(),
 
         &Value::String(ref s) => {
@@ -74,6 +163,15 @@ fn builtin_print(
         &Value::Number(ref n) => {
             print!("{}", n);
         }
+        Value::Int(n) => {
+            print!("{}", n);
+        }
+        Value::BigInt(n) => {
+            print!("{}", n);
+        }
+        Value::BigDecimal(n) => {
+            print!("{}", n);
+        }
         &Value::Boolean(ref b) => {
             print!("{}", b);
         }
@@ -86,16 +184,38 @@ fn builtin_print(
         &Value::Function(ref lit) => {
             print!("{}", lit.pretty_print());
         }
+        Value::Quote(block) => {
+            let code = block
+                .iter()
+                .map(|ast| ast.pretty_print())
+                .collect::<Vec<String>>();
+            print!("{}", code.join("\n"));
+        }
         &Value::Char(ref c) => {
             print!("{}", c);
         }
+        Value::Enum(variant, fields) => {
+            print!("{}", enum_to_str(variant, fields, ast, scopechain, line)?);
+        }
+        Value::EnumConstructor(variant) => {
+            print!("constructor({}.{})", variant.enum_name, variant.name);
+        }
+        Value::StructConstructor(def) => {
+            print!("constructor({})", def.name);
+        }
+        Value::Future(_) => {
+            print!("future");
+        }
+        Value::Range(range) => {
+            print!("{}", range);
+        }
         &Value::ObjectAccess(_,_) => {
             return Err(Box::new(RuntimeError::new(
                 "Cannot print object access".to_string(),
                 line,
             )));
         }
-        &Value::Variable(_) => {
+        &Value::Variable(_, _) => {
             return Err(Box::new(RuntimeError::new(
                 "Cannot print variable".to_string(),
                 line,
@@ -108,7 +228,7 @@ fn builtin_print(
 fn builtin_concat(
     args: Vec<Rc<Value>>,
     ast: &AST,
-    scopechain: &ScopeChain,
+    scopechain: &mut ScopeChain,
     line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     match (
@@ -137,17 +257,26 @@ fn builtin_concat(
 pub fn builtin_sleep_nanos(
     args: Vec<Rc<Value>>,
     ast: &AST,
-    scopechain: &ScopeChain,
+    scopechain: &mut ScopeChain,
     line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     match 
         args[0].unpack_and_transform(scopechain, line, ast)?.as_ref() 
      {
-        Value::Number(ref n) => {
+        Value::Number(ref n) if *n >= 0.0 => {
             let nanos = n.round() as u64;
             std::thread::sleep(std::time::Duration::from_nanos(nanos));
 
         }
+        Value::Int(ref n) if *n >= 0 => {
+            std::thread::sleep(std::time::Duration::from_nanos(*n as u64));
+        }
+        Value::Number(_) | Value::Int(_) => {
+            return Err(Box::new(RuntimeError::new(
+                "Cannot sleep for a negative number of nanoseconds".to_string(),
+                line,
+            )));
+        }
         _ => {
             return Err(Box::new(RuntimeError::new(
                 "Cannot toStr variable".to_string(),
@@ -161,7 +290,7 @@ pub fn builtin_sleep_nanos(
 pub fn builtin_nanos(
     _args: Vec<Rc<Value>>,
     _ast: &AST,
-    _scopechain: &ScopeChain,
+    _scopechain: &mut ScopeChain,
     _line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     use std::time::SystemTime;
@@ -169,12 +298,178 @@ pub fn builtin_nanos(
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_nanos();
-    Ok(Rc::new(Value::Number(time as f64)))
+    Ok(Rc::new(Value::Int(time as i64)))
+}
+// a future that resolves once the given number of milliseconds have passed
+pub fn builtin_after(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let ms = match args[0].unpack_and_transform(scopechain, line, ast)?.as_ref() {
+        Value::Int(n) if *n >= 0 => *n as f64,
+        Value::Number(n) if *n >= 0.0 => *n,
+        v => {
+            return Err(Box::new(RuntimeError::new(
+                format!(
+                    "Expected a positive number of milliseconds, got {}",
+                    v.pretty_type(scopechain, line)
+                ),
+                line,
+            )))
+        }
+    };
+    // infinity or anything past what an Instant can hold would never resolve anyway
+    let deadline = Duration::try_from_secs_f64(ms / 1000.0)
+        .ok()
+        .and_then(|wait| Instant::now().checked_add(wait))
+        .ok_or_else(|| {
+            Box::new(RuntimeError::new(
+                format!("Cannot wait for {} milliseconds", ms),
+                line,
+            ))
+        })?;
+    Ok(Rc::new(Value::Future(Future::new(FutureState::Timer(deadline), line))))
+}
+// runs a function with no arguments as its own task, awaiting the future joins it
+pub fn builtin_task_spawn(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    match args[0].unpack_and_transform(scopechain, line, ast)?.as_ref() {
+        Value::Function(func) => {
+            let task = Future::new(FutureState::Pending(func.clone(), vec![]), line);
+            scopechain.get_event_loop().spawn(task.clone());
+            Ok(Rc::new(Value::Future(task)))
+        }
+        v => Err(Box::new(RuntimeError::new(
+            format!("Cannot spawn a task from {}", v.pretty_type(scopechain, line)),
+            line,
+        ))),
+    }
+}
+// a future that resolves to an array of every value once all of them have resolved
+pub fn builtin_task_all(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let arr = args[0].unpack_and_transform(scopechain, line, ast)?;
+    let obj = match arr.as_ref() {
+        Value::Object(obj) => obj,
+        v => {
+            return Err(Box::new(RuntimeError::new(
+                format!("Expected an array of futures, got {}", v.pretty_type(scopechain, line)),
+                line,
+            )))
+        }
+    };
+    let mut futures = vec![];
+    for (key, value) in obj.fields.iter() {
+        if let ObjectKey::Number(_) = key {
+            futures.push(match value.as_ref() {
+                Value::Future(future) => future.clone(),
+                // plain values are already done
+                _ => Future::new(FutureState::Done(value.clone()), line),
+            });
+        }
+    }
+    Ok(Rc::new(Value::Future(Future::new(FutureState::All { futures, finished: 0 }, line))))
+}
+// a string argument to one of the file or subprocess builtins
+fn string_arg(
+    arg: &Rc<Value>,
+    what: &str,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<String, Box<RuntimeError>> {
+    match arg.unpack_and_transform(scopechain, line, ast)?.as_ref() {
+        Value::String(s) => Ok(s.clone()),
+        v => Err(Box::new(RuntimeError::new(
+            format!("Expected a string for the {}, got {}", what, v.pretty_type(scopechain, line)),
+            line,
+        ))),
+    }
+}
+// a future that resolves to the contents of a file
+pub fn builtin_fs_read(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let path = string_arg(&args[0], "path", ast, scopechain, line)?;
+    let future = scopechain.get_event_loop().start_io(line, move || {
+        std::fs::read_to_string(&path)
+            .map(IoResult::Text)
+            .map_err(|e| format!("Cannot read {}: {}", path, e))
+    })?;
+    Ok(Rc::new(Value::Future(future)))
+}
+// a future that resolves once the string has been written to the file
+pub fn builtin_fs_write(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let path = string_arg(&args[0], "path", ast, scopechain, line)?;
+    let text = string_arg(&args[1], "contents", ast, scopechain, line)?;
+    let future = scopechain.get_event_loop().start_io(line, move || {
+        std::fs::write(&path, text)
+            .map(|_| IoResult::Nothing)
+            .map_err(|e| format!("Cannot write {}: {}", path, e))
+    })?;
+    Ok(Rc::new(Value::Future(future)))
+}
+// a future that resolves to { code, stdout, stderr } once the program exits
+pub fn builtin_proc_run(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let program = string_arg(&args[0], "program", ast, scopechain, line)?;
+    let arr = args[1].unpack_and_transform(scopechain, line, ast)?;
+    let mut program_args = vec![];
+    match arr.as_ref() {
+        Value::Object(obj) => {
+            for (key, value) in obj.fields.iter() {
+                if let ObjectKey::Number(_) = key {
+                    program_args.push(string_arg(value, "argument", ast, scopechain, line)?);
+                }
+            }
+        }
+        v => {
+            return Err(Box::new(RuntimeError::new(
+                format!("Expected an array of arguments, got {}", v.pretty_type(scopechain, line)),
+                line,
+            )))
+        }
+    }
+    let future = scopechain.get_event_loop().start_io(line, move || {
+        let output = std::process::Command::new(&program)
+            .args(&program_args)
+            .output()
+            .map_err(|e| format!("Cannot run {}: {}", program, e))?;
+        Ok(IoResult::Exited {
+            // killed by a signal, there's no code to give back
+            code: output.status.code().map_or(-1, |c| c as i64),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    })?;
+    Ok(Rc::new(Value::Future(future)))
 }
 pub fn builtin_to_str(
     args: Vec<Rc<Value>>,
     ast: &AST,
-    scopechain: &ScopeChain,
+    scopechain: &mut ScopeChain,
     line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     match 
@@ -189,6 +484,15 @@ pub fn builtin_to_str(
         Value::Number(ref n) => {
             Ok(Rc::new(Value::String(n.to_string())))
         }
+        Value::Int(ref n) => {
+            Ok(Rc::new(Value::String(n.to_string())))
+        }
+        Value::BigInt(ref n) => {
+            Ok(Rc::new(Value::String(n.to_string())))
+        }
+        Value::BigDecimal(ref n) => {
+            Ok(Rc::new(Value::String(n.to_string())))
+        }
         Value::Boolean(ref b) => {
             Ok(Rc::new(Value::String(b.to_string())))
         }
@@ -201,16 +505,42 @@ pub fn builtin_to_str(
         Value::Function(ref lit) => {
             Ok(Rc::new(Value::String(lit.pretty_print())))
         }
+        Value::Quote(block) => {
+            let code = block
+                .iter()
+                .map(|ast| ast.pretty_print())
+                .collect::<Vec<String>>();
+            Ok(Rc::new(Value::String(code.join("\n"))))
+        }
+        Value::Object(obj) if obj.struct_def.is_some() => {
+            let def = obj.struct_def.as_ref().unwrap();
+            Ok(Rc::new(Value::String(struct_to_str(def, obj, ast, scopechain, line)?)))
+        }
         Value::Object(_) => {
             Ok(Rc::new(Value::String("object".to_string())))
         }
+        Value::Enum(variant, fields) => {
+            Ok(Rc::new(Value::String(enum_to_str(variant, fields, ast, scopechain, line)?)))
+        }
+        Value::EnumConstructor(variant) => {
+            Ok(Rc::new(Value::String(format!("constructor({}.{})", variant.enum_name, variant.name))))
+        }
+        Value::StructConstructor(def) => {
+            Ok(Rc::new(Value::String(format!("constructor({})", def.name))))
+        }
+        Value::Future(_) => {
+            Ok(Rc::new(Value::String("future".to_string())))
+        }
+        Value::Range(range) => {
+            Ok(Rc::new(Value::String(range.to_string())))
+        }
         Value::ObjectAccess(_,_) => {
             return Err(Box::new(RuntimeError::new(
                 "Cannot toStr object access".to_string(),
                 line,
             )));
         }
-        Value::Variable(_) => {
+        Value::Variable(_, _) => {
             return Err(Box::new(RuntimeError::new(
                 "Cannot toStr variable".to_string(),
                 line,
@@ -221,10 +551,32 @@ pub fn builtin_to_str(
 
 }
 
+// -1, 0 or 1 for whether a comes before, with or after b, for sorting with
+pub fn builtin_cmp_compare(
+    args: Vec<Rc<Value>>,
+    _ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let ord = order_values(&args[0], &args[1], line, scopechain)?;
+    Ok(Rc::new(Value::Int(ord as i64)))
+}
+
+// whether both are the same value rather than equal ones, an object is only the same as
+//  itself and a variable that was set to it
+pub fn builtin_ref_same(
+    args: Vec<Rc<Value>>,
+    _ast: &AST,
+    _scopechain: &mut ScopeChain,
+    _line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    Ok(Rc::new(Value::Boolean(Rc::ptr_eq(&args[0], &args[1]))))
+}
+
 pub fn builtin_arr_len(
     args: Vec<Rc<Value>>,
     ast: &AST,
-    scopechain: &ScopeChain,
+    scopechain: &mut ScopeChain,
     line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     match args[0]
@@ -232,9 +584,15 @@ pub fn builtin_arr_len(
         .as_ref()
     {
         Value::Object(ref obj) => {
-            let len = obj.fields.iter().filter(|v| match v.0 {ObjectKey::Number(_) => true, ObjectKey::String(_) => false}).map(|_| 1).collect::<Vec<i32>>().len();
-            Ok(Rc::new(Value::Number(len as f64)))
+            Ok(Rc::new(Value::Int(obj.array_len() as i64)))
         }
+        Value::Range(range) => match i64::try_from(range.len()) {
+            Ok(len) => Ok(Rc::new(Value::Int(len))),
+            Err(_) => Err(Box::new(RuntimeError::new(
+                format!("The length of {} is too big for an int", range),
+                line,
+            ))),
+        },
         _ => {
             return Err(Box::new(RuntimeError::new(
                 "Cannot get length of non-object".to_string(),
@@ -244,10 +602,70 @@ pub fn builtin_arr_len(
     }
 }
 
+pub fn builtin_obj_freeze(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let obj = args[0].unpack_and_transform(scopechain, line, ast)?;
+    match obj.as_ref() {
+        Value::Object(_) => {
+            Value::freeze(&obj);
+            Ok(obj)
+        }
+        _ => Err(Box::new(RuntimeError::new(
+            "Cannot freeze non-object".to_string(),
+            line,
+        ))),
+    }
+}
+
+pub fn builtin_obj_is_frozen(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let obj = args[0].unpack_and_transform(scopechain, line, ast)?;
+    Ok(Rc::new(Value::Boolean(obj.is_frozen())))
+}
+
+// errors with the message when the condition is false, mostly for checks in comptime
+pub fn builtin_assert(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let cond = args[0].unpack_and_transform(scopechain, line, ast)?;
+    match cond.as_ref() {
+        Value::Boolean(true) => Ok(Rc::new(Value::Undefined)),
+        Value::Boolean(false) => {
+            let msg = builtin_to_str(vec![args[1].clone()], ast, scopechain, line)?;
+            let msg = match msg.as_ref() {
+                Value::String(s) => s.clone(),
+                _ => "".to_string(),
+            };
+            Err(Box::new(RuntimeError::new(
+                format!("Assertion failed: {}", msg),
+                line,
+            )))
+        }
+        v => Err(Box::new(RuntimeError::new(
+            format!(
+                "Expected a boolean to assert, got {}",
+                v.pretty_type(scopechain, line)
+            ),
+            line,
+        ))),
+    }
+}
+
 pub fn builtin_str_len(
     args: Vec<Rc<Value>>,
     ast: &AST,
-    scopechain: &ScopeChain,
+    scopechain: &mut ScopeChain,
     line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     match args[0]
@@ -255,7 +673,8 @@ pub fn builtin_str_len(
         .as_ref()
     {
         Value::String(ref s) => {
-            Ok(Rc::new(Value::Number(s.len() as f64)))
+            // counted in chars, the same as indexing and slicing a string
+            Ok(Rc::new(Value::Int(s.chars().count() as i64)))
         }
         _ => {
             return Err(Box::new(RuntimeError::new(
@@ -269,7 +688,7 @@ pub fn builtin_str_len(
 pub fn builtin_math_ceil(
     args: Vec<Rc<Value>>,
     ast: &AST,
-    scopechain: &ScopeChain,
+    scopechain: &mut ScopeChain,
     line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     match args[0]
@@ -279,6 +698,9 @@ pub fn builtin_math_ceil(
         Value::Number(ref n) => {
             Ok(Rc::new(Value::Number(n.ceil())))
         }
+        Value::Int(ref n) => {
+            Ok(Rc::new(Value::Int(*n)))
+        }
         _ => {
             return Err(Box::new(RuntimeError::new(
                 "Cannot floor non-number".to_string(),
@@ -290,7 +712,7 @@ pub fn builtin_math_ceil(
 pub fn builtin_math_floor(
     args: Vec<Rc<Value>>,
     ast: &AST,
-    scopechain: &ScopeChain,
+    scopechain: &mut ScopeChain,
     line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     match args[0]
@@ -300,6 +722,9 @@ pub fn builtin_math_floor(
         Value::Number(ref n) => {
             Ok(Rc::new(Value::Number(n.floor())))
         }
+        Value::Int(ref n) => {
+            Ok(Rc::new(Value::Int(*n)))
+        }
         _ => {
             return Err(Box::new(RuntimeError::new(
                 "Cannot floor non-number".to_string(),
@@ -311,7 +736,7 @@ pub fn builtin_math_floor(
 pub fn builtin_math_round(
     args: Vec<Rc<Value>>,
     ast: &AST,
-    scopechain: &ScopeChain,
+    scopechain: &mut ScopeChain,
     line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     match args[0]
@@ -321,6 +746,9 @@ pub fn builtin_math_round(
         Value::Number(ref n) => {
             Ok(Rc::new(Value::Number(n.round())))
         }
+        Value::Int(ref n) => {
+            Ok(Rc::new(Value::Int(*n)))
+        }
         _ => {
             return Err(Box::new(RuntimeError::new(
                 "Cannot round non-number".to_string(),
@@ -329,10 +757,169 @@ pub fn builtin_math_round(
         }
     }
 }
+pub fn builtin_math_to_int(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    match args[0]
+        .unpack_and_transform(scopechain, line, ast)?
+        .as_ref()
+    {
+        Value::Int(ref n) => {
+            Ok(Rc::new(Value::Int(*n)))
+        }
+        // truncates towards zero like a cast would, but refuses values an int can't hold
+        Value::Number(ref n) if n.is_finite() && n.trunc() >= i64::MIN as f64 && n.trunc() < i64::MAX as f64 => {
+            Ok(Rc::new(Value::Int(n.trunc() as i64)))
+        }
+        Value::Number(ref n) => Err(Box::new(RuntimeError::new(
+            format!("Cannot convert {} to an int", n),
+            line,
+        ))),
+        Value::Char(ref c) => {
+            Ok(Rc::new(Value::Int(*c as i64)))
+        }
+        Value::BigInt(ref n) => match n.to_i64() {
+            Some(n) => Ok(Rc::new(Value::Int(n))),
+            None => Err(Box::new(RuntimeError::new(
+                format!("Cannot convert {} to an int", n),
+                line,
+            ))),
+        },
+        Value::BigDecimal(ref n) => match n.trunc().to_i64() {
+            Some(n) => Ok(Rc::new(Value::Int(n))),
+            None => Err(Box::new(RuntimeError::new(
+                format!("Cannot convert {} to an int", n),
+                line,
+            ))),
+        },
+        _ => Err(Box::new(RuntimeError::new(
+            "Cannot convert non-number to an int".to_string(),
+            line,
+        ))),
+    }
+}
+pub fn builtin_math_to_float(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    match args[0]
+        .unpack_and_transform(scopechain, line, ast)?
+        .as_ref()
+    {
+        Value::Number(ref n) => {
+            Ok(Rc::new(Value::Number(*n)))
+        }
+        Value::Int(ref n) => {
+            Ok(Rc::new(Value::Number(*n as f64)))
+        }
+        Value::Char(ref c) => {
+            Ok(Rc::new(Value::Number(*c as i32 as f64)))
+        }
+        Value::BigInt(ref n) => {
+            Ok(Rc::new(Value::Number(n.to_f64())))
+        }
+        Value::BigDecimal(ref n) => {
+            Ok(Rc::new(Value::Number(n.to_f64())))
+        }
+        _ => Err(Box::new(RuntimeError::new(
+            "Cannot convert non-number to a float".to_string(),
+            line,
+        ))),
+    }
+}
+
+// big values can be made from ints, strings and other big values, floats are only accepted
+//  when they are written out exactly the way they print
+fn to_big_decimal(value: &Value, line: usize) -> Result<BigDecimal, Box<RuntimeError>> {
+    let parsed = match value {
+        Value::Int(n) => Some(BigDecimal::from_int(&BigInt::from_i64(*n))),
+        Value::BigInt(n) => Some(BigDecimal::from_int(n)),
+        Value::BigDecimal(n) => Some(n.clone()),
+        Value::Number(n) if n.is_finite() => BigDecimal::parse(&n.to_string()),
+        Value::String(s) => BigDecimal::parse(s.trim()),
+        _ => None,
+    };
+    match parsed {
+        Some(n) => Ok(n),
+        None => Err(Box::new(RuntimeError::new(
+            match value {
+                Value::String(s) => format!("Cannot convert \"{}\" to a big number", s),
+                _ => "Cannot convert non-number to a big number".to_string(),
+            },
+            line,
+        ))),
+    }
+}
+pub fn builtin_big_int(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let value = args[0].unpack_and_transform(scopechain, line, ast)?;
+    match value.as_ref() {
+        Value::String(s) if s.contains('.') => Err(Box::new(RuntimeError::new(
+            format!("Cannot convert \"{}\" to a bigint, use std.big.dec for decimals", s),
+            line,
+        ))),
+        // anything with a fractional part gets truncated like std.math.toInt does
+        v => Ok(Rc::new(Value::BigInt(to_big_decimal(v, line)?.trunc()))),
+    }
+}
+pub fn builtin_big_dec(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let value = args[0].unpack_and_transform(scopechain, line, ast)?;
+    Ok(Rc::new(Value::BigDecimal(to_big_decimal(&value, line)?)))
+}
+fn get_scale(value: &Value, line: usize) -> Result<u32, Box<RuntimeError>> {
+    match value {
+        Value::Int(n) if *n >= 0 && *n <= u32::MAX as i64 => Ok(*n as u32),
+        _ => Err(Box::new(RuntimeError::new(
+            "Scale has to be a non-negative int".to_string(),
+            line,
+        ))),
+    }
+}
+pub fn builtin_big_div(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let left = to_big_decimal(args[0].unpack_and_transform(scopechain, line, ast)?.as_ref(), line)?;
+    let right = to_big_decimal(args[1].unpack_and_transform(scopechain, line, ast)?.as_ref(), line)?;
+    let scale = get_scale(args[2].unpack_and_transform(scopechain, line, ast)?.as_ref(), line)?;
+    match left.div(&right, scale) {
+        Some(n) => Ok(Rc::new(Value::BigDecimal(n))),
+        None => Err(Box::new(RuntimeError::new(
+            "Cannot divide a big number by zero".to_string(),
+            line,
+        ))),
+    }
+}
+pub fn builtin_big_round(
+    args: Vec<Rc<Value>>,
+    ast: &AST,
+    scopechain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let value = to_big_decimal(args[0].unpack_and_transform(scopechain, line, ast)?.as_ref(), line)?;
+    let scale = get_scale(args[1].unpack_and_transform(scopechain, line, ast)?.as_ref(), line)?;
+    Ok(Rc::new(Value::BigDecimal(value.round(scale))))
+}
 
 pub fn make_builtin_std(scope_chain: &mut ScopeChain) -> Result<(), Box<RuntimeError>> {
-    let mut std_obj = Object { fields: vec![] };
-    let mut std_io_obj = Object { fields: vec![] };
+    let mut std_obj = Object::new();
+    let mut std_io_obj = Object::new();
     std_io_obj.set(
         ObjectKey::String("println".to_string()),
         Rc::new(Value::BuiltinFunction(builtin_println, 1)),
@@ -346,7 +933,11 @@ pub fn make_builtin_std(scope_chain: &mut ScopeChain) -> Result<(), Box<RuntimeE
         ObjectKey::String("io".to_string()),
         std_io_rc.clone(),
     );
-    let mut std_time_obj = Object { fields: vec![] };
+    std_obj.set(
+        ObjectKey::String("assert".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_assert, 2)),
+    );
+    let mut std_time_obj = Object::new();
     std_time_obj.set(
         ObjectKey::String("nanos".to_string()),
         Rc::new(Value::BuiltinFunction(builtin_nanos, 0)),
@@ -355,13 +946,17 @@ pub fn make_builtin_std(scope_chain: &mut ScopeChain) -> Result<(), Box<RuntimeE
         ObjectKey::String("sleepNanos".to_string()),
         Rc::new(Value::BuiltinFunction(builtin_sleep_nanos, 1)),
     );
+    std_time_obj.set(
+        ObjectKey::String("after".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_after, 1)),
+    );
     let std_time_rc = Rc::new(Value::Object(std_time_obj));
     std_obj.set(
         ObjectKey::String("time".to_string()),
         std_time_rc.clone(),
     );
 
-    let mut std_arr_obj = Object { fields: vec![] };
+    let mut std_arr_obj = Object::new();
     std_arr_obj.set(
         ObjectKey::String("len".to_string()),
         Rc::new(Value::BuiltinFunction(builtin_arr_len, 1)),
@@ -372,7 +967,85 @@ pub fn make_builtin_std(scope_chain: &mut ScopeChain) -> Result<(), Box<RuntimeE
         std_arr_rc.clone(),
     );
 
-    let mut std_math_obj = Object { fields: vec![] };
+    let mut std_cmp_obj = Object::new();
+    std_cmp_obj.set(
+        ObjectKey::String("compare".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_cmp_compare, 2)),
+    );
+    let std_cmp_rc = Rc::new(Value::Object(std_cmp_obj));
+    std_obj.set(
+        ObjectKey::String("cmp".to_string()),
+        std_cmp_rc.clone(),
+    );
+
+    let mut std_ref_obj = Object::new();
+    std_ref_obj.set(
+        ObjectKey::String("same".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_ref_same, 2)),
+    );
+    let std_ref_rc = Rc::new(Value::Object(std_ref_obj));
+    std_obj.set(
+        ObjectKey::String("ref".to_string()),
+        std_ref_rc.clone(),
+    );
+
+    let mut std_task_obj = Object::new();
+    std_task_obj.set(
+        ObjectKey::String("spawn".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_task_spawn, 1)),
+    );
+    std_task_obj.set(
+        ObjectKey::String("all".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_task_all, 1)),
+    );
+    let std_task_rc = Rc::new(Value::Object(std_task_obj));
+    std_obj.set(
+        ObjectKey::String("task".to_string()),
+        std_task_rc.clone(),
+    );
+
+    let mut std_fs_obj = Object::new();
+    std_fs_obj.set(
+        ObjectKey::String("read".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_fs_read, 1)),
+    );
+    std_fs_obj.set(
+        ObjectKey::String("write".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_fs_write, 2)),
+    );
+    let std_fs_rc = Rc::new(Value::Object(std_fs_obj));
+    std_obj.set(
+        ObjectKey::String("fs".to_string()),
+        std_fs_rc.clone(),
+    );
+
+    let mut std_proc_obj = Object::new();
+    std_proc_obj.set(
+        ObjectKey::String("run".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_proc_run, 2)),
+    );
+    let std_proc_rc = Rc::new(Value::Object(std_proc_obj));
+    std_obj.set(
+        ObjectKey::String("proc".to_string()),
+        std_proc_rc.clone(),
+    );
+
+    let mut std_obj_obj = Object::new();
+    std_obj_obj.set(
+        ObjectKey::String("freeze".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_obj_freeze, 1)),
+    );
+    std_obj_obj.set(
+        ObjectKey::String("isFrozen".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_obj_is_frozen, 1)),
+    );
+    let std_obj_rc = Rc::new(Value::Object(std_obj_obj));
+    std_obj.set(
+        ObjectKey::String("obj".to_string()),
+        std_obj_rc.clone(),
+    );
+
+    let mut std_math_obj = Object::new();
     std_math_obj.set(
         ObjectKey::String("PI".to_string()),
         Rc::new(Value::Number(std::f64::consts::PI)),
@@ -389,13 +1062,44 @@ pub fn make_builtin_std(scope_chain: &mut ScopeChain) -> Result<(), Box<RuntimeE
         ObjectKey::String("round".to_string()),
         Rc::new(Value::BuiltinFunction(builtin_math_round, 1)),
     );
+    std_math_obj.set(
+        ObjectKey::String("toInt".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_math_to_int, 1)),
+    );
+    std_math_obj.set(
+        ObjectKey::String("toFloat".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_math_to_float, 1)),
+    );
     let std_math_rc = Rc::new(Value::Object(std_math_obj));
     std_obj.set(
         ObjectKey::String("math".to_string()),
         std_math_rc.clone(),
     );
 
-    let mut std_str_obj = Object { fields: vec![] };
+    let mut std_big_obj = Object::new();
+    std_big_obj.set(
+        ObjectKey::String("int".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_big_int, 1)),
+    );
+    std_big_obj.set(
+        ObjectKey::String("dec".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_big_dec, 1)),
+    );
+    std_big_obj.set(
+        ObjectKey::String("div".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_big_div, 3)),
+    );
+    std_big_obj.set(
+        ObjectKey::String("round".to_string()),
+        Rc::new(Value::BuiltinFunction(builtin_big_round, 2)),
+    );
+    let std_big_rc = Rc::new(Value::Object(std_big_obj));
+    std_obj.set(
+        ObjectKey::String("big".to_string()),
+        std_big_rc.clone(),
+    );
+
+    let mut std_str_obj = Object::new();
     std_str_obj.set(
         ObjectKey::String("len".to_string()),
         Rc::new(Value::BuiltinFunction(builtin_str_len, 1)),
@@ -424,7 +1128,7 @@ pub fn make_builtin_std(scope_chain: &mut ScopeChain) -> Result<(), Box<RuntimeE
         .to_runtime_error()?;
     Ok(())
 }
-pub fn add_builtin_fn(scope_chain: &mut ScopeChain, name: &str, len: usize, func: fn(Vec<Rc<Value>>, &AST, &ScopeChain, usize) -> Result<Rc<Value>, Box<RuntimeError>>) -> Result<(), Box<RuntimeError>> {
+pub fn add_builtin_fn(scope_chain: &mut ScopeChain, name: &str, len: usize, func: fn(Vec<Rc<Value>>, &AST, &mut ScopeChain, usize) -> Result<Rc<Value>, Box<RuntimeError>>) -> Result<(), Box<RuntimeError>> {
     let name = name.to_string();
     scope_chain
         .add_variable(&name, true, 0)
@@ -0,0 +1,459 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use crate::ast::FunctionLiteral;
+use crate::error::RuntimeError;
+use crate::parser::{Object, ObjectKey, Value};
+use crate::scopechain::ScopeChain;
+use crate::vm::{Frame, Step};
+
+#[derive(Debug)]
+pub enum FutureState {
+    // a task that the event loop hasn't started yet
+    Pending(FunctionLiteral, Vec<Rc<Value>>),
+    // a task that has started and hasn't finished, it might be stopped at an await
+    Running,
+    Timer(Instant),
+    // resolves to an array of the values once every future in it has resolved, finished
+    //  counts the ones at the start that already have
+    All {
+        futures: Vec<Rc<Future>>,
+        finished: usize,
+    },
+    // a file or subprocess being worked on by a thread of its own
+    Io(Receiver<Result<IoResult, String>>),
+    Done(Rc<Value>),
+    Failed(Box<RuntimeError>),
+}
+
+// what a file or subprocess gives back. it's made on the thread doing the work, so it only
+//  turns into a value once the event loop picks it up
+#[derive(Debug)]
+pub enum IoResult {
+    Nothing,
+    Text(String),
+    Exited {
+        code: i64,
+        stdout: String,
+        stderr: String,
+    },
+}
+
+impl IoResult {
+    fn into_value(self) -> Rc<Value> {
+        match self {
+            IoResult::Nothing => Rc::new(Value::Undefined),
+            IoResult::Text(s) => Rc::new(Value::String(s)),
+            IoResult::Exited {
+                code,
+                stdout,
+                stderr,
+            } => {
+                let mut obj = Object::new();
+                obj.set(ObjectKey::String("code".into()), Rc::new(Value::Int(code)));
+                obj.set(
+                    ObjectKey::String("stdout".into()),
+                    Rc::new(Value::String(stdout)),
+                );
+                obj.set(
+                    ObjectKey::String("stderr".into()),
+                    Rc::new(Value::String(stderr)),
+                );
+                Rc::new(Value::Object(obj))
+            }
+        }
+    }
+}
+
+// the value of an async call, std.time.after, std.task.all or a file or subprocess,
+//  awaiting it stops whatever awaits until it resolves
+#[derive(Debug)]
+pub struct Future {
+    pub state: RefCell<FutureState>,
+    // failures are only reported at the end if nothing awaited the task
+    awaited: Cell<bool>,
+    pub line: usize,
+}
+
+impl PartialEq for Future {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Future {
+    pub fn new(state: FutureState, line: usize) -> Rc<Future> {
+        Rc::new(Future {
+            state: RefCell::new(state),
+            awaited: Cell::new(false),
+            line,
+        })
+    }
+    pub fn is_awaited(&self) -> bool {
+        self.awaited.get()
+    }
+}
+
+// a task that has started, everything it was in the middle of is kept in its frame while
+//  it waits at an await
+struct Task {
+    future: Rc<Future>,
+    func: FunctionLiteral,
+    // the task's own scope chain, it borrows the global scope of the event loop while the
+    //  task runs
+    chain: ScopeChain,
+    frame: Frame,
+}
+
+impl Task {
+    // runs the body until it finishes or stops at an await, giving back what it waits on
+    fn resume(&mut self) -> Result<Option<Rc<Future>>, Box<RuntimeError>> {
+        let chunk = self.func.chunk.get(&self.func.body, true);
+        match chunk.resume(&mut self.frame, &mut self.chain)? {
+            Step::Await(future) => Ok(Some(future)),
+            Step::Done(_) => {
+                let value = self.func.finish_frame(&mut self.chain, self.future.line)?;
+                self.future.state.replace(FutureState::Done(value));
+                Ok(None)
+            }
+        }
+    }
+}
+
+struct EventLoopInner {
+    // tasks waiting to be started, oldest first
+    ready: VecDeque<Rc<Future>>,
+    // tasks stopped at an await and the future each one is waiting on
+    waiting: Vec<(Task, Rc<Future>)>,
+    // tasks that failed before anything awaited them
+    failed: Vec<Rc<Future>>,
+    // how many tasks are running right now, an await anywhere but in the body of one of
+    //  them can't stop it to let the others run
+    running: usize,
+    // files and subprocesses that haven't finished, they send on wake when they do
+    in_flight: Arc<AtomicUsize>,
+    wake: Sender<()>,
+    woken: Receiver<()>,
+}
+
+impl Default for EventLoopInner {
+    fn default() -> Self {
+        let (wake, woken) = mpsc::channel();
+        EventLoopInner {
+            ready: VecDeque::new(),
+            waiting: vec![],
+            failed: vec![],
+            running: 0,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            wake,
+            woken,
+        }
+    }
+}
+
+// there is one event loop per interpreter, every scope chain (and imported module) gets a
+//  handle to the same one
+#[derive(Clone, Default)]
+pub struct EventLoop {
+    inner: Rc<RefCell<EventLoopInner>>,
+}
+
+impl EventLoop {
+    pub fn new() -> EventLoop {
+        EventLoop::default()
+    }
+    pub fn spawn(&self, task: Rc<Future>) {
+        self.inner.borrow_mut().ready.push_back(task);
+    }
+    pub fn next_ready(&self) -> Option<Rc<Future>> {
+        self.inner.borrow_mut().ready.pop_front()
+    }
+    // errors of an awaited future go to whatever awaits it instead of the end of the program
+    pub fn mark_awaited(&self, future: &Rc<Future>) {
+        future.awaited.set(true);
+        self.inner
+            .borrow_mut()
+            .failed
+            .retain(|f| !Rc::ptr_eq(f, future));
+        if let FutureState::All { futures, .. } = &*future.state.borrow() {
+            for f in futures.iter() {
+                self.mark_awaited(f);
+            }
+        }
+    }
+    pub fn add_failed(&self, task: Rc<Future>) {
+        self.inner.borrow_mut().failed.push(task);
+    }
+    // runs work on a thread of its own, the future resolves to whatever it gives back. the
+    //  work never touches maple values, so it's the only thing that leaves the main thread
+    pub fn start_io(
+        &self,
+        line: usize,
+        work: impl FnOnce() -> Result<IoResult, String> + Send + 'static,
+    ) -> Result<Rc<Future>, Box<RuntimeError>> {
+        let (send, receive) = mpsc::channel();
+        let inner = self.inner.borrow();
+        let in_flight = inner.in_flight.clone();
+        let wake = inner.wake.clone();
+        in_flight.fetch_add(1, Ordering::SeqCst);
+        let counted = in_flight.clone();
+        let started = thread::Builder::new().spawn(move || {
+            let _ = send.send(work());
+            counted.fetch_sub(1, Ordering::SeqCst);
+            let _ = wake.send(());
+        });
+        if let Err(e) = started {
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+            return Err(Box::new(RuntimeError::new(
+                format!("Could not start a thread for the work: {}", e),
+                line,
+            )));
+        }
+        Ok(Future::new(FutureState::Io(receive), line))
+    }
+    // runs everything that's left, called once the rest of the program is done. tasks that
+    //  are stuck waiting on something that can't finish fail, and the first task that
+    //  failed without being awaited is reported here
+    pub fn run(&self, scope_chain: &mut ScopeChain) -> Result<(), Box<RuntimeError>> {
+        loop {
+            let in_flight = self.in_flight();
+            if self.step(scope_chain) || self.wait(None, in_flight) {
+                continue;
+            }
+            let stuck = std::mem::take(&mut self.inner.borrow_mut().waiting);
+            if stuck.is_empty() {
+                break;
+            }
+            for (mut task, _) in stuck {
+                task.frame.cancelled = true;
+                self.resume(task, scope_chain);
+            }
+        }
+        let failed = std::mem::take(&mut self.inner.borrow_mut().failed);
+        for task in failed.iter() {
+            if let FutureState::Failed(e) = &*task.state.borrow() {
+                return Err(e.clone());
+            }
+        }
+        Ok(())
+    }
+    // runs tasks until the future resolves, sleeping when everything left is waiting
+    fn run_until(
+        &self,
+        future: &Rc<Future>,
+        scope_chain: &mut ScopeChain,
+    ) -> Result<Rc<Value>, Box<RuntimeError>> {
+        loop {
+            // read before polling so that work finishing in between still wakes the wait
+            let in_flight = self.in_flight();
+            if let Some(result) = poll(future) {
+                return result;
+            }
+            if !self.step(scope_chain) && !self.wait(Some(future), in_flight) {
+                return Err(stuck_error(future));
+            }
+        }
+    }
+    fn in_flight(&self) -> usize {
+        self.inner.borrow().in_flight.load(Ordering::SeqCst)
+    }
+    // picks a task back up once what it waits on has resolved, otherwise starts a new one.
+    //  gives back false if there was nothing to run
+    fn step(&self, scope_chain: &mut ScopeChain) -> bool {
+        let woken = {
+            let mut inner = self.inner.borrow_mut();
+            let i = inner.waiting.iter().position(|(_, f)| poll(f).is_some());
+            i.map(|i| inner.waiting.remove(i))
+        };
+        if let Some((task, _)) = woken {
+            self.resume(task, scope_chain);
+            return true;
+        }
+        match self.next_ready() {
+            Some(future) => {
+                self.start(future, scope_chain);
+                true
+            }
+            None => false,
+        }
+    }
+    // sets up the frame of a task that hasn't started and runs it until it first awaits
+    fn start(&self, future: Rc<Future>, scope_chain: &mut ScopeChain) {
+        let (func, args) = match future.state.replace(FutureState::Running) {
+            FutureState::Pending(func, args) => (func, args),
+            state => {
+                future.state.replace(state);
+                return;
+            }
+        };
+        let mut chain = scope_chain.for_module();
+        if let Err(e) = func.enter_frame(&mut chain, args, future.line) {
+            self.fail(&future, e, false);
+            return;
+        }
+        let task = Task {
+            frame: Frame::task(&chain),
+            future,
+            func,
+            chain,
+        };
+        self.resume(task, scope_chain);
+    }
+    // lends the task the global scope and runs it until it finishes or awaits again
+    fn resume(&self, mut task: Task, scope_chain: &mut ScopeChain) {
+        scope_chain.swap_globals(&mut task.chain);
+        self.inner.borrow_mut().running += 1;
+        let result = task.resume();
+        self.inner.borrow_mut().running -= 1;
+        scope_chain.swap_globals(&mut task.chain);
+        match result {
+            Ok(Some(waiting_on)) => self.inner.borrow_mut().waiting.push((task, waiting_on)),
+            Ok(None) => {}
+            Err(e) => self.fail(&task.future, e, task.frame.cancelled),
+        }
+    }
+    fn fail(&self, task: &Rc<Future>, e: Box<RuntimeError>, cancelled: bool) {
+        task.state.replace(FutureState::Failed(e));
+        // a task that got stuck is always reported, whatever awaits it is stuck too
+        if !task.is_awaited() || cancelled {
+            self.add_failed(task.clone());
+        }
+    }
+    // sleeps until the next timer or until a file or subprocess finishes, false if neither
+    //  could happen
+    fn wait(&self, future: Option<&Rc<Future>>, in_flight: usize) -> bool {
+        let inner = self.inner.borrow();
+        let deadline = future
+            .into_iter()
+            .chain(inner.waiting.iter().map(|(_, f)| f))
+            .filter_map(next_deadline)
+            .min();
+        match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if deadline > now {
+                    let _ = inner.woken.recv_timeout(deadline - now);
+                }
+                true
+            }
+            None if in_flight > 0 => {
+                let _ = inner.woken.recv();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl PartialEq for EventLoop {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Debug for EventLoop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("EventLoop")
+            .field("ready", &inner.ready.len())
+            .field("failed", &inner.failed.len())
+            .finish()
+    }
+}
+
+pub fn stuck_error(future: &Rc<Future>) -> Box<RuntimeError> {
+    Box::new(RuntimeError::new(
+        "Nothing is left that could finish the awaited value".into(),
+        future.line,
+    ))
+}
+// the result of a future if it has resolved, timers resolve once their time is up
+pub fn poll(future: &Rc<Future>) -> Option<Result<Rc<Value>, Box<RuntimeError>>> {
+    let mut state = future.state.borrow_mut();
+    let value = match &mut *state {
+        FutureState::Done(value) => return Some(Ok(value.clone())),
+        FutureState::Failed(e) => return Some(Err(e.clone())),
+        FutureState::Pending(_, _) | FutureState::Running => return None,
+        FutureState::Timer(deadline) => {
+            if Instant::now() < *deadline {
+                return None;
+            }
+            Rc::new(Value::Undefined)
+        }
+        FutureState::All { futures, finished } => {
+            // nothing is built until every one of them has resolved, and the ones that
+            //  already had aren't looked at again
+            while *finished < futures.len() {
+                if let Err(e) = poll(&futures[*finished])? {
+                    *state = FutureState::Failed(e.clone());
+                    return Some(Err(e));
+                }
+                *finished += 1;
+            }
+            let mut obj = Object::new();
+            for (i, f) in futures.iter().enumerate() {
+                if let FutureState::Done(value) = &*f.state.borrow() {
+                    // the keys are all different, so there's no need to look for them
+                    obj.fields
+                        .push((ObjectKey::Number(i as f64), value.clone()));
+                }
+            }
+            Rc::new(Value::Object(obj))
+        }
+        FutureState::Io(receive) => match receive.try_recv() {
+            Ok(Ok(result)) => result.into_value(),
+            Ok(Err(msg)) => {
+                let e = Box::new(RuntimeError::new(msg, future.line));
+                *state = FutureState::Failed(e.clone());
+                return Some(Err(e));
+            }
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => {
+                let e = Box::new(RuntimeError::new(
+                    "The thread doing the work stopped without finishing".into(),
+                    future.line,
+                ));
+                *state = FutureState::Failed(e.clone());
+                return Some(Err(e));
+            }
+        },
+    };
+    *state = FutureState::Done(value.clone());
+    Some(Ok(value))
+}
+// the soonest time that waiting on the future could make progress
+fn next_deadline(future: &Rc<Future>) -> Option<Instant> {
+    match &*future.state.borrow() {
+        FutureState::Timer(deadline) => Some(*deadline),
+        FutureState::All { futures, .. } => futures.iter().filter_map(next_deadline).min(),
+        _ => None,
+    }
+}
+// the main program runs the event loop until the future resolves. a task only stops at an
+//  await in its own body, which the vm takes care of, so anywhere else in a task it has to
+//  have resolved already
+pub fn await_future(
+    future: &Rc<Future>,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let event_loop = scope_chain.get_event_loop();
+    event_loop.mark_awaited(future);
+    if event_loop.inner.borrow().running == 0 {
+        return event_loop.run_until(future, scope_chain);
+    }
+    match poll(future) {
+        Some(result) => result,
+        None => Err(Box::new(RuntimeError::new(
+            "A task can't stop at this await, give the awaited value a variable of its own first"
+                .into(),
+            future.line,
+        ))),
+    }
+}
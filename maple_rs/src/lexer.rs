@@ -54,6 +54,7 @@ impl GetFromIndex<char> for String {
 pub enum TokenType {
     Import(String),
     Number(f64),
+    Int(i64),
     Char(char),
     String(String),
     Ident(String),
@@ -66,8 +67,23 @@ pub enum TokenType {
     If,
     Else,
     While,
+    Loop,
+    For,
+    In,
+    Do,
+    Macro,
+    Quote,
+    Unquote,
+    Comptime,
+    Enum,
+    Struct,
+    Async,
+    Await,
+    Match,
+    Arrow,
     Elseif,
     Return,
+    Delete,
     Break,
     Continue,
     EndOfStatement,
@@ -86,6 +102,8 @@ pub enum TokenType {
     OpMns,
     OpTimes,
     OpDiv,
+    OpDotDot,
+    OpDotDotEq,
     EOF,
     LeftBrace,
     RightBrace,
@@ -94,6 +112,7 @@ pub enum TokenType {
     LeftSquare,
     RightSquare,
     Comma,
+    Colon,
     Comment(String),
 }
 impl TokenType {}
@@ -121,6 +140,9 @@ impl Token {
             TokenType::OpMns => Ok(6),
             TokenType::OpTimes => Ok(5),
             TokenType::OpDiv => Ok(5),
+            TokenType::In => Ok(9),
+            TokenType::OpDotDot => Ok(8),
+            TokenType::OpDotDotEq => Ok(8),
             _ => Err(Box::new(ParserError::new(
                 format!("Unknown operator: {:?}", self).into(),
                 lexer.get_line(),
@@ -155,6 +177,9 @@ impl Token {
             TokenType::OpMns => Ok(Assoc::Left),
             TokenType::OpTimes => Ok(Assoc::Left),
             TokenType::OpDiv => Ok(Assoc::Left),
+            TokenType::In => Ok(Assoc::Left),
+            TokenType::OpDotDot => Ok(Assoc::Left),
+            TokenType::OpDotDotEq => Ok(Assoc::Left),
             _ => Err(Box::new(ParserError::new(
                 format!("Unknown operator: {:?}", self).into(),
                 lexer.get_line(),
@@ -177,6 +202,9 @@ impl Token {
             | TokenType::OpMns
             | TokenType::OpTimes
             | TokenType::OpDiv
+            | TokenType::OpDotDot
+            | TokenType::OpDotDotEq
+            | TokenType::In
             | TokenType::OpPls => true,
             _ => false,
         }
@@ -240,7 +268,7 @@ impl Lexer {
         let number;
         let start_index = self.i;
         let starts_with_zero = self.input.at(self.i, self.line)? == '0'
-            && self.i + 1 < self.input.len()
+            && self.i + 1 < self.input[self.line].len()
             && self.input.at(self.i + 1, self.line)? != '.';
         let mut char_number = 0;
         let mut integer_base = 10;
@@ -257,6 +285,12 @@ impl Lexer {
                 'o' if starts_with_zero && char_number == 1 => {
                     integer_base = 8;
                 }
+                // the start of a range like 0..10
+                '.' if self.i + 1 < self.input[self.line].len()
+                    && self.input.at(self.i + 1, self.line)? == '.' =>
+                {
+                    break
+                }
                 '.' if !starts_with_zero && decimal_count > 0 => {
                     err = Some(Box::new(LexerError::new(
                         "Invalid number \"\", note: number already has decimal point".to_string(),
@@ -336,11 +370,11 @@ impl Lexer {
                     },
                 )));
             }
-            number = match i32::from_str_radix(
+            number = match i64::from_str_radix(
                 &self.input[self.line][start_index..self.i],
                 integer_base,
             ) {
-                Ok(n) => n as f64,
+                Ok(n) => TokenType::Int(n),
                 Err(_) => {
                     return Err(Box::new(LexerError::new(
                         format!(
@@ -360,10 +394,35 @@ impl Lexer {
                     )))
                 }
             };
-            self.i += 1;
+        } else if decimal_count == 0 {
+            number = match self.input[self.line][start_index..self.i].parse::<i64>() {
+                Ok(n) => TokenType::Int(n),
+                // one past i64::MAX only fits once it's negated, which the parser checks for.
+                //  no other literal can lex to i64::MIN
+                Err(_) if &self.input[self.line][start_index..self.i] == "9223372036854775808" => {
+                    TokenType::Int(i64::MIN)
+                }
+                Err(_) => {
+                    return Err(Box::new(LexerError::new(
+                        format!(
+                            "Integer {} does not fit in 64 bits",
+                            &self.input[self.line][start_index..self.i]
+                        ),
+                        self.get_line(),
+                        Token {
+                            t: TokenType::Ident(
+                                self.input[self.line][start_index..self.i].to_string(),
+                            ),
+                            line: self.line,
+                            char_start: start_index,
+                            char_end: self.i,
+                        },
+                    )))
+                }
+            };
         } else {
             number = match self.input[self.line][start_index..self.i].parse::<f64>() {
-                Ok(n) => n,
+                Ok(n) => TokenType::Number(n),
                 Err(_) => {
                     return Err(Box::new(LexerError::new(
                         "Invalid number".to_string() + &self.input[self.line][start_index..self.i],
@@ -381,7 +440,7 @@ impl Lexer {
             };
         }
         Ok(Token {
-            t: TokenType::Number(number),
+            t: number,
             line: self.line,
             char_start: start_index,
             char_end: self.i,
@@ -432,7 +491,12 @@ impl Lexer {
                     },
                 )));
             }
-            c => c,
+            c if c.is_ascii() => c,
+            _ => {
+                let c = self.input[self.line][self.i..].chars().next().unwrap();
+                self.i += c.len_utf8() - 1;
+                c
+            }
         };
 
         if self.input.at(self.i + 1, self.line)? != '\'' {
@@ -455,6 +519,17 @@ impl Lexer {
             char_end: self.i,
         })
     }
+    // newlines and ; are both EndOfStatement, this tells them apart. tokens the parser made
+    //  up itself have no width and count as newlines
+    pub fn is_semicolon(&self, token: &Token) -> bool {
+        token.t == TokenType::EndOfStatement
+            && token.char_end > token.char_start
+            && self
+                .input
+                .get(token.line)
+                .and_then(|line| line.as_bytes().get(token.char_start))
+                == Some(&b';')
+    }
     pub fn feed_token(&mut self, token: Token) {
         self.feed_tokens.push(token);
     }
@@ -495,8 +570,14 @@ impl Lexer {
                     )));
                 }
                 c => {
+                    // the line is read a byte at a time, a char past ascii takes up more than one
+                    let c = if c.is_ascii() {
+                        c
+                    } else {
+                        self.input[self.line][self.i..].chars().next().unwrap()
+                    };
                     string.push(c);
-                    self.i += 1;
+                    self.i += c.len_utf8();
                 }
             }
         }
@@ -525,11 +606,25 @@ impl Lexer {
         ident = self.input[self.line][start_index..self.i].to_string();
         let t = match ident.as_str() {
             "while" => TokenType::While,
+            "loop" => TokenType::Loop,
+            "for" => TokenType::For,
+            "in" => TokenType::In,
+            "do" => TokenType::Do,
+            "macro" => TokenType::Macro,
+            "quote" => TokenType::Quote,
+            "unquote" => TokenType::Unquote,
+            "comptime" => TokenType::Comptime,
+            "enum" => TokenType::Enum,
+            "struct" => TokenType::Struct,
+            "async" => TokenType::Async,
+            "await" => TokenType::Await,
+            "match" => TokenType::Match,
             "const" => TokenType::Const,
             "if" => TokenType::If,
             "else" => TokenType::Else,
             "elseif" => TokenType::Elseif,
             "return" => TokenType::Return,
+            "delete" => TokenType::Delete,
             "break" => TokenType::Break,
             "continue" => TokenType::Continue,
             "var" => TokenType::Var,
@@ -600,10 +695,14 @@ impl Lexer {
         let i = self.i;
         let line = self.line;
         let current_token = self.current_token.clone();
+        let feed_tokens = self.feed_tokens.clone();
+        let comment_count = self.comments.len();
         let token = self.get_next_token();
         self.i = i;
         self.line = line;
         self.current_token = current_token;
+        self.feed_tokens = feed_tokens;
+        self.comments.truncate(comment_count);
         token
     }
     fn single_char_token(&mut self, t: TokenType) -> Token {
@@ -657,12 +756,25 @@ impl Lexer {
                     self.i += 1;
                     self.get_next_token()?
                 }
+                '.' if self.peek_next_char() == '.' => {
+                    if self.i + 2 < self.input[self.line].len()
+                        && self.input.at(self.i + 2, self.line)? == '='
+                    {
+                        self.char_token(TokenType::OpDotDotEq, 3)
+                    } else {
+                        self.char_token(TokenType::OpDotDot, 2)
+                    }
+                }
                 '.' => self.single_char_token(TokenType::Dot),
                 ',' => self.single_char_token(TokenType::Comma),
+                // ; ends a statement just like a newline, so several can share a line
+                ';' => self.single_char_token(TokenType::EndOfStatement),
+                ':' => self.single_char_token(TokenType::Colon),
                 '!' if self.peek_next_char() == '=' => self.char_token(TokenType::OpNotEq, 2),
                 '!' => self.single_char_token(TokenType::OpNot),
                 '*' => self.single_char_token(TokenType::OpTimes),
                 '=' if self.peek_next_char() == '=' => self.char_token(TokenType::OpEqEq, 2),
+                '=' if self.peek_next_char() == '>' => self.char_token(TokenType::Arrow, 2),
                 '=' => self.single_char_token(TokenType::OpEq),
                 '+' if self.peek_next_char() == '=' => self.char_token(TokenType::OpPlsEq, 2),
                 '+' => self.single_char_token(TokenType::OpPls),
@@ -783,7 +895,7 @@ mod test {
     fn test_numbers() {
         let mut contents = "1";
         let mut tokens: Vec<TokenType> = vec![
-            TokenType::Number(1.0),
+            TokenType::Int(1),
             TokenType::EndOfStatement,
             TokenType::EOF,
         ];
@@ -803,6 +915,31 @@ mod test {
             TokenType::EOF,
         ];
         expect_tokens(contents.to_string(), tokens);
+
+        contents = "9007199254740993";
+        tokens = vec![
+            TokenType::Int(9007199254740993),
+            TokenType::EndOfStatement,
+            TokenType::EOF,
+        ];
+        expect_tokens(contents.to_string(), tokens);
+
+        contents = "x = 0x1F + 0b101 - 0o17";
+        tokens = vec![
+            TokenType::Ident("x".to_string()),
+            TokenType::OpEq,
+            TokenType::Int(31),
+            TokenType::OpPls,
+            TokenType::Int(5),
+            TokenType::OpMns,
+            TokenType::Int(15),
+            TokenType::EndOfStatement,
+            TokenType::EOF,
+        ];
+        expect_tokens(contents.to_string(), tokens);
+
+        let mut lexer = Lexer::new("99999999999999999999".to_string());
+        assert!(lexer.get_next_token().is_err());
     }
 
     #[test]
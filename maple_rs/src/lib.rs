@@ -1,9 +1,14 @@
 pub mod lexer;
 // use crate::lexer::{Lexer, Token};
 pub mod ast;
+pub mod big;
 pub mod builtins;
 pub mod error;
+pub mod event_loop;
+pub mod modules;
 pub mod parser;
 mod runtime;
 
+pub mod resolver;
 pub mod scopechain;
+pub mod vm;
@@ -0,0 +1,362 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::parser::Value;
+
+// prefix of the Maple-source library modules that are compiled into maple_rs
+pub const STD_PREFIX: &str = "std/";
+
+const STD_MODULES: [(&str, &str); 2] = [
+    ("std/math.mpl", include_str!("../std/math.mpl")),
+    ("std/str.mpl", include_str!("../std/str.mpl")),
+];
+
+// where the interpreter gets module source from, imports under STD_PREFIX never reach the loader
+pub trait ModuleLoader {
+    // turns an import name into the key the module is cached under,
+    // the error should list every location that was tried
+    fn resolve(&self, name: &str) -> Result<String, String>;
+    // the source of a module, given a key returned by resolve
+    fn load(&self, key: &str) -> Result<String, String>;
+}
+
+// the default loader, reads files from the working directory then the search path
+#[derive(Debug, Clone, Default)]
+pub struct FsModuleLoader {
+    search_path: Vec<PathBuf>,
+}
+
+impl FsModuleLoader {
+    pub fn new() -> FsModuleLoader {
+        FsModuleLoader::default()
+    }
+    pub fn add_search_path(&mut self, dir: impl Into<PathBuf>) {
+        self.search_path.push(dir.into());
+    }
+    // appends every directory listed in the MAPLE_PATH environment variable
+    pub fn add_env_search_paths(&mut self) {
+        if let Some(paths) = std::env::var_os("MAPLE_PATH") {
+            for dir in std::env::split_paths(&paths) {
+                if !dir.as_os_str().is_empty() {
+                    self.add_search_path(dir);
+                }
+            }
+        }
+    }
+    pub fn get_search_path(&self) -> &Vec<PathBuf> {
+        &self.search_path
+    }
+}
+
+impl ModuleLoader for FsModuleLoader {
+    fn resolve(&self, name: &str) -> Result<String, String> {
+        let mut candidates = vec![PathBuf::from(name)];
+        if Path::new(name).is_relative() {
+            // relative imports are tried against the working directory first
+            if let Ok(cwd) = std::env::current_dir() {
+                candidates[0] = cwd.join(name);
+            }
+            for dir in self.search_path.iter() {
+                candidates.push(dir.join(name));
+            }
+        }
+        for candidate in candidates.iter() {
+            if candidate.is_file() {
+                if let Ok(path) = std::fs::canonicalize(candidate) {
+                    return Ok(path.display().to_string());
+                }
+            }
+        }
+        Err(format!(
+            "Cannot find module {}, tried:\n{}",
+            name,
+            candidates
+                .iter()
+                .map(|p| format!("  {}", p.display()))
+                .collect::<Vec<String>>()
+                .join("\n")
+        ))
+    }
+    fn load(&self, key: &str) -> Result<String, String> {
+        std::fs::read_to_string(key).map_err(|e| format!("Cannot read file {}: {}", key, e))
+    }
+}
+
+// serves modules from a name -> source table, for embedding and tests
+#[derive(Debug, Clone, Default)]
+pub struct MemoryModuleLoader {
+    modules: HashMap<String, String>,
+}
+
+impl MemoryModuleLoader {
+    pub fn new() -> MemoryModuleLoader {
+        MemoryModuleLoader::default()
+    }
+    pub fn add_module(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        self.modules.insert(name.into(), source.into());
+    }
+}
+
+impl ModuleLoader for MemoryModuleLoader {
+    fn resolve(&self, name: &str) -> Result<String, String> {
+        if self.modules.contains_key(name) {
+            Ok(name.to_string())
+        } else {
+            Err(format!(
+                "Cannot find module {}, tried:\n  in-memory modules",
+                name
+            ))
+        }
+    }
+    fn load(&self, key: &str) -> Result<String, String> {
+        match self.modules.get(key) {
+            Some(source) => Ok(source.clone()),
+            None => Err(format!("Cannot read module {}: not in memory", key)),
+        }
+    }
+}
+
+struct ModuleRegistryInner {
+    // exports of every module that finished running, keyed by the key its loader gave it
+    cache: HashMap<String, Rc<Value>>,
+    // modules that are currently running, outermost first
+    loading: Vec<String>,
+    loader: Rc<dyn ModuleLoader>,
+}
+
+impl Default for ModuleRegistryInner {
+    fn default() -> Self {
+        ModuleRegistryInner {
+            cache: HashMap::new(),
+            loading: vec![],
+            loader: Rc::new(FsModuleLoader::new()),
+        }
+    }
+}
+
+// one registry is shared by every scope chain of an interpreter (including the ones
+// imported modules run in), so it is a cheap handle around shared state
+#[derive(Clone, Default)]
+pub struct ModuleRegistry {
+    inner: Rc<RefCell<ModuleRegistryInner>>,
+}
+
+impl ModuleRegistry {
+    pub fn new() -> ModuleRegistry {
+        ModuleRegistry::default()
+    }
+    pub fn with_loader(loader: impl ModuleLoader + 'static) -> ModuleRegistry {
+        let registry = ModuleRegistry::new();
+        registry.set_loader(loader);
+        registry
+    }
+    pub fn set_loader(&self, loader: impl ModuleLoader + 'static) {
+        self.inner.borrow_mut().loader = Rc::new(loader);
+    }
+    fn get_loader(&self) -> Rc<dyn ModuleLoader> {
+        self.inner.borrow().loader.clone()
+    }
+    // finds the module an import refers to, the error lists every location that was tried
+    pub fn resolve(&self, name: &str) -> Result<String, String> {
+        if !name.starts_with(STD_PREFIX) {
+            return self.get_loader().resolve(name);
+        }
+        match STD_MODULES.iter().find(|(n, _)| *n == name) {
+            Some((n, _)) => Ok(n.to_string()),
+            None => Err(format!(
+                "Cannot find module {}, tried:\n  bundled library (has {})",
+                name,
+                STD_MODULES
+                    .iter()
+                    .map(|(n, _)| *n)
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            )),
+        }
+    }
+    pub fn load(&self, key: &str) -> Result<String, String> {
+        match STD_MODULES.iter().find(|(n, _)| *n == key) {
+            Some((_, source)) => Ok(source.to_string()),
+            None => self.get_loader().load(key),
+        }
+    }
+    pub fn get_cached(&self, key: &str) -> Option<Rc<Value>> {
+        self.inner.borrow().cache.get(key).cloned()
+    }
+    pub fn is_loaded(&self, key: &str) -> bool {
+        self.inner.borrow().cache.contains_key(key)
+    }
+    pub fn loaded_count(&self) -> usize {
+        self.inner.borrow().cache.len()
+    }
+    // marks a module as running, fails with the full import chain if it is already running
+    pub fn begin_loading(&self, key: &str) -> Result<(), String> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.loading.iter().any(|k| k == key) {
+            let mut chain = inner.loading.clone();
+            chain.push(key.to_string());
+            return Err(format!("Cyclic import detected: {}", chain.join(" -> ")));
+        }
+        inner.loading.push(key.to_string());
+        Ok(())
+    }
+    // pops the module off the loading stack, its exports are only cached if it ran successfully
+    pub fn finish_loading(&self, key: &str, exports: Option<Rc<Value>>) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(i) = inner.loading.iter().rposition(|k| k == key) {
+            inner.loading.remove(i);
+        }
+        if let Some(exports) = exports {
+            inner.cache.insert(key.to_string(), exports);
+        }
+    }
+}
+
+// registries are compared by identity, comparing contents would recurse through
+// module exports whose closures point back at the registry
+impl PartialEq for ModuleRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Debug for ModuleRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("ModuleRegistry")
+            .field("cached", &inner.cache.keys().collect::<Vec<&String>>())
+            .field("loading", &inner.loading)
+            .finish()
+    }
+}
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use super::{FsModuleLoader, MemoryModuleLoader, ModuleRegistry};
+    use crate::builtins::create_builtins;
+    use crate::parser::Parser;
+    use crate::scopechain::ScopeChain;
+
+    fn write_module(dir: &std::path::Path, name: &str, contents: &str) -> String {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    }
+    fn run(code: String, scope_chain: &mut ScopeChain) -> Result<(), String> {
+        create_builtins(scope_chain).map_err(|e| e.to_string())?;
+        let ast = Parser::new(code).parse(true).map_err(|e| e.to_string())?;
+        for stmt in ast.iter() {
+            stmt.interpret(scope_chain).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn module_runs_once() {
+        let dir = std::env::temp_dir().join("maple_module_runs_once");
+        let a = write_module(&dir, "a.mpl", "return { x = 1 }\n");
+        let code = format!("var first = import {}\nvar second = import {}\n", a, a);
+        let mut scope_chain = ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let first = scope_chain.get_variable(&"first".to_string(), 0).unwrap();
+        let second = scope_chain.get_variable(&"second".to_string(), 0).unwrap();
+        assert!(Rc::ptr_eq(&first, &second));
+        assert_eq!(scope_chain.get_modules().loaded_count(), 1);
+    }
+    #[test]
+    fn nested_import_shares_cache() {
+        let dir = std::env::temp_dir().join("maple_nested_import_shares_cache");
+        let leaf = write_module(&dir, "leaf.mpl", "return 1\n");
+        let mid = write_module(&dir, "mid.mpl", &format!("return import {}\n", leaf));
+        let code = format!("var a = import {}\nvar b = import {}\n", mid, leaf);
+        let mut scope_chain = ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        assert_eq!(scope_chain.get_modules().loaded_count(), 2);
+    }
+    #[test]
+    fn search_path_is_tried_in_order() {
+        let dir = std::env::temp_dir().join("maple_search_path_is_tried_in_order");
+        write_module(&dir.join("first"), "shared.mpl", "return 1\n");
+        write_module(&dir.join("second"), "shared.mpl", "return 2\n");
+        write_module(&dir.join("second"), "only_second.mpl", "return 3\n");
+        let mut loader = FsModuleLoader::new();
+        loader.add_search_path(dir.join("first"));
+        loader.add_search_path(dir.join("second"));
+        let mut scope_chain = ScopeChain::with_modules(ModuleRegistry::with_loader(loader));
+        let code = "var a = import shared.mpl\nvar b = import only_second.mpl\n".to_string();
+        run(code, &mut scope_chain).unwrap();
+        let a = scope_chain.get_variable(&"a".to_string(), 0).unwrap();
+        let b = scope_chain.get_variable(&"b".to_string(), 0).unwrap();
+        assert_eq!(*a, crate::parser::Value::Int(1));
+        assert_eq!(*b, crate::parser::Value::Int(3));
+    }
+    #[test]
+    fn resolve_failure_lists_tried_locations() {
+        let mut loader = FsModuleLoader::new();
+        loader.add_search_path("/maple/lib");
+        loader.add_search_path("/maple/vendor");
+        let modules = ModuleRegistry::with_loader(loader);
+        let err = modules.resolve("missing_module.mpl").unwrap_err();
+        assert!(err.contains("/maple/lib/missing_module.mpl"), "{}", err);
+        assert!(err.contains("/maple/vendor/missing_module.mpl"), "{}", err);
+        let err = modules.resolve("std/missing.mpl").unwrap_err();
+        assert!(err.contains("std/math.mpl"), "{}", err);
+    }
+    #[test]
+    fn bundled_std_module() {
+        let code = "const m = import std/math.mpl\nvar a = m.clamp(12, 0, 10)\n".to_string();
+        let mut scope_chain = ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let a = scope_chain.get_variable(&"a".to_string(), 0).unwrap();
+        assert_eq!(*a, crate::parser::Value::Int(10));
+    }
+    #[test]
+    fn cyclic_import_lists_chain() {
+        let dir = std::env::temp_dir().join("maple_cyclic_import_lists_chain");
+        let a_path = dir.join("a.mpl").display().to_string();
+        let b = write_module(&dir, "b.mpl", &format!("return import {}\n", a_path));
+        let a = write_module(&dir, "a.mpl", &format!("return import {}\n", b));
+        let mut scope_chain = ScopeChain::new();
+        let err = run(format!("var a = import {}\n", a), &mut scope_chain).unwrap_err();
+        let canon = |p: &String| std::fs::canonicalize(p).unwrap().display().to_string();
+        let chain = format!("{} -> {} -> {}", canon(&a), canon(&b), canon(&a));
+        assert!(err.contains(&chain), "{}", err);
+        // a failed import is not cached
+        assert_eq!(scope_chain.get_modules().loaded_count(), 0);
+    }
+    #[test]
+    fn memory_loader_serves_imports() {
+        let mut loader = MemoryModuleLoader::new();
+        loader.add_module("config.mpl", "return import helper.mpl\n");
+        loader.add_module("helper.mpl", "return { answer = 42 }\n");
+        let mut scope_chain = ScopeChain::with_modules(ModuleRegistry::with_loader(loader));
+        let code = "var a = import config.mpl\nvar b = import helper.mpl\n".to_string();
+        run(code, &mut scope_chain).unwrap();
+        let a = scope_chain.get_variable(&"a".to_string(), 0).unwrap();
+        let b = scope_chain.get_variable(&"b".to_string(), 0).unwrap();
+        assert!(Rc::ptr_eq(&a, &b));
+        assert!(scope_chain.get_modules().is_loaded("config.mpl"));
+        // the missing module has to be reported by the memory loader, not the filesystem one
+        let ast = Parser::new("var c = import nope.mpl\n".to_string())
+            .parse(true)
+            .unwrap();
+        let err = ast[0].interpret(&mut scope_chain).unwrap_err().to_string();
+        assert!(err.contains("Cannot find module nope.mpl"), "{}", err);
+        assert!(err.contains("in-memory modules"), "{}", err);
+    }
+    #[test]
+    fn memory_loader_cycle() {
+        let mut loader = MemoryModuleLoader::new();
+        loader.add_module("a.mpl", "return import b.mpl\n");
+        loader.add_module("b.mpl", "return import c.mpl\n");
+        loader.add_module("c.mpl", "return import a.mpl\n");
+        let mut scope_chain = ScopeChain::with_modules(ModuleRegistry::with_loader(loader));
+        let err = run("var a = import a.mpl\n".to_string(), &mut scope_chain).unwrap_err();
+        assert!(err.contains("a.mpl -> b.mpl -> c.mpl -> a.mpl"), "{}", err);
+    }
+}
@@ -1,13 +1,21 @@
 // turn off dead code warnings
 #![allow(dead_code)]
 use core::fmt;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 
 use crate::error::{MapleError, ParserError, RuntimeError, ScopeError};
 
-use crate::ast::{ASTType, Block, FnParam, FunctionLiteral, IfLiteral, AST};
+use crate::big::{BigDecimal, BigInt};
+
+use crate::ast::{
+    ASTType, Block, ComptimeLiteral, EnumLiteral, FieldKey, FnParam, ForLiteral, FunctionLiteral,
+    IfLiteral, MatchArm, MatchLiteral, RangeLiteral, StructLiteral, WhileLiteral, AST,
+};
+use crate::builtins::create_builtins;
+use crate::event_loop::Future;
 use crate::lexer::{Assoc, Lexer, Token, TokenType};
-use crate::scopechain::ScopeChain;
+use crate::runtime::value_to_ast;
+use crate::scopechain::{Address, ScopeChain};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum ObjectKey {
@@ -23,22 +31,93 @@ impl fmt::Display for ObjectKey {
     }
 }
 
+// one variant of an enum declaration, shared by every value made from it
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumVariant {
+    pub enum_name: String,
+    pub name: String,
+    pub fields: Vec<String>,
+}
+// a struct declaration, objects made by its constructor can only ever have these fields
 #[derive(Debug, Clone, PartialEq)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+// start..end by step, the numbers in it are worked out when they're needed instead of
+//  being stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+    pub inclusive: bool,
+}
+impl Range {
+    // the whole span of an i64 has one more number in it than a usize can count, so the
+    //  length stops at usize::MAX
+    pub fn len(&self) -> usize {
+        let (start, end, step) = (self.start as i128, self.end as i128, self.step as i128);
+        let end = if self.inclusive { end + step.signum() } else { end };
+        let span = if step > 0 { end - start } else { start - end };
+        if span <= 0 {
+            0
+        } else {
+            ((span + step.abs() - 1) / step.abs()).min(usize::MAX as i128) as usize
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn contains(&self, n: i64) -> bool {
+        let offset = n as i128 - self.start as i128;
+        let step = self.step as i128;
+        offset % step == 0 && offset / step >= 0 && ((offset / step) as usize) < self.len()
+    }
+    // i has to be less than len(), which keeps the number between start and end
+    pub fn get(&self, i: usize) -> i64 {
+        (self.start as i128 + self.step as i128 * i as i128) as i64
+    }
+    pub fn iter(&self) -> impl Iterator<Item = i64> {
+        let range = *self;
+        (0..range.len()).map(move |i| range.get(i))
+    }
+}
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}{}",
+            self.start,
+            if self.inclusive { "..=" } else { ".." },
+            self.end
+        )?;
+        if self.step != 1 {
+            write!(f, " by {}", self.step)?;
+        }
+        Ok(())
+    }
+}
+#[derive(Debug, Clone)]
 pub struct Object {
     pub fields: Vec<(ObjectKey, Rc<Value>)>,
+    // frozen objects can't have fields added, removed or reassigned
+    pub frozen: bool,
+    // set for objects made by a struct constructor, they can't have fields added
+    pub struct_def: Option<Rc<StructDef>>,
+    // a frozen object points weakly at each of its fields that isn't an object, so that
+    //  anything else holding one of them knows not to write into it in place
+    pub frozen_fields: Vec<Weak<Value>>,
+}
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        self.fields == other.fields && self.struct_def == other.struct_def
+    }
 }
 
 impl Object {
     pub fn get(&self, key: ObjectKey, line: usize) -> Result<Rc<Value>, Box<RuntimeError>> {
-        for (k, v) in self.fields.iter() {
-            if k == &key {
-                return Ok(v.clone());
-            }
-        }
-        Err(Box::new(RuntimeError::new(
-            format!("Object does not have key {:?}", key),
-            line,
-        )))
+        self.get_ref(&key, line)
     }
     pub fn get_ref(&self, key: &ObjectKey, line: usize) -> Result<Rc<Value>, Box<RuntimeError>> {
         for (k, v) in self.fields.iter() {
@@ -46,6 +125,12 @@ impl Object {
                 return Ok(v.clone());
             }
         }
+        if let Some(def) = &self.struct_def {
+            return Err(Box::new(RuntimeError::new(
+                format!("{} has no field {}", def.name, key),
+                line,
+            )));
+        }
         Err(Box::new(RuntimeError::new(
             format!("Object does not have key {:?}", key),
             line,
@@ -54,6 +139,10 @@ impl Object {
     pub fn set(&mut self, key: ObjectKey, value: Rc<Value>) {
         for (k, v) in self.fields.iter_mut() {
             if k == &key {
+                if Value::is_held_frozen(v) {
+                    *v = value;
+                    return;
+                }
                 let val_ptr = Rc::<Value>::as_ptr(&v) as *mut Value;
                 unsafe {
                     *val_ptr = value.as_ref().clone();
@@ -66,6 +155,10 @@ impl Object {
     pub fn set_ref(&mut self, key: &ObjectKey, value: Rc<Value>) {
         for (k, v) in self.fields.iter_mut() {
             if k == key {
+                if Value::is_held_frozen(v) {
+                    *v = value;
+                    return;
+                }
                 let val_ptr = Rc::<Value>::as_ptr(&v) as *mut Value;
                 unsafe {
                     *val_ptr = value.as_ref().clone();
@@ -75,8 +168,41 @@ impl Object {
         }
         self.fields.push((key.clone(), value));
     }
+    // takes the field out of the object, giving back its value if it had one
+    pub fn remove(&mut self, key: &ObjectKey) -> Option<Rc<Value>> {
+        let i = self.fields.iter().position(|(k, _)| k == key)?;
+        Some(self.fields.remove(i).1)
+    }
+    // takes an element out of an array, the ones after it move down so there's no gap
+    pub fn remove_element(&mut self, index: f64) -> Option<Rc<Value>> {
+        let removed = self.remove(&ObjectKey::Number(index))?;
+        for (k, _) in self.fields.iter_mut() {
+            if let ObjectKey::Number(n) = k {
+                if *n > index {
+                    *n -= 1.0;
+                }
+            }
+        }
+        Some(removed)
+    }
+    // an object is used as an array when it has fields and every one of them is numbered
+    pub fn is_array(&self) -> bool {
+        !self.fields.is_empty() && self.array_len() == self.fields.len()
+    }
+    // how many elements the object has as an array, which is how many numbered keys it has
+    pub fn array_len(&self) -> usize {
+        self.fields
+            .iter()
+            .filter(|(k, _)| matches!(k, ObjectKey::Number(_)))
+            .count()
+    }
     pub fn new() -> Object {
-        Object { fields: vec![] }
+        Object {
+            fields: vec![],
+            frozen: false,
+            struct_def: None,
+            frozen_fields: vec![],
+        }
     }
 }
 pub trait Unpack<T> {
@@ -108,8 +234,8 @@ impl Unpack<Rc<Value>> for Result<Rc<Value>, Box<RuntimeError>> {
 impl Unpack<Rc<Value>> for Rc<Value> {
     fn unpack(&self, scope_chain: &ScopeChain, line: usize) -> Result<Rc<Value>, ScopeError> {
         match self.as_ref() {
-            Value::Variable(name) => {
-                let value = scope_chain.get_variable(&name, line);
+            Value::Variable(name, address) => {
+                let value = scope_chain.get_variable_at(name, *address, line);
                 match value {
                     Ok(value) => value.unpack(scope_chain, line),
                     Err(e) => Err(e),
@@ -118,6 +244,11 @@ impl Unpack<Rc<Value>> for Rc<Value> {
             Value::ObjectAccess(v, key) => match v.as_ref() {
                 Value::Object(l) => match l.get(key.clone(), line) {
                     Ok(value) => value.unpack(scope_chain, line),
+                    Err(e) if l.struct_def.is_some() => Err(ScopeError::new(e.get_raw_msg(), line)),
+                    Err(_) if matches!(key, ObjectKey::Number(_)) => Err(ScopeError::new(
+                        format!("Index {} is out of range for length {}", key, l.array_len()),
+                        line,
+                    )),
                     Err(_) => Err(ScopeError::new(
                         format!("Object does not have key {}", key),
                         line,
@@ -150,16 +281,28 @@ impl Unpack<Rc<Value>> for Rc<Value> {
 pub enum Value {
     String(String),
     Number(f64),
+    Int(i64),
+    BigInt(BigInt),
+    BigDecimal(BigDecimal),
     Boolean(bool),
-    Variable(String),
+    Variable(String, Address),
     Char(char),
     Function(FunctionLiteral),
     Object(Object),
     ObjectAccess(Rc<Value>, ObjectKey),
     BuiltinFunction(
-        fn(Vec<Rc<Value>>, &AST, &ScopeChain, usize) -> Result<Rc<Value>, Box<RuntimeError>>,
+        fn(Vec<Rc<Value>>, &AST, &mut ScopeChain, usize) -> Result<Rc<Value>, Box<RuntimeError>>,
         usize,
     ),
+    // code made by quote, macros get their arguments as this too
+    Quote(Block),
+    // Shape.Circle before it's called, variants without fields are values right away
+    EnumConstructor(Rc<EnumVariant>),
+    Enum(Rc<EnumVariant>, Vec<Rc<Value>>),
+    // Point from struct Point { x, y }, calling it makes the object
+    StructConstructor(Rc<StructDef>),
+    Future(Rc<Future>),
+    Range(Range),
     Undefined,
 }
 impl Value {
@@ -172,20 +315,107 @@ impl Value {
                     .pretty_type(scope_chain, line),
                 _ => format!("unknown type"),
             },
-            Value::Object(_) => "object".to_string(),
+            Value::Object(obj) => match &obj.struct_def {
+                Some(def) => def.name.clone(),
+                None => "object".to_string(),
+            },
             Value::BuiltinFunction(_, count) => format!("builtin_function(<{}>)", count),
             Value::Function(_) => "function".to_string(),
             Value::String(_) => "string".to_string(),
-            Value::Number(_) => "number".to_string(),
+            Value::Number(_) => "float".to_string(),
+            Value::Int(_) => "int".to_string(),
+            Value::BigInt(_) => "bigint".to_string(),
+            Value::BigDecimal(_) => "decimal".to_string(),
             Value::Boolean(_) => "boolean".to_string(),
-            Value::Variable(name) => scope_chain
-                .get_variable(&name, line)
+            Value::Variable(name, address) => scope_chain
+                .get_variable_at(name, *address, line)
                 .unwrap()
                 .pretty_type(scope_chain, line),
             Value::Char(_) => "char".to_string(),
+            Value::Quote(_) => "quote".to_string(),
+            Value::EnumConstructor(v) => format!("constructor({}.{})", v.enum_name, v.name),
+            Value::Enum(v, _) => v.enum_name.clone(),
+            Value::StructConstructor(def) => format!("constructor({})", def.name),
+            Value::Future(_) => "future".to_string(),
+            Value::Range(_) => "range".to_string(),
             Value::Undefined => "undefined".to_string(),
         }
     }
+    // deep freezes an object in place, fields that aren't objects get swapped out for fresh
+    //  copies so that anything still aliasing them can't modify the frozen object
+    pub fn freeze(value: &Rc<Value>) {
+        if let Value::Object(obj) = value.as_ref() {
+            // already frozen objects only ever hold frozen objects, this also stops cycles
+            if obj.frozen {
+                return;
+            }
+            let obj_ptr = obj as *const Object as *mut Object;
+            unsafe {
+                (*obj_ptr).frozen = true;
+                for (_, v) in (*obj_ptr).fields.iter_mut() {
+                    match v.as_ref() {
+                        Value::Object(_) => Value::freeze(v),
+                        _ => {
+                            *v = Rc::new(v.as_ref().clone());
+                            (*obj_ptr).frozen_fields.push(Rc::downgrade(v));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    // makes a deep frozen copy of an object, used by const declarations so that
+    //  other variables holding the object can still modify their version
+    pub fn frozen_copy(value: &Rc<Value>) -> Rc<Value> {
+        Value::frozen_copy_inner(value, &mut vec![])
+    }
+    fn frozen_copy_inner(
+        value: &Rc<Value>,
+        copied: &mut Vec<(*const Value, Rc<Value>)>,
+    ) -> Rc<Value> {
+        match value.as_ref() {
+            Value::Object(obj) if !obj.frozen => {
+                let ptr = Rc::as_ptr(value);
+                if let Some((_, copy)) = copied.iter().find(|(p, _)| *p == ptr) {
+                    return copy.clone();
+                }
+                // the copy has to exist before the fields are copied so that cycles point to it
+                let copy = Rc::new(Value::Undefined);
+                copied.push((ptr, copy.clone()));
+                let mut new_obj = Object::new();
+                new_obj.frozen = true;
+                new_obj.struct_def = obj.struct_def.clone();
+                for (k, v) in obj.fields.iter() {
+                    let field = match v.as_ref() {
+                        Value::Object(_) => Value::frozen_copy_inner(v, copied),
+                        _ => {
+                            let field = Rc::new(v.as_ref().clone());
+                            new_obj.frozen_fields.push(Rc::downgrade(&field));
+                            field
+                        }
+                    };
+                    new_obj.fields.push((k.clone(), field));
+                }
+                let copy_ptr = Rc::as_ptr(&copy) as *mut Value;
+                unsafe {
+                    *copy_ptr = Value::Object(new_obj);
+                }
+                copy
+            }
+            _ => value.clone(),
+        }
+    }
+    pub fn is_frozen(&self) -> bool {
+        match self {
+            Value::Object(obj) => obj.frozen,
+            _ => false,
+        }
+    }
+    // whether writing into the value in place would change a frozen object, either because
+    //  it is one or because one of them holds it as a field
+    pub fn is_held_frozen(value: &Rc<Value>) -> bool {
+        value.is_frozen() || Rc::weak_count(value) > 0
+    }
 }
 #[derive(Debug, Clone, PartialEq)]
 pub struct Variable {
@@ -197,6 +427,12 @@ pub struct Variable {
 #[derive(Debug)]
 pub struct Parser {
     pub lexer: Lexer,
+    // labels of the loops being parsed, innermost last
+    loop_labels: Vec<String>,
+    // macros declared so far, calls to them get expanded while parsing
+    macros: Vec<(String, FunctionLiteral)>,
+    // whether each function being parsed is async, innermost last
+    async_fns: Vec<bool>,
 }
 fn usable_operator(
     op: &Token,
@@ -220,12 +456,48 @@ fn usable_operator(
 impl Parser {
     pub fn new(contents: String) -> Parser {
         let lexer = Lexer::new(contents);
-        Parser { lexer }
+        Parser {
+            lexer,
+            loop_labels: vec![],
+            macros: vec![],
+            async_fns: vec![],
+        }
+    }
+    // newlines don't end anything inside brackets or after an operator, so they get skipped there
+    fn next_token_skipping_newlines(&mut self) -> Result<Token, Box<dyn MapleError>> {
+        self.lexer.get_next_token()?;
+        self.skip_newlines()
+    }
+    // moves past newlines starting at the current token
+    fn skip_newlines(&mut self) -> Result<Token, Box<dyn MapleError>> {
+        let mut token = self.lexer.get_current_token();
+        while token.t == TokenType::EndOfStatement {
+            self.expect_newline(&token)?;
+            token = self.lexer.get_next_token()?;
+        }
+        Ok(token)
+    }
+    // inside of brackets and after an operator only a newline can go, a ; would end the
+    //  statement partway through
+    fn expect_newline(&self, token: &Token) -> Result<(), Box<dyn MapleError>> {
+        if self.lexer.is_semicolon(token) {
+            return Err(Box::new(ParserError::new(
+                "Unexpected ; in the middle of an expression".into(),
+                self.lexer.get_line(),
+                token.clone(),
+            )));
+        }
+        Ok(())
     }
     fn parse_function(&mut self, anon: bool) -> Result<Box<AST>, Box<dyn MapleError>> {
-        let mut params: Vec<FnParam> = vec![];
+        let is_async = self.lexer.get_current_token().t == TokenType::Async;
+        if is_async {
+            self.lexer.get_next_token()?;
+        }
         match self.lexer.get_current_token().t {
             TokenType::Fn => (),
+            // macros are declared just like functions
+            TokenType::Macro if !is_async => (),
             _ => {
                 return Err(Box::new(ParserError::new(
                     format!("Expected fn, got {:?}", self.lexer.get_current_token()).into(),
@@ -276,8 +548,36 @@ impl Parser {
                 )))
             }
         };
+        let func = self.parse_params_and_body(is_async)?;
+        if !anon {
+            Ok(Box::new(AST {
+                t: ASTType::OpEq(
+                    Box::new(AST {
+                        t: ASTType::VariableDeclaration(name.clone(), true),
+                        token: name_token,
+                    }),
+                    Box::new(AST {
+                        t: ASTType::FunctionLiteral(func),
+                        token: fn_token.clone(),
+                    }),
+                ),
+                token: fn_token.clone(),
+            }))
+        } else {
+            Ok(Box::new(AST {
+                t: ASTType::FunctionLiteral(func),
+                token: fn_token,
+            }))
+        }
+    }
+    // (params) { body }, the current token is the left paren
+    fn parse_params_and_body(
+        &mut self,
+        is_async: bool,
+    ) -> Result<FunctionLiteral, Box<dyn MapleError>> {
+        let mut params: Vec<FnParam> = vec![];
         loop {
-            match self.lexer.get_next_token()?.t {
+            match self.next_token_skipping_newlines()?.t {
                 TokenType::Ident(name) => params.push(FnParam {
                     name,
                     char_start: self.lexer.get_current_token().char_start,
@@ -296,7 +596,7 @@ impl Parser {
                     )))
                 }
             }
-            match self.lexer.get_next_token()?.t {
+            match self.next_token_skipping_newlines()?.t {
                 TokenType::RightParen => break,
                 TokenType::Comma => (),
                 _ => {
@@ -312,41 +612,44 @@ impl Parser {
             }
         }
         self.lexer.get_next_token()?;
-        let body = self.parse_block()?;
-        if !anon {
-            Ok(Box::new(AST {
-                t: ASTType::OpEq(
-                    Box::new(AST {
-                        t: ASTType::VariableDeclaration(name.clone(), true),
-                        token: name_token,
-                    }),
-                    Box::new(AST {
-                        t: ASTType::FunctionLiteral(FunctionLiteral::basic(params, body)),
-                        token: fn_token.clone(),
-                    }),
-                ),
-                token: fn_token.clone(),
-            }))
-        } else {
-            Ok(Box::new(AST {
-                t: ASTType::FunctionLiteral(FunctionLiteral::basic(params, body)),
-                token: fn_token,
-            }))
-        }
+        // loops outside of the function can't be broken out of from inside it
+        let loop_labels = std::mem::take(&mut self.loop_labels);
+        self.async_fns.push(is_async);
+        let body = self.parse_block();
+        self.async_fns.pop();
+        self.loop_labels = loop_labels;
+        let mut func = FunctionLiteral::basic(params, body?);
+        func.is_async = is_async;
+        Ok(func)
     }
     fn parse_object_literal(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
         let token = self.lexer.get_next_token()?;
-        let mut fields: Vec<(ObjectKey, Box<AST>)> = vec![];
+        let mut fields: Vec<(FieldKey, Box<AST>)> = vec![];
         loop {
-            while self.lexer.get_current_token().t == TokenType::EndOfStatement {
-                self.lexer.get_next_token()?;
-            }
-            if self.lexer.get_current_token().t == TokenType::RightBrace {
+            if self.skip_newlines()?.t == TokenType::RightBrace {
                 break;
             }
-            let name = match self.lexer.get_current_token().t {
-                TokenType::Ident(name) => ObjectKey::String(name),
-                TokenType::Number(num) => ObjectKey::Number(num),
+            let key_token = self.lexer.get_current_token();
+            let key = match self.lexer.get_current_token().t {
+                TokenType::Ident(name) => FieldKey::Static(ObjectKey::String(name)),
+                TokenType::Number(num) => FieldKey::Static(ObjectKey::Number(num)),
+                TokenType::Int(num) => FieldKey::Static(ObjectKey::Number(num as f64)),
+                // [expr] = value, the key is worked out when the object is made
+                TokenType::LeftSquare => {
+                    self.next_token_skipping_newlines()?;
+                    let key = self.parse_clause(1000)?;
+                    if self.next_token_skipping_newlines()?.t != TokenType::RightSquare {
+                        return Err(Box::new(ParserError::new(
+                            format!(
+                                "Expected right square bracket after a computed key, got {:?} in object literal",
+                                self.lexer.get_current_token()
+                            ),
+                            self.lexer.get_line(),
+                            self.lexer.get_current_token(),
+                        )));
+                    }
+                    FieldKey::Computed(key)
+                }
                 _ => {
                     return Err(Box::new(ParserError::new(
                         format!(
@@ -358,8 +661,34 @@ impl Parser {
                     )))
                 }
             };
-            match self.lexer.get_next_token()?.t {
-                TokenType::OpEq => (),
+            let name = match &key {
+                FieldKey::Static(ObjectKey::String(name)) => Some(name.clone()),
+                _ => None,
+            };
+            // whether the value ended on the comma or brace after it, which only happens
+            //  for shorthand fields
+            let mut at_separator = false;
+            let expr = match (self.lexer.get_next_token()?.t, name) {
+                (TokenType::OpEq, _) => {
+                    self.next_token_skipping_newlines()?;
+                    self.parse_clause(1000)?
+                }
+                // area() { } is the same as area = fn() { }
+                (TokenType::LeftParen, Some(_)) => Box::new(AST {
+                    t: ASTType::FunctionLiteral(self.parse_params_and_body(false)?),
+                    token: key_token,
+                }),
+                // {x, y} is the same as {x = x, y = y}
+                (
+                    TokenType::Comma | TokenType::RightBrace | TokenType::EndOfStatement,
+                    Some(name),
+                ) => {
+                    at_separator = true;
+                    Box::new(AST {
+                        t: ASTType::VariableAccess(name, Address::Unresolved),
+                        token: key_token,
+                    })
+                }
                 _ => {
                     return Err(Box::new(ParserError::new(
                         format!(
@@ -370,13 +699,17 @@ impl Parser {
                         self.lexer.get_current_token().clone(),
                     )))
                 }
-            }
-            self.lexer.get_next_token()?;
-            let expr = self.parse_clause(1000)?;
-            fields.push((name, expr));
-            match self.lexer.get_next_token()?.t {
+            };
+            fields.push((key, expr));
+            let separator = if at_separator {
+                self.lexer.get_current_token()
+            } else {
+                self.lexer.get_next_token()?
+            };
+            match separator.t {
                 TokenType::RightBrace => break,
-                TokenType::Comma | TokenType::EndOfStatement => (),
+                TokenType::Comma => (),
+                TokenType::EndOfStatement => self.expect_newline(&separator)?,
                 _ => {
                     return Err(Box::new(ParserError::new(
                         format!(
@@ -400,17 +733,15 @@ impl Parser {
         self.lexer.get_next_token()?;
         let mut fields: Vec<Box<AST>> = vec![];
         loop {
-            while self.lexer.get_current_token().t == TokenType::EndOfStatement {
-                self.lexer.get_next_token()?;
-            }
-            if self.lexer.get_current_token().t == TokenType::RightSquare {
+            if self.skip_newlines()?.t == TokenType::RightSquare {
                 break;
             }
             let expr = self.parse_clause(1000)?;
             fields.push(expr);
             match self.lexer.get_next_token()?.t {
                 TokenType::RightSquare => break,
-                TokenType::Comma | TokenType::EndOfStatement => (),
+                TokenType::Comma => (),
+                TokenType::EndOfStatement => self.expect_newline(&self.lexer.get_current_token())?,
                 _ => {
                     return Err(Box::new(ParserError::new(
                         format!(
@@ -429,6 +760,292 @@ impl Parser {
             token,
         }))
     }
+    fn parse_macro(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
+        let mut ast = self.parse_function(false)?;
+        if let ASTType::OpEq(decl, func) = &ast.t {
+            if let (ASTType::VariableDeclaration(name, _), ASTType::FunctionLiteral(func)) =
+                (&decl.t, &func.t)
+            {
+                self.macros.retain(|(n, _)| n != name);
+                self.macros.push((name.clone(), func.clone()));
+                ast.t = ASTType::Macro(name.clone(), func.clone());
+            }
+        }
+        Ok(ast)
+    }
+    // runs a macro with its arguments as quoted code, whatever it returns gets put in
+    //  place of the call
+    fn expand_macro(
+        &self,
+        name: &str,
+        args: Block,
+        token: Token,
+    ) -> Result<Box<AST>, Box<dyn MapleError>> {
+        let mac = match self.macros.iter().find(|(n, _)| n == name) {
+            Some((_, mac)) => mac,
+            None => {
+                return Err(Box::new(ParserError::new(
+                    format!("No macro named {}", name),
+                    token.line + 1,
+                    token,
+                )))
+            }
+        };
+        let expansion_error = |e: Box<RuntimeError>| -> Box<dyn MapleError> {
+            Box::new(ParserError::new(
+                format!("Error while expanding macro {}: {}", name, e.get_raw_msg()),
+                token.line + 1,
+                token.clone(),
+            ))
+        };
+        let mut scope_chain = ScopeChain::new();
+        create_builtins(&mut scope_chain).map_err(expansion_error)?;
+        let args = args
+            .into_iter()
+            .map(|arg| Rc::new(Value::Quote(vec![arg])))
+            .collect();
+        let value = mac
+            .call_values(&mut scope_chain, args, token.line + 1)
+            .map_err(expansion_error)?;
+        let mut ast =
+            value_to_ast(&value, token.clone(), &scope_chain).map_err(expansion_error)?;
+        ast.set_token(&token);
+        Ok(ast)
+    }
+    // runs comptime code while parsing, what it gives back gets turned into literals
+    fn eval_comptime(
+        &self,
+        code: &Block,
+        is_block: bool,
+        token: &Token,
+    ) -> Result<Box<AST>, Box<dyn MapleError>> {
+        let comptime_error = |e: Box<RuntimeError>| -> Box<dyn MapleError> {
+            Box::new(ParserError::new(
+                format!("Error in comptime: {}", e.get_raw_msg()),
+                e.get_line(),
+                token.clone(),
+            ))
+        };
+        let mut scope_chain = ScopeChain::new();
+        create_builtins(&mut scope_chain).map_err(comptime_error)?;
+        let line = token.line + 1;
+        let value = if is_block {
+            // a block works just like a function body, it gives back whatever it returns
+            FunctionLiteral::basic(vec![], code.clone()).call_values(&mut scope_chain, vec![], line)
+        } else {
+            code[0]
+                .get_value(&mut scope_chain)
+                .unpack_and_transform(&scope_chain, line, &code[0])
+        }
+        .map_err(comptime_error)?;
+        value_to_ast(&value, token.clone(), &scope_chain).map_err(comptime_error)
+    }
+    // enum Name { Variant(field, field), Other }, variants are split by commas or newlines
+    fn parse_enum(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
+        let token = self.lexer.get_current_token();
+        let name = match self.lexer.get_next_token()?.t {
+            TokenType::Ident(name) => name,
+            _ => {
+                return Err(Box::new(ParserError::new(
+                    format!(
+                        "Expected enum name, got {:?}",
+                        self.lexer.get_current_token()
+                    ),
+                    self.lexer.get_line(),
+                    self.lexer.get_current_token(),
+                )))
+            }
+        };
+        if self.lexer.get_next_token()?.t != TokenType::LeftBrace {
+            return Err(Box::new(ParserError::new(
+                format!(
+                    "Expected left brace after enum {}, got {:?}",
+                    name,
+                    self.lexer.get_current_token()
+                ),
+                self.lexer.get_line(),
+                self.lexer.get_current_token(),
+            )));
+        }
+        let mut variants: Vec<(String, Vec<String>)> = vec![];
+        loop {
+            let variant = match self.lexer.get_next_token()?.t {
+                TokenType::EndOfStatement | TokenType::Comma => continue,
+                TokenType::RightBrace => break,
+                TokenType::Ident(variant) => variant,
+                _ => {
+                    return Err(Box::new(ParserError::new(
+                        format!(
+                            "Expected variant name in enum {}, got {:?}",
+                            name,
+                            self.lexer.get_current_token()
+                        ),
+                        self.lexer.get_line(),
+                        self.lexer.get_current_token(),
+                    )))
+                }
+            };
+            if variants.iter().any(|(v, _)| v == &variant) {
+                return Err(Box::new(ParserError::new(
+                    format!("Enum {} already has a variant named {}", name, variant),
+                    self.lexer.get_line(),
+                    self.lexer.get_current_token(),
+                )));
+            }
+            let mut fields = vec![];
+            if self.lexer.peek_next_token()?.t == TokenType::LeftParen {
+                self.lexer.get_next_token()?;
+                loop {
+                    match self.next_token_skipping_newlines()?.t {
+                        TokenType::Ident(field) => fields.push(field),
+                        TokenType::RightParen if fields.is_empty() => break,
+                        _ => {
+                            return Err(Box::new(ParserError::new(
+                                format!(
+                                    "Expected field name in {}.{}, got {:?}",
+                                    name,
+                                    variant,
+                                    self.lexer.get_current_token()
+                                ),
+                                self.lexer.get_line(),
+                                self.lexer.get_current_token(),
+                            )))
+                        }
+                    }
+                    match self.next_token_skipping_newlines()?.t {
+                        TokenType::Comma => (),
+                        TokenType::RightParen => break,
+                        _ => {
+                            return Err(Box::new(ParserError::new(
+                                format!(
+                                    "Expected comma or right paren in {}.{}, got {:?}",
+                                    name,
+                                    variant,
+                                    self.lexer.get_current_token()
+                                ),
+                                self.lexer.get_line(),
+                                self.lexer.get_current_token(),
+                            )))
+                        }
+                    }
+                }
+            }
+            variants.push((variant, fields));
+        }
+        Ok(Box::new(AST {
+            t: ASTType::Enum(EnumLiteral { name, variants }),
+            token,
+        }))
+    }
+    // struct Name { field, field }, fields are split by commas or newlines
+    fn parse_struct(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
+        let token = self.lexer.get_current_token();
+        let name = match self.lexer.get_next_token()?.t {
+            TokenType::Ident(name) => name,
+            _ => {
+                return Err(Box::new(ParserError::new(
+                    format!(
+                        "Expected struct name, got {:?}",
+                        self.lexer.get_current_token()
+                    ),
+                    self.lexer.get_line(),
+                    self.lexer.get_current_token(),
+                )))
+            }
+        };
+        if self.lexer.get_next_token()?.t != TokenType::LeftBrace {
+            return Err(Box::new(ParserError::new(
+                format!(
+                    "Expected left brace after struct {}, got {:?}",
+                    name,
+                    self.lexer.get_current_token()
+                ),
+                self.lexer.get_line(),
+                self.lexer.get_current_token(),
+            )));
+        }
+        let mut fields: Vec<String> = vec![];
+        loop {
+            let field = match self.lexer.get_next_token()?.t {
+                TokenType::EndOfStatement | TokenType::Comma => continue,
+                TokenType::RightBrace => break,
+                TokenType::Ident(field) => field,
+                _ => {
+                    return Err(Box::new(ParserError::new(
+                        format!(
+                            "Expected field name in struct {}, got {:?}",
+                            name,
+                            self.lexer.get_current_token()
+                        ),
+                        self.lexer.get_line(),
+                        self.lexer.get_current_token(),
+                    )))
+                }
+            };
+            if fields.contains(&field) {
+                return Err(Box::new(ParserError::new(
+                    format!("Struct {} already has a field named {}", name, field),
+                    self.lexer.get_line(),
+                    self.lexer.get_current_token(),
+                )));
+            }
+            fields.push(field);
+        }
+        Ok(Box::new(AST {
+            t: ASTType::Struct(StructLiteral { name, fields }),
+            token,
+        }))
+    }
+    // match value { pattern => expr, pattern => { block } }, arms are split by commas or newlines
+    fn parse_match(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
+        let token = self.lexer.get_current_token();
+        self.lexer.get_next_token()?;
+        let value = self.parse_clause(1000)?;
+        if self.lexer.get_next_token()?.t != TokenType::LeftBrace {
+            return Err(Box::new(ParserError::new(
+                format!(
+                    "Expected left brace after match value, got {:?}",
+                    self.lexer.get_current_token()
+                ),
+                self.lexer.get_line(),
+                self.lexer.get_current_token(),
+            )));
+        }
+        let mut arms = vec![];
+        loop {
+            match self.lexer.get_next_token()?.t {
+                TokenType::EndOfStatement | TokenType::Comma => continue,
+                TokenType::RightBrace => break,
+                _ => (),
+            }
+            let pattern = self.parse_clause(1000)?;
+            if self.lexer.get_next_token()?.t != TokenType::Arrow {
+                return Err(Box::new(ParserError::new(
+                    format!(
+                        "Expected => after match pattern, got {:?}",
+                        self.lexer.get_current_token()
+                    ),
+                    self.lexer.get_line(),
+                    self.lexer.get_current_token(),
+                )));
+            }
+            let is_block = self.lexer.get_next_token()?.t == TokenType::LeftBrace;
+            let body = if is_block {
+                self.parse_block()?
+            } else {
+                vec![self.parse_clause(1000)?]
+            };
+            arms.push(MatchArm {
+                pattern,
+                body,
+                is_block,
+            });
+        }
+        Ok(Box::new(AST {
+            t: ASTType::Match(MatchLiteral { value, arms }),
+            token,
+        }))
+    }
     fn parse_clause(&mut self, max_op_prec: i32) -> Result<Box<AST>, Box<dyn MapleError>> {
         let mut ret: Option<Box<AST>>;
 
@@ -436,26 +1053,35 @@ impl Parser {
             op if op.is_unary_prefix_op() && op.get_unary_op_prec(&self.lexer)? <= max_op_prec => {
                 let token = self.lexer.get_current_token();
                 self.lexer.get_next_token()?;
-                let innards = self.parse_clause(op.get_unary_op_prec(&self.lexer)?)?;
-                match op.t {
-                    TokenType::OpMns => {
-                        ret = Some(Box::new(AST {
-                            t: ASTType::OpMnsPrefix(innards),
-                            token,
-                        }));
-                    }
-                    TokenType::OpNot => {
-                        ret = Some(Box::new(AST {
-                            t: ASTType::OpNot(innards),
-                            token,
-                        }));
-                    }
-                    _ => {
-                        return Err(Box::new(ParserError::new(
-                            format!("Unusable unary prefix operator {:?}", op),
-                            self.lexer.get_line(),
-                            op,
-                        )))
+                let literal = self.lexer.get_current_token();
+                // i64::MIN can only be written negated, its digits alone don't fit in an int
+                if op.t == TokenType::OpMns && literal.t == TokenType::Int(i64::MIN) {
+                    ret = Some(Box::new(AST {
+                        t: ASTType::IntLiteral(i64::MIN),
+                        token,
+                    }));
+                } else {
+                    let innards = self.parse_clause(op.get_unary_op_prec(&self.lexer)?)?;
+                    match op.t {
+                        TokenType::OpMns => {
+                            ret = Some(Box::new(AST {
+                                t: ASTType::OpMnsPrefix(innards),
+                                token,
+                            }));
+                        }
+                        TokenType::OpNot => {
+                            ret = Some(Box::new(AST {
+                                t: ASTType::OpNot(innards),
+                                token,
+                            }));
+                        }
+                        _ => {
+                            return Err(Box::new(ParserError::new(
+                                format!("Unusable unary prefix operator {:?}", op),
+                                self.lexer.get_line(),
+                                op,
+                            )))
+                        }
                     }
                 }
             }
@@ -469,13 +1095,43 @@ impl Parser {
                 TokenType::LeftSquare => {
                     ret = Some(self.parse_array_literal()?);
                 }
-                TokenType::Fn => ret = Some(self.parse_function(true)?),
+                TokenType::Fn | TokenType::Async => ret = Some(self.parse_function(true)?),
+                TokenType::Await => {
+                    let token = self.lexer.get_current_token();
+                    if self.async_fns.last() == Some(&false) {
+                        return Err(Box::new(ParserError::new(
+                            "await can only be used inside of an async fn or at the top level"
+                                .into(),
+                            self.lexer.get_line(),
+                            token,
+                        )));
+                    }
+                    self.lexer.get_next_token()?;
+                    // binds as tightly as a unary prefix operator
+                    ret = Some(Box::new(AST {
+                        t: ASTType::Await(self.parse_clause(3)?),
+                        token,
+                    }));
+                }
                 TokenType::Number(num) => {
                     ret = Some(Box::new(AST {
                         t: ASTType::NumberLiteral(num),
                         token: self.lexer.get_current_token(),
                     }))
                 }
+                TokenType::Int(i64::MIN) => {
+                    return Err(Box::new(ParserError::new(
+                        "Integer 9223372036854775808 does not fit in 64 bits".into(),
+                        self.lexer.get_line(),
+                        self.lexer.get_current_token(),
+                    )))
+                }
+                TokenType::Int(num) => {
+                    ret = Some(Box::new(AST {
+                        t: ASTType::IntLiteral(num),
+                        token: self.lexer.get_current_token(),
+                    }))
+                }
                 TokenType::String(str) => {
                     ret = Some(Box::new(AST {
                         t: ASTType::StringLiteral(str),
@@ -502,21 +1158,78 @@ impl Parser {
                 }
                 TokenType::Ident(name) => {
                     ret = Some(Box::new(AST {
-                        t: ASTType::VariableAccess(name),
+                        t: ASTType::VariableAccess(name, Address::Unresolved),
                         token: self.lexer.get_current_token(),
                     }))
                 }
                 TokenType::LeftBrace => {
                     ret = Some(self.parse_object_literal()?);
                 }
+                TokenType::Match => ret = Some(self.parse_match()?),
+                TokenType::Comptime => {
+                    let token = self.lexer.get_current_token();
+                    let is_block = self.lexer.peek_next_token()?.t == TokenType::LeftBrace;
+                    self.lexer.get_next_token()?;
+                    let code = if is_block {
+                        self.parse_block()?
+                    } else {
+                        vec![self.parse_clause(1000)?]
+                    };
+                    let value = self.eval_comptime(&code, is_block, &token)?;
+                    ret = Some(Box::new(AST {
+                        t: ASTType::Comptime(ComptimeLiteral {
+                            code,
+                            is_block,
+                            value,
+                        }),
+                        token,
+                    }));
+                }
+                TokenType::Quote => {
+                    let token = self.lexer.get_current_token();
+                    self.lexer.get_next_token()?;
+                    ret = Some(Box::new(AST {
+                        t: ASTType::Quote(self.parse_block()?),
+                        token,
+                    }));
+                }
+                TokenType::Unquote => {
+                    let token = self.lexer.get_current_token();
+                    if self.lexer.get_next_token()?.t != TokenType::LeftParen {
+                        return Err(Box::new(ParserError::new(
+                            format!(
+                                "Expected left paren after unquote, got {:?}",
+                                self.lexer.get_current_token()
+                            ),
+                            self.lexer.get_line(),
+                            self.lexer.get_current_token(),
+                        )));
+                    }
+                    self.lexer.get_next_token()?;
+                    let innards = self.parse_clause(1000)?;
+                    if self.lexer.get_next_token()?.t != TokenType::RightParen {
+                        return Err(Box::new(ParserError::new(
+                            format!(
+                                "Expected right paren after unquote, got {:?}",
+                                self.lexer.get_current_token()
+                            ),
+                            self.lexer.get_line(),
+                            self.lexer.get_current_token(),
+                        )));
+                    }
+                    ret = Some(Box::new(AST {
+                        t: ASTType::Unquote(innards),
+                        token,
+                    }));
+                }
                 TokenType::LeftParen => {
                     let token = self.lexer.get_current_token();
-                    _ = self.lexer.get_next_token()?;
+                    self.next_token_skipping_newlines()?;
                     ret = Some(Box::new(AST {
                         t: ASTType::Paren(self.parse_clause(1000)?),
                         token,
                     }));
-                    match self.lexer.get_next_token()?.t {
+                    match self.next_token_skipping_newlines()?.t {
                     TokenType::RightParen => (),
                     _ => {
                         return Err(Box::new(ParserError::new(
@@ -548,16 +1261,17 @@ impl Parser {
                     self.lexer.get_next_token()?;
                     let token = self.lexer.get_current_token();
                     let mut args: Block = vec![];
+                    self.next_token_skipping_newlines()?;
                     loop {
-                        if args.len() == 0
-                            && self.lexer.get_next_token()?.t == TokenType::RightParen
+                        if args.is_empty()
+                            && self.lexer.get_current_token().t == TokenType::RightParen
                         {
                             break;
                         }
                         args.push(self.parse_clause(1000)?);
-                        match self.lexer.get_next_token()?.t {
+                        match self.next_token_skipping_newlines()?.t {
                             TokenType::RightParen => break,
-                            TokenType::Comma => _ = self.lexer.get_next_token()?,
+                            TokenType::Comma => _ = self.next_token_skipping_newlines()?,
                             _ => {
                                 return Err(Box::new(ParserError::new(
                                     format!(
@@ -570,16 +1284,53 @@ impl Parser {
                             }
                         }
                     }
-                    ret = Some(Box::new(AST {
-                        token,
-                        t: ASTType::FunctionCall(ret.unwrap(), args),
-                    }));
+                    let func = ret.unwrap();
+                    ret = Some(match &func.t {
+                        ASTType::VariableAccess(name, _)
+                            if self.macros.iter().any(|(n, _)| n == name) =>
+                        {
+                            let expansion = self.expand_macro(name, args.clone(), token.clone())?;
+                            Box::new(AST {
+                                t: ASTType::MacroCall(
+                                    Box::new(AST {
+                                        t: ASTType::FunctionCall(func.clone(), args),
+                                        token: token.clone(),
+                                    }),
+                                    expansion,
+                                ),
+                                token,
+                            })
+                        }
+                        _ => Box::new(AST {
+                            token,
+                            t: ASTType::FunctionCall(func, args),
+                        }),
+                    });
                 }
                 TokenType::LeftSquare => {
                     let token = self.lexer.get_next_token()?;
-                    _ = self.lexer.get_next_token()?;
-                    let index = self.parse_clause(1000)?;
-                    match self.lexer.get_next_token()?.t {
+                    self.next_token_skipping_newlines()?;
+                    // arr[i], or arr[start:end] where either end can be left out
+                    let index = match self.lexer.get_current_token().t {
+                        TokenType::Colon => None,
+                        _ => {
+                            let index = self.parse_clause(1000)?;
+                            self.next_token_skipping_newlines()?;
+                            Some(index)
+                        }
+                    };
+                    let slice_end = match self.lexer.get_current_token().t {
+                        TokenType::Colon => match self.next_token_skipping_newlines()?.t {
+                            TokenType::RightSquare => Some(None),
+                            _ => {
+                                let end = self.parse_clause(1000)?;
+                                self.next_token_skipping_newlines()?;
+                                Some(Some(end))
+                            }
+                        },
+                        _ => None,
+                    };
+                    match self.lexer.get_current_token().t {
                         TokenType::RightSquare => (),
                         _ => {
                             return Err(Box::new(ParserError::new(
@@ -593,7 +1344,11 @@ impl Parser {
                         }
                     }
                     ret = Some(Box::new(AST {
-                        t: ASTType::BracketAccess(ret.unwrap(), index),
+                        t: match slice_end {
+                            Some(end) => ASTType::Slice(ret.unwrap(), index, end),
+                            // there's always an index when there's no colon
+                            None => ASTType::BracketAccess(ret.unwrap(), index.unwrap()),
+                        },
                         token,
                     }));
                 }
@@ -626,7 +1381,8 @@ impl Parser {
             && usable_operator(&self.lexer.peek_next_token()?, max_op_prec, &self.lexer)?
         {
             let op = self.lexer.get_next_token()?;
-            _ = self.lexer.get_next_token()?;
+            // the right side can be on the next line when the operator ends the line
+            self.next_token_skipping_newlines()?;
             let rhs = self.parse_clause(op.get_op_prec(&self.lexer)?)?;
 
             ret = Some(match op.t {
@@ -642,10 +1398,23 @@ impl Parser {
                 TokenType::OpLt => Box::new(AST{t:ASTType::OpLt(ret.unwrap(), rhs), token: op}),
                 TokenType::OpGtEq => Box::new(AST{t:ASTType::OpGtEq(ret.unwrap(), rhs), token: op}),
                 TokenType::OpLtEq => Box::new(AST{t:ASTType::OpLtEq(ret.unwrap(), rhs), token: op}),
+                TokenType::In => Box::new(AST{t:ASTType::OpIn(ret.unwrap(), rhs), token: op}),
                 TokenType::OpAndAnd => {
                     Box::new(AST{t:ASTType::OpAndAnd(ret.unwrap(), rhs), token: op})
                 }
                 TokenType::OpOrOr => Box::new(AST{t:ASTType::OpOrOr(ret.unwrap(), rhs), token: op}),
+                TokenType::OpDotDot | TokenType::OpDotDotEq => {
+                    let step = self.parse_range_step()?;
+                    Box::new(AST {
+                        t: ASTType::Range(RangeLiteral {
+                            start: ret.unwrap(),
+                            end: rhs,
+                            step,
+                            inclusive: op.t == TokenType::OpDotDotEq,
+                        }),
+                        token: op,
+                    })
+                }
                 _ => {
                     return Err(Box::new(ParserError::new(
                         format!("Operator not implemented in parse clause {:?}, note: this is an internal error, nothing necessarily wrong with ur code", op),
@@ -686,7 +1455,7 @@ impl Parser {
         match self.lexer.get_next_token()?.t {
             TokenType::OpEq => {
                 let token = self.lexer.get_current_token();
-                self.lexer.get_next_token()?;
+                self.next_token_skipping_newlines()?;
                 let expr = self.parse_clause(
                     Token {
                         t: TokenType::OpEq,
@@ -747,19 +1516,8 @@ impl Parser {
                 )))
             }
         };
-        match self.lexer.get_next_token()?.t {
-            TokenType::EndOfStatement => (),
-            _ => {
-                return Err(Box::new(ParserError::new(
-                    format!(
-                        "Expected newline after left brace, got {:?}",
-                        self.lexer.get_current_token()
-                    ),
-                    self.lexer.get_line(),
-                    self.lexer.get_current_token(),
-                )))
-            }
-        };
+        // the first statement can be on the same line as the brace, like if x { a(); b() }
+        self.lexer.get_next_token()?;
         let body = self.parse(false)?;
         Ok(body)
     }
@@ -770,21 +1528,148 @@ impl Parser {
         let body = self.parse_block()?;
         Ok((cond, body))
     }
-    fn parse_while(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
+    // parses while, do while and loop, the current token is the keyword
+    fn parse_while(&mut self, label: Option<String>) -> Result<Box<AST>, Box<dyn MapleError>> {
         let token = self.lexer.get_current_token();
         self.lexer.get_next_token()?;
-        let (cond, body) = self.parse_condition_and_block()?;
+        if let Some(label) = &label {
+            self.loop_labels.push(label.clone());
+        }
+        let cond_and_body = match token.t {
+            TokenType::Loop => self.parse_block().map(|body| (None, body)),
+            TokenType::Do => self.parse_do_while(),
+            _ => self
+                .parse_condition_and_block()
+                .map(|(cond, body)| (Some(cond), body)),
+        };
+        if label.is_some() {
+            self.loop_labels.pop();
+        }
+        let (cond, body) = cond_and_body?;
+        self.lexer.get_next_token()?;
+        let else_body = self.parse_loop_else()?;
+        if cond.is_none() && else_body.is_some() {
+            return Err(Box::new(ParserError::new(
+                "loop can only be ended by break, so it can't have an else block".into(),
+                self.lexer.get_line(),
+                token,
+            )));
+        }
+        Ok(Box::new(AST {
+            t: ASTType::While(WhileLiteral {
+                cond,
+                is_do_while: token.t == TokenType::Do,
+                body,
+                else_body,
+                label,
+            }),
+            token,
+        }))
+    }
+    // for x in iterable { }, the current token is for
+    fn parse_for(&mut self, label: Option<String>) -> Result<Box<AST>, Box<dyn MapleError>> {
+        let token = self.lexer.get_current_token();
+        let var = match self.lexer.get_next_token()?.t {
+            TokenType::Ident(name) => name,
+            _ => {
+                return Err(Box::new(ParserError::new(
+                    format!(
+                        "Expected a variable name after for, got {:?}",
+                        self.lexer.get_current_token()
+                    ),
+                    self.lexer.get_line(),
+                    self.lexer.get_current_token(),
+                )))
+            }
+        };
+        if self.lexer.get_next_token()?.t != TokenType::In {
+            return Err(Box::new(ParserError::new(
+                format!(
+                    "Expected in after the variable of a for loop, got {:?}",
+                    self.lexer.get_current_token()
+                ),
+                self.lexer.get_line(),
+                self.lexer.get_current_token(),
+            )));
+        }
         self.lexer.get_next_token()?;
+        if let Some(label) = &label {
+            self.loop_labels.push(label.clone());
+        }
+        let iter_and_body = self.parse_condition_and_block();
+        if label.is_some() {
+            self.loop_labels.pop();
+        }
+        let (iter, body) = iter_and_body?;
+        Ok(Box::new(AST {
+            t: ASTType::For(ForLiteral {
+                var,
+                iter,
+                body,
+                label,
+            }),
+            token,
+        }))
+    }
+    // the by step after a range, it has to be on the same line
+    fn parse_range_step(&mut self) -> Result<Option<Box<AST>>, Box<dyn MapleError>> {
+        match self.lexer.peek_next_token()?.t {
+            TokenType::Ident(name) if name == "by" => {
+                self.lexer.get_next_token()?;
+                self.lexer.get_next_token()?;
+                Ok(Some(self.parse_clause(8)?))
+            }
+            _ => Ok(None),
+        }
+    }
+    // do { } while cond, the condition has to be on the same line as the closing brace
+    fn parse_do_while(&mut self) -> Result<(Option<Box<AST>>, Block), Box<dyn MapleError>> {
+        let body = self.parse_block()?;
+        match self.lexer.get_next_token()?.t {
+            TokenType::While => (),
+            _ => {
+                return Err(Box::new(ParserError::new(
+                    format!(
+                        "Expected while after do block, got {:?}",
+                        self.lexer.get_current_token()
+                    ),
+                    self.lexer.get_line(),
+                    self.lexer.get_current_token(),
+                )))
+            }
+        }
+        self.lexer.get_next_token()?;
+        let cond = self.parse_clause(1000)?;
+        Ok((Some(cond), body))
+    }
+    // the else block after a loop, whatever is found instead gets fed back to the lexer
+    fn parse_loop_else(&mut self) -> Result<Option<Block>, Box<dyn MapleError>> {
+        let mut feed_token = Token {
+            t: TokenType::EndOfStatement,
+            line: self.lexer.get_line(),
+            char_start: 0,
+            char_end: 0,
+        };
+        while self.lexer.get_current_token().t == TokenType::EndOfStatement {
+            self.lexer.get_next_token()?;
+        }
+        let else_body = if self.lexer.get_current_token().t == TokenType::Else {
+            self.lexer.get_next_token()?;
+            Some(self.parse_block()?)
+        } else {
+            feed_token = self.lexer.get_current_token();
+            None
+        };
         self.lexer.feed_token(Token {
             t: TokenType::EndOfStatement,
             line: self.lexer.get_line(),
             char_start: 0,
             char_end: 0,
         });
-        Ok(Box::new(AST {
-            t: ASTType::While(cond, body),
-            token,
-        }))
+        if feed_token.t != TokenType::EndOfStatement {
+            self.lexer.feed_token(feed_token);
+        }
+        Ok(else_body)
     }
     fn parse_if(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
         let token = self.lexer.get_current_token();
@@ -846,18 +1731,58 @@ impl Parser {
     fn parse_break(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
         let token = self.lexer.get_current_token();
         // self.lexer.get_next_token()?;
+        let label = self.parse_loop_label()?;
         Ok(Box::new(AST {
-            t: ASTType::Break,
+            t: ASTType::Break(label),
             token,
         }))
     }
-    fn parse_continue(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
-        let token = self.lexer.get_current_token();
-        // self.lexer.get_next_token()?;
-        Ok(Box::new(AST {
-            t: ASTType::Continue,
-            token,
-        }))
+    fn parse_continue(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
+        let token = self.lexer.get_current_token();
+        // self.lexer.get_next_token()?;
+        let label = self.parse_loop_label()?;
+        Ok(Box::new(AST {
+            t: ASTType::Continue(label),
+            token,
+        }))
+    }
+    // the optional label after break or continue, it has to belong to a loop around it
+    fn parse_loop_label(&mut self) -> Result<Option<String>, Box<dyn MapleError>> {
+        let name = match self.lexer.peek_next_token()?.t {
+            TokenType::Ident(name) => name,
+            _ => return Ok(None),
+        };
+        let token = self.lexer.get_next_token()?;
+        if !self.loop_labels.contains(&name) {
+            return Err(Box::new(ParserError::new(
+                format!("No enclosing loop has the label {}", name),
+                self.lexer.get_line(),
+                token,
+            )));
+        }
+        Ok(Some(name))
+    }
+    // label: while ...
+    fn parse_labeled_loop(&mut self, label: String) -> Result<Box<AST>, Box<dyn MapleError>> {
+        let label_token = self.lexer.get_current_token();
+        if self.loop_labels.contains(&label) {
+            return Err(Box::new(ParserError::new(
+                format!("Label {} is already used by an enclosing loop", label),
+                self.lexer.get_line(),
+                label_token,
+            )));
+        }
+        // skip over the colon
+        self.lexer.get_next_token()?;
+        match self.lexer.get_next_token()?.t {
+            TokenType::While | TokenType::Loop | TokenType::Do => self.parse_while(Some(label)),
+            TokenType::For => self.parse_for(Some(label)),
+            _ => Err(Box::new(ParserError::new(
+                format!("Label {} has to be followed by a loop", label),
+                self.lexer.get_line(),
+                label_token,
+            ))),
+        }
     }
     fn parse_return(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
         let token = self.lexer.get_current_token();
@@ -869,6 +1794,26 @@ impl Parser {
             token,
         }))
     }
+    // delete obj.key or delete obj[key]
+    fn parse_delete(&mut self) -> Result<Box<AST>, Box<dyn MapleError>> {
+        let token = self.lexer.get_current_token();
+        self.lexer.get_next_token()?;
+        let field = self.parse_clause(1000)?;
+        match field.t {
+            ASTType::DotAccess(_, _) | ASTType::BracketAccess(_, _) => Ok(Box::new(AST {
+                t: ASTType::Delete(field),
+                token,
+            })),
+            _ => Err(Box::new(ParserError::new(
+                format!(
+                    "delete needs a field like obj.key or obj[key], got {}",
+                    field.pretty_print()
+                ),
+                self.lexer.get_line(),
+                token,
+            ))),
+        }
+    }
     pub fn parse(&mut self, top_level: bool) -> Result<Block, Box<dyn MapleError>> {
         let mut ret: Block = vec![];
         if top_level {
@@ -876,18 +1821,35 @@ impl Parser {
         }
         loop {
             let ast = match self.lexer.get_current_token().t {
-                TokenType::Fn => Some(self.parse_function(false)?),
+                TokenType::Fn | TokenType::Async => Some(self.parse_function(false)?),
                 TokenType::Break => Some(self.parse_break()?),
                 TokenType::Continue => Some(self.parse_continue()?),
                 TokenType::Return => Some(self.parse_return()?),
+                TokenType::Delete => Some(self.parse_delete()?),
                 TokenType::Const => Some(self.parse_variable_declaration(true)?),
                 TokenType::Var => Some(self.parse_variable_declaration(false)?),
-                TokenType::Ident(_) => {
+                TokenType::Ident(name)
+                    if self.lexer.peek_next_token()?.t == TokenType::Colon =>
+                {
+                    Some(self.parse_labeled_loop(name)?)
+                }
+                TokenType::Macro => Some(self.parse_macro()?),
+                TokenType::Enum => Some(self.parse_enum()?),
+                TokenType::Struct => Some(self.parse_struct()?),
+                TokenType::Ident(_)
+                | TokenType::Quote
+                | TokenType::Unquote
+                | TokenType::Comptime
+                | TokenType::Match
+                | TokenType::Await => {
                     let ast = self.parse_clause(1000)?;
                     // self.lexer.get_next_token()?;
                     Some(ast)
                 }
-                TokenType::While => Some(self.parse_while()?),
+                TokenType::While | TokenType::Loop | TokenType::Do => {
+                    Some(self.parse_while(None)?)
+                }
+                TokenType::For => Some(self.parse_for(None)?),
                 TokenType::If => Some(self.parse_if()?),
                 TokenType::EOF if top_level => break,
                 TokenType::EOF if !top_level => {
@@ -916,6 +1878,12 @@ impl Parser {
                     )))
                 }
             };
+            // pushed before looking for the end of the statement, a closing brace on the
+            //  same line ends the block right away
+            let is_statement = ast.is_some();
+            if let Some(ast) = ast {
+                ret.push(ast);
+            }
             match self.lexer.get_next_token()?.t {
                 TokenType::RightBrace if !top_level => {
                     break;
@@ -936,12 +1904,12 @@ impl Parser {
                     )));
                 }
                 TokenType::EOF => break,
-                _ if ast.is_none() => (),
+                _ if !is_statement => (),
                 _ => {
                     return Err(Box::new(ParserError::new(
                         format!(
-                            "Expected newline after statement \"{}\", instead got {:?}",
-                            match ast {
+                            "Expected newline or ; after statement \"{}\", instead got {:?}",
+                            match ret.last() {
                                 Some(ast) => ast.pretty_print(),
                                 None => "".to_string(),
                             },
@@ -952,14 +1920,6 @@ impl Parser {
                     )));
                 }
             };
-            // _ = self.lexer.get_next_token()?;
-            match ast {
-                Some(ast) => {
-                    // println!("{}", ast.pretty_print());
-                    ret.push(ast)
-                }
-                None => {}
-            }
         }
         Ok(ret)
     }
@@ -1100,6 +2060,826 @@ var x = 0 var y = 0"#;
         assert!(ast.is_err());
     }
     #[test]
+    fn semicolons_separate_statements() {
+        let code = "
+var a = 0; var b = 1
+if a == 0 { a += 1; b += 1 }
+fn add(x, y) { return x + y }
+var c = add(a, b);
+while c < 10 { c += 1 } else { c += 100 }
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("a"), super::Rc::new(super::Value::Int(1)));
+        assert_eq!(get("b"), super::Rc::new(super::Value::Int(2)));
+        assert_eq!(get("c"), super::Rc::new(super::Value::Int(110)));
+
+        let err = super::Parser::new("var x = 0; var y = 0 var z = 0\n".to_string())
+            .parse(true)
+            .unwrap_err();
+        assert!(err.to_string().contains("Expected newline or ; after statement"), "{}", err);
+    }
+    #[test]
+    fn newlines_inside_brackets_and_after_operators() {
+        let code = "
+fn add(
+    a,
+    b
+) {
+    return a + b
+}
+var call = add(
+    1,
+    2
+)
+var arr = [
+    1,
+    2
+]
+var obj = {
+    x =
+        5,
+    y = 6
+}
+var index = arr[
+    1
+]
+var paren = (
+    1 +
+    2
+)
+var cond = true &&
+    false
+var decl =
+    7
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("call"), super::Rc::new(super::Value::Int(3)));
+        assert_eq!(get("index"), super::Rc::new(super::Value::Int(2)));
+        assert_eq!(get("paren"), super::Rc::new(super::Value::Int(3)));
+        assert_eq!(get("cond"), super::Rc::new(super::Value::Boolean(false)));
+        assert_eq!(get("decl"), super::Rc::new(super::Value::Int(7)));
+        match get("obj").as_ref() {
+            super::Value::Object(obj) => assert_eq!(
+                obj.get(super::ObjectKey::String("x".to_string()), 0).unwrap(),
+                super::Rc::new(super::Value::Int(5))
+            ),
+            v => panic!("expected an object, got {:?}", v),
+        }
+
+        // a ; can't stand in for one of those newlines
+        for code in [
+            "var a = [1; 2]\n",
+            "fn f(x) { return x }\nvar a = f(1;)\n",
+            "var a = (1;)\n",
+            "var a = 1 +; 2\n",
+            "var a = [1,; 2]\n",
+            "var a = { x = 1; y = 2 }\n",
+        ] {
+            let err = super::Parser::new(code.to_string()).parse(true).unwrap_err();
+            assert!(err.get_msg().contains("Unexpected ; in the middle of an expression"), "{}", code);
+        }
+
+        // without a trailing operator the newline still ends the statement
+        let ast = super::Parser::new("var a = 1\nvar b = 2\n".to_string())
+            .parse(true)
+            .unwrap();
+        assert_eq!(ast.len(), 2);
+        assert!(super::Parser::new("var a = 1\n+ 2\n".to_string()).parse(true).is_err());
+    }
+    #[test]
+    fn enums_and_match() {
+        let code = "
+enum Shape { Circle(r), Rect(w, h), Empty }
+enum Opt {
+    Some(v)
+    None
+}
+fn area(s) {
+    return match s {
+        Shape.Circle(r) => 3 * r * r
+        Shape.Rect(w, h) => w * h,
+        Shape.Empty => 0
+    }
+}
+var circle = area(Shape.Circle(2))
+var rect = area(Shape.Rect(2, 5))
+var empty = area(Shape.Empty)
+var same = Shape.Circle(2) == Shape.Circle(2.0)
+var different = Shape.Circle(2) == Shape.Circle(3)
+var nested = match Opt.Some(Shape.Rect(1, 4)) { Opt.Some(Shape.Rect(_, h)) => h, _ => 0 }
+var text = std.str.from(Shape.Rect(1, \"x\"))
+var shape = Shape.Empty
+var kind = 0
+match 3 {
+    1 => kind = 1
+    _ => {
+        kind = 2
+    }
+}
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("circle"), super::Rc::new(super::Value::Int(12)));
+        assert_eq!(get("rect"), super::Rc::new(super::Value::Int(10)));
+        assert_eq!(get("empty"), super::Rc::new(super::Value::Int(0)));
+        assert_eq!(get("same"), super::Rc::new(super::Value::Boolean(true)));
+        assert_eq!(get("different"), super::Rc::new(super::Value::Boolean(false)));
+        assert_eq!(get("nested"), super::Rc::new(super::Value::Int(4)));
+        assert_eq!(
+            get("text"),
+            super::Rc::new(super::Value::String("Shape.Rect(1, \"x\")".to_string()))
+        );
+        assert_eq!(get("kind"), super::Rc::new(super::Value::Int(2)));
+        assert_eq!(get("shape").pretty_type(&scope_chain, 0), "Shape");
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run(
+            "enum E { A(x), B }\nvar a = E.A(1)\nvar t = std.str.from(match E.B { E.A(x) => x })\n",
+            &mut scope_chain,
+        )
+        .unwrap_err();
+        assert!(err.contains("No arm of the match matched a value of type E"), "{}", err);
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("enum E { A(x) }\nvar a = E.A(1, 2)\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("E.A expects 1 fields, got 2"), "{}", err);
+    }
+    #[test]
+    fn structs_have_fixed_fields() {
+        let code = "
+struct Point {
+    x
+    y
+}
+var p = Point(1, 2)
+p.x = 5
+var sum = p.x + p.y
+var same = p == Point(5, 2.0)
+var different = p == Point(5, 3)
+var not_struct = p == { x = 5, y = 2 }
+var text = std.str.from(Point(1, \"a\"))
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("sum"), super::Rc::new(super::Value::Int(7)));
+        assert_eq!(get("same"), super::Rc::new(super::Value::Boolean(true)));
+        assert_eq!(get("different"), super::Rc::new(super::Value::Boolean(false)));
+        assert_eq!(get("not_struct"), super::Rc::new(super::Value::Boolean(false)));
+        assert_eq!(
+            get("text"),
+            super::Rc::new(super::Value::String("Point { x = 1, y = \"a\" }".to_string()))
+        );
+        assert_eq!(get("p").pretty_type(&scope_chain, 0), "Point");
+
+        for (code, msg) in [
+            ("var p = Point(1, 2)\np.z = 3\n", "Point has no field z"),
+            ("var p = Point(1, 2)\nvar z = p.z\n", "Point has no field z"),
+            ("var p = Point(1)\n", "Point expects 2 fields, got 1"),
+        ] {
+            let mut scope_chain = super::ScopeChain::new();
+            let code = format!("struct Point {{ x, y }}\n{}", code);
+            let err = run(&code, &mut scope_chain).unwrap_err();
+            assert!(err.contains(msg), "{}", err);
+        }
+    }
+    #[test]
+    fn async_tasks_overlap_timers() {
+        let string = |s: &str| super::Rc::new(super::Value::String(s.to_string()));
+        // whichever is spawned first, the shorter wait finishes first
+        for spawn in [
+            "var slow = wait(100, \"slow\")\nvar fast = wait(50, \"fast\")",
+            "var fast = wait(50, \"fast\")\nvar slow = wait(100, \"slow\")",
+        ] {
+            let code = format!("
+var order = []
+async fn wait(ms, name) {{
+    await std.time.after(ms)
+    order[std.arr.len(order)] = name
+    return ms
+}}
+{}
+var both = await std.task.all([slow, fast])
+var total = both[0] + both[1]
+var spawned = await std.task.spawn(fn() {{
+    return 5
+}}) + 1
+var plain = await 3
+", spawn);
+            let mut scope_chain = super::ScopeChain::new();
+            run(&code, &mut scope_chain).unwrap();
+            let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+            assert_eq!(get("total"), super::Rc::new(super::Value::Int(150)));
+            assert_eq!(get("spawned"), super::Rc::new(super::Value::Int(6)));
+            assert_eq!(get("plain"), super::Rc::new(super::Value::Int(3)));
+            match get("order").as_ref() {
+                super::Value::Object(obj) => {
+                    assert_eq!(obj.get(super::ObjectKey::Number(0.0), 0).unwrap(), string("fast"));
+                    assert_eq!(obj.get(super::ObjectKey::Number(1.0), 0).unwrap(), string("slow"));
+                }
+                v => panic!("expected an array, got {:?}", v),
+            }
+        }
+
+        // a task can join one that's already waiting, and two tasks can join the same one
+        let code = "
+async fn a() {
+    await std.time.after(20)
+    return 7
+}
+var ta = a()
+async fn b() {
+    return await ta
+}
+var tb = b()
+var tc = b()
+var joined = await tb + await tc
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        assert_eq!(
+            scope_chain.get_variable(&"joined".to_string(), 0).unwrap(),
+            super::Rc::new(super::Value::Int(14))
+        );
+
+        // files and subprocesses are futures too
+        let path = std::env::temp_dir().join(format!("maple_async_{}.txt", std::process::id()));
+        let code = format!("
+await std.fs.write({:?}, \"hello\")
+var read = await std.fs.read({:?})
+", path, path);
+        let mut scope_chain = super::ScopeChain::new();
+        run(&code, &mut scope_chain).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(scope_chain.get_variable(&"read".to_string(), 0).unwrap(), string("hello"));
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var gone = await std.fs.read(\"/nonexistent/maple\")\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Cannot read /nonexistent/maple"), "{}", err);
+        if cfg!(unix) {
+            let mut scope_chain = super::ScopeChain::new();
+            run(
+                "var p = await std.proc.run(\"sh\", [\"-c\", \"echo hi; exit 3\"])\nvar code = p.code\nvar out = p.stdout\n",
+                &mut scope_chain,
+            )
+            .unwrap();
+            let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+            assert_eq!(get("code"), super::Rc::new(super::Value::Int(3)));
+            assert_eq!(get("out"), string("hi\n"));
+        }
+
+        // a wait that could never end is an error instead of a panic
+        for ms in ["1.0 / 0.0", "1.0 * 9223372036854775807 * 9223372036854775807"] {
+            let mut scope_chain = super::ScopeChain::new();
+            let err = run(&format!("var t = std.time.after({})\n", ms), &mut scope_chain).unwrap_err();
+            assert!(err.contains("Cannot wait for"), "{}", err);
+        }
+
+        // a failed task errors where it's awaited, or at the end if nothing awaits it
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run(
+            "async fn bad() {\n    return 1 + \"a\"\n}\nvar x = await bad()\n",
+            &mut scope_chain,
+        )
+        .unwrap_err();
+        assert!(err.contains("Cannot add types int and string"), "{}", err);
+        let mut scope_chain = super::ScopeChain::new();
+        run(
+            "var ran = false\nasync fn bad() {\n    ran = true\n    return 1 + \"a\"\n}\nvar t = bad()\n",
+            &mut scope_chain,
+        )
+        .unwrap();
+        let err = scope_chain
+            .get_event_loop()
+            .run(&mut scope_chain)
+            .unwrap_err();
+        assert!(err.to_string().contains("Cannot add types"), "{}", err);
+        assert_eq!(
+            scope_chain.get_variable(&"ran".to_string(), 0).unwrap(),
+            super::Rc::new(super::Value::Boolean(true))
+        );
+
+        // lots of tasks all waiting at once, std.task.all only builds its array once
+        let code = "
+async fn square(n) {
+    await std.time.after(1)
+    return n * n
+}
+var tasks = []
+for i in 0..2000 {
+    tasks[i] = square(i)
+}
+var squares = await std.task.all(tasks)
+var last = squares[1999]
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        assert_eq!(
+            scope_chain.get_variable(&"last".to_string(), 0).unwrap(),
+            super::Rc::new(super::Value::Int(1999 * 1999))
+        );
+
+        // a task stops at an await wherever it is in the expression
+        let code = "
+struct Pair { a, b }
+enum Wrap { Both(a, b) }
+async fn later(v) {
+    await std.time.after(1)
+    return v
+}
+async fn go() {
+    var obj = { a = await later(1), [await later(\"b\")] = 2 }
+    var pair = Pair(await later(3), 4)
+    var total = 0
+    for i in 0..await later(3) {
+        match await later(i) {
+            1 => { continue }
+            _ => { total += i }
+        }
+    }
+    var arm = match Wrap.Both(pair.a, pair.b) {
+        Wrap.Both(a, b) => a + await later(b),
+    }
+    var found = await later(2) in [1, 2]
+    return [obj.a + obj.b + total + arm, found]
+}
+var result = await go()
+var sum = result[0]
+var found = result[1]
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("sum"), super::Rc::new(super::Value::Int(1 + 2 + 2 + 7)));
+        assert_eq!(get("found"), super::Rc::new(super::Value::Boolean(true)));
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run(
+            "var o = { a = 1 }\nasync fn f() {\n    delete o[await std.time.after(1)]\n}\nvar x = await f()\n",
+            &mut scope_chain,
+        )
+        .unwrap_err();
+        assert!(err.contains("A task can't stop at this await"), "{}", err);
+
+        let err = super::Parser::new("fn f() {\n    await std.time.after(1)\n}\n".to_string())
+            .parse(true)
+            .unwrap_err();
+        assert!(err.get_msg().contains("await can only be used inside of an async fn"));
+    }
+    #[test]
+    fn ranges_and_for_loops() {
+        let code = "
+var r = 0..10 by 3
+var len = std.arr.len(r)
+var total = 0
+for i in 0..=10 by 2 {
+    total += i
+}
+var down = \"\"
+for i in 5..0 by -2 {
+    down = down + std.str.from(i)
+}
+var arr = [1, 2, 3, 4, 5]
+var middle = arr[1..3]
+var word = \"hello\"[1..=3]
+var letters = 0
+outer: for x in arr {
+    for c in \"ab\" {
+        if x == 3 {
+            break outer
+        }
+        letters += 1
+    }
+}
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        match get("r").as_ref() {
+            super::Value::Range(range) => {
+                assert_eq!(range.iter().collect::<Vec<i64>>(), vec![0, 3, 6, 9])
+            }
+            v => panic!("expected a range, got {:?}", v),
+        }
+        assert_eq!(get("len"), super::Rc::new(super::Value::Int(4)));
+        assert_eq!(get("total"), super::Rc::new(super::Value::Int(30)));
+        assert_eq!(get("down"), super::Rc::new(super::Value::String("531".to_string())));
+        assert_eq!(get("word"), super::Rc::new(super::Value::String("ell".to_string())));
+        assert_eq!(get("letters"), super::Rc::new(super::Value::Int(4)));
+        match get("middle").as_ref() {
+            super::Value::Object(obj) => assert_eq!(
+                obj.fields,
+                vec![
+                    (super::ObjectKey::Number(0.0), super::Rc::new(super::Value::Int(2))),
+                    (super::ObjectKey::Number(1.0), super::Rc::new(super::Value::Int(3))),
+                ]
+            ),
+            v => panic!("expected an array, got {:?}", v),
+        }
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var x = [1, 2][0..5]\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Index 4 is out of range for length 2"), "{}", err);
+        for code in ["for x in { a = 1 } {\n}\n", "for x in 5 {\n}\n"] {
+            let mut scope_chain = super::ScopeChain::new();
+            let err = run(code, &mut scope_chain).unwrap_err();
+            assert!(err.contains("Cannot loop over a value of type"), "{}", err);
+        }
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var x = 0..10 by 0\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("step of a range can't be 0"), "{}", err);
+
+        // ranges across the whole of an int don't overflow
+        let huge = super::Range {
+            start: -i64::MAX,
+            end: i64::MAX,
+            step: i64::MAX,
+            inclusive: true,
+        };
+        assert_eq!(huge.iter().collect::<Vec<i64>>(), vec![-i64::MAX, 0, i64::MAX]);
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run(
+            "var x = \"abc\"[(0 - 9223372036854775807)..9223372036854775807]\n",
+            &mut scope_chain,
+        )
+        .unwrap_err();
+        assert!(err.contains("Index -9223372036854775807 is out of range"), "{}", err);
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run(
+            "var x = std.arr.len((0 - 9223372036854775807)..9223372036854775807)\n",
+            &mut scope_chain,
+        )
+        .unwrap_err();
+        assert!(err.contains("is too big for an int"), "{}", err);
+    }
+    #[test]
+    fn slices_and_negative_indexes() {
+        let code = "
+var arr = [1, 2, 3, 4, 5]
+var last = arr[-1]
+var middle = arr[1:3]
+var head = std.arr.len(arr[:2])
+var tail = arr[-2:]
+var other = [1, 2]
+other[-1] = 20
+var s = \"hello\"
+var rest = s[2:]
+var c = s[1]
+var lastChar = s[-1]
+var trimmed = s[:-1]
+var empty = s[3:1]
+var wide = \"hélloé\"
+var wideLen = std.str.len(wide)
+var wideLast = wide[std.str.len(wide) - 1]
+var wideTail = wide[std.str.len(wide) - 2:]
+var wideChar = 'é'
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        let int = |i: i64| super::Rc::new(super::Value::Int(i));
+        let string = |s: &str| super::Rc::new(super::Value::String(s.to_string()));
+        assert_eq!(get("last"), int(5));
+        assert_eq!(get("head"), int(2));
+        assert_eq!(get("rest"), string("llo"));
+        assert_eq!(get("c"), super::Rc::new(super::Value::Char('e')));
+        assert_eq!(get("lastChar"), super::Rc::new(super::Value::Char('o')));
+        assert_eq!(get("trimmed"), string("hell"));
+        assert_eq!(get("empty"), string(""));
+        assert_eq!(get("wideLen"), int(6));
+        assert_eq!(get("wideLast"), super::Rc::new(super::Value::Char('é')));
+        assert_eq!(get("wideTail"), string("oé"));
+        assert_eq!(get("wideChar"), get("wideLast"));
+        let elements = |name: &str| match get(name).as_ref() {
+            super::Value::Object(obj) => obj.fields.iter().map(|(_, v)| v.clone()).collect::<Vec<_>>(),
+            v => panic!("expected an array, got {:?}", v),
+        };
+        assert_eq!(elements("middle"), vec![int(2), int(3)]);
+        assert_eq!(elements("tail"), vec![int(4), int(5)]);
+        assert_eq!(elements("other"), vec![int(1), int(20)]);
+
+        for (code, msg) in [
+            ("var x = [1, 2][2]\n", "Index 2 is out of range for length 2"),
+            ("var x = [1, 2][-3]\n", "Index -3 is out of range for length 2"),
+            ("var x = \"abc\"[7]\n", "Index 7 is out of range for length 3"),
+            ("var x = \"abc\"[1:5]\n", "Index 5 is out of range for length 3"),
+        ] {
+            let mut scope_chain = super::ScopeChain::new();
+            let err = run(code, &mut scope_chain).unwrap_err();
+            assert!(err.contains(msg), "{}", err);
+        }
+    }
+    #[test]
+    fn object_literal_keys_and_methods() {
+        let code = "
+var x = 1
+var y = 2
+var k = \"dyn\"
+var o = {x, y, [k + \"amic\"] = 3, [1 + 1] = \"two\",
+    area() {
+        return x * 6
+    }
+    scale(n) { return n * 2 }
+}
+var sum = o.x + o.y
+var dynamic = o.dynamic
+var two = o[2]
+var area = o.area()
+var scaled = o.scale(4)
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("sum"), super::Rc::new(super::Value::Int(3)));
+        assert_eq!(get("dynamic"), super::Rc::new(super::Value::Int(3)));
+        assert_eq!(get("two"), super::Rc::new(super::Value::String("two".to_string())));
+        assert_eq!(get("area"), super::Rc::new(super::Value::Int(6)));
+        assert_eq!(get("scaled"), super::Rc::new(super::Value::Int(8)));
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var o = {[true] = 1}\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Cannot use type boolean as an object key"), "{}", err);
+        assert!(super::Parser::new("var o = {1}\n".to_string())
+            .parse(true)
+            .is_err());
+    }
+    #[test]
+    fn in_and_delete() {
+        let code = "
+var o = {a = 1, b = 2}
+var hasA = \"a\" in o
+var hasZ = \"z\" in o
+var inArr = 3 in [1, 2, 3]
+var indexNotValue = 0 in [1, 2, 3]
+var sub = \"ell\" in \"hello\"
+var inRange = 4 in 0..10 by 2
+var offStep = 5 in 0..10 by 2
+delete o.a
+var hadA = \"a\" in o
+delete o[\"b\"]
+var left = std.arr.len(o)
+var arr = [1, 2, 3, 4]
+delete arr[0]
+delete arr[-1]
+var rest = \"\"
+for x in arr {
+    rest = rest + std.str.from(x)
+}
+var tail = arr[1:]
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        let boolean = |b: bool| super::Rc::new(super::Value::Boolean(b));
+        assert_eq!(get("hasA"), boolean(true));
+        assert_eq!(get("hasZ"), boolean(false));
+        assert_eq!(get("inArr"), boolean(true));
+        assert_eq!(get("indexNotValue"), boolean(false));
+        assert_eq!(get("sub"), boolean(true));
+        assert_eq!(get("inRange"), boolean(true));
+        assert_eq!(get("offStep"), boolean(false));
+        assert_eq!(get("hadA"), boolean(false));
+        // deleting from an array doesn't leave a hole
+        assert_eq!(get("rest"), super::Rc::new(super::Value::String("23".to_string())));
+        match get("tail").as_ref() {
+            super::Value::Object(obj) => assert_eq!(
+                obj.fields,
+                vec![(super::ObjectKey::Number(0.0), super::Rc::new(super::Value::Int(3)))]
+            ),
+            v => panic!("expected an array, got {:?}", v),
+        }
+        match get("o").as_ref() {
+            super::Value::Object(obj) => assert!(obj.fields.is_empty()),
+            v => panic!("expected an object, got {:?}", v),
+        }
+
+        for (code, msg) in [
+            ("const c = {x = 1}\ndelete c.x\n", "Cannot delete c.x because c is frozen"),
+            ("struct P { x }\nvar p = P(1)\ndelete p.x\n", "P is a struct"),
+            ("var o = {}\ndelete o.x\n", "Object does not have key x"),
+        ] {
+            let mut scope_chain = super::ScopeChain::new();
+            let err = run(code, &mut scope_chain).unwrap_err();
+            assert!(err.contains(msg), "{}", err);
+        }
+        assert!(super::Parser::new("var x = 1\ndelete x\n".to_string())
+            .parse(true)
+            .is_err());
+    }
+    #[test]
+    fn strings_and_arrays_are_ordered() {
+        let code = "
+var names = \"alice\" < \"bob\"
+var prefix = \"app\" < \"apple\"
+var versions = [1, 10, 2] > [1, 9, 9]
+var shorter = [1, 2] <= [1, 2, 0]
+var same = [\"a\", 1] >= [\"a\", 1]
+var before = std.cmp.compare(\"a\", \"b\")
+var equal = std.cmp.compare(3, 3.0)
+var after = std.cmp.compare([2], [1, 5])
+fn box(v) {
+    return { v = v, __lt = fn(a, b) { return a.v < b.v } }
+}
+var boxes = [box(1)] < [box(2)]
+var boxesAfter = std.cmp.compare([box(1), 2], [box(1), 1])
+var boxBefore = std.cmp.compare(box(1), box(2))
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        let boolean = |b: bool| super::Rc::new(super::Value::Boolean(b));
+        assert_eq!(get("names"), boolean(true));
+        assert_eq!(get("prefix"), boolean(true));
+        assert_eq!(get("versions"), boolean(true));
+        assert_eq!(get("shorter"), boolean(true));
+        assert_eq!(get("same"), boolean(true));
+        assert_eq!(get("before"), super::Rc::new(super::Value::Int(-1)));
+        assert_eq!(get("equal"), super::Rc::new(super::Value::Int(0)));
+        assert_eq!(get("after"), super::Rc::new(super::Value::Int(1)));
+        // elements and std.cmp.compare use __lt like < does
+        assert_eq!(get("boxes"), boolean(true));
+        assert_eq!(get("boxesAfter"), super::Rc::new(super::Value::Int(1)));
+        assert_eq!(get("boxBefore"), super::Rc::new(super::Value::Int(-1)));
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var x = \"a\" < 1\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Cannot compare types string and int"), "{}", err);
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var x = std.cmp.compare([1], [\"a\"])\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Cannot compare types int and string"), "{}", err);
+        // the elements that couldn't be compared are named rather than the arrays
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var x = [1, \"a\"] < [1, 2]\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Cannot compare types string and int"), "{}", err);
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var x = std.cmp.compare(0.0 / 0.0, 1)\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Cannot put NaN in order"), "{}", err);
+        let mut scope_chain = super::ScopeChain::new();
+        run("var x = 0.0 / 0.0 < 1\n", &mut scope_chain).unwrap();
+        assert_eq!(
+            scope_chain.get_variable(&"x".to_string(), 0).unwrap(),
+            super::Rc::new(super::Value::Boolean(false))
+        );
+    }
+    #[test]
+    fn equality_is_structural() {
+        let code = "
+var a = {x = 1, y = {z = [1, 2]}}
+var b = {y = {z = [1, 2]}, x = 1.0}
+var unordered = a == b
+var missing = a == {x = 1}
+var c = {n = 1}
+c.self = c
+var d = {n = 1}
+d.self = d
+var cycles = c == d
+d.n = 2
+var changed = c != d
+var copies = std.ref.same(a, b)
+var e = a
+var aliases = std.ref.same(a, e)
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        let boolean = |b: bool| super::Rc::new(super::Value::Boolean(b));
+        assert_eq!(get("unordered"), boolean(true));
+        assert_eq!(get("missing"), boolean(false));
+        assert_eq!(get("cycles"), boolean(true));
+        assert_eq!(get("changed"), boolean(true));
+        assert_eq!(get("copies"), boolean(false));
+        assert_eq!(get("aliases"), boolean(true));
+    }
+    #[test]
+    fn vm_matches_tree_walker() {
+        use crate::scopechain::Engine;
+        let programs = [
+            "fn fib(n) {\n    if n < 2 { return n }\n    return fib(n - 1) + fib(n - 2)\n}\nvar out = fib(12)\n",
+            "var out = 0\nouter: for x in [1, 2, 3] {\n    for y in 0..4 {\n        if y == 2 { continue outer }\n        if x == 3 { break outer }\n        out += x * y\n    }\n}\n",
+            "var out = 0\ndo {\n    out += 1\n} while out < 5\nwhile false {} else { out += 10 }\n",
+            "var out = [1, \"a\"]\nout[0] += 2\nout[1] += \"b\"\nvar t = !(1 < 2) || 3 >= 3 && -out[0] == -3\nout[2] = t\n",
+            "var out = match 2 { 1 => \"one\", _ => \"other\" }\n",
+            "fn count(n, acc) {\n    if n == 0 { return acc }\n    return count(n - 1, acc + 1)\n}\nvar out = count(5000, 0)\n",
+            "var out = 1\nif out { }\n",
+            "var out = 1\nvar z = out.a.b\n",
+            "fn f(a) { return a + nope }\nvar out = f([1][0])\n",
+            "var out = 0\nvar out = 1\n",
+            "var out = 0\nwhile out + 1 { }\n",
+            "std.io.println(1, 2)\n",
+            "var out = 5\nout(1)\n",
+            "continue\n",
+            "var o = { __index = fn(a, i) { return 7 } }\no[0] = 3\no[0] += 1\nvar out = [0 in o, o[0]]\n",
+        ];
+        for code in programs {
+            let results = [Engine::Tree, Engine::Vm].map(|engine| {
+                let mut scope_chain = super::ScopeChain::new();
+                scope_chain.set_engine(engine);
+                crate::builtins::create_builtins(&mut scope_chain).unwrap();
+                let ast = super::Parser::new(code.to_string()).parse(true).unwrap();
+                let res = crate::vm::interpret(&ast, &mut scope_chain).map_err(|e| e.to_string());
+                let out = scope_chain.get_variable(&"out".to_string(), 0).ok();
+                (res.map(|_| ()), out)
+            });
+            assert_eq!(results[0], results[1], "{}", code);
+        }
+    }
+    #[test]
+    fn resolver_finds_variables_before_running() {
+        use crate::ast::ASTType;
+        use crate::scopechain::Address;
+        // undefined names are reported when they're reached, with the same trace as always
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var a = 1\na = 2\nstd.io.println(x)\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Variable x not found"), "{}", err);
+        assert!(err.contains("while evaluating: \"std.io.println(x)\""), "{}", err);
+        assert_eq!(
+            scope_chain.get_variable(&"a".to_string(), 0).unwrap(),
+            super::Rc::new(super::Value::Int(2))
+        );
+        // a closure that uses a parameter of the function around it is fine until it's called
+        let code = "
+fn outer(x) {
+    fn inner() {
+        return x
+    }
+    return inner
+}
+var made = outer(5)
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run(&format!("{}var y = made()\n", code), &mut scope_chain).unwrap_err();
+        assert!(err.contains("Variable x not found"), "{}", err);
+        // functions can use globals made after them, shadowing and closures still work
+        let mut scope_chain = super::ScopeChain::new();
+        let code = "
+fn f() { return later }
+var later = 2
+var out = f()
+var n = 10
+if true {
+    var n = 1
+    fn add(x) { return x + n }
+    out += add(3) + n
+}
+out += n
+";
+        run(code, &mut scope_chain).unwrap();
+        let out = scope_chain.get_variable(&"out".to_string(), 0).unwrap();
+        assert_eq!(out, super::Rc::new(super::Value::Int(17)));
+        let mut ast = super::Parser::new("var a = 1\nif true {\n    var b = a\n    b\n}\n".to_string())
+            .parse(true)
+            .unwrap();
+        let mut scope_chain = super::ScopeChain::new();
+        crate::resolver::resolve(&mut ast, &scope_chain);
+        let ASTType::If(if_lit) = &ast[1].t else { panic!() };
+        let ASTType::OpEq(_, a) = &if_lit.body[0].t else { panic!() };
+        assert!(matches!(a.t, ASTType::VariableAccess(_, Address::Global { slot: 0 })));
+        assert!(matches!(if_lit.body[1].t, ASTType::VariableAccess(_, Address::Local { depth: 0, slot: 0 })));
+        scope_chain.set_engine(crate::scopechain::Engine::Vm);
+        crate::vm::interpret(&ast, &mut scope_chain).unwrap();
+    }
+    #[test]
+    fn tail_calls_reuse_the_frame() {
+        let code = "
+fn count(n, acc) {
+    if n == 0 {
+        return acc
+    }
+    return count(n - 1, acc + 1)
+}
+fn isEven(n) {
+    if n == 0 {
+        return true
+    }
+    return isOdd(n - 1)
+}
+fn isOdd(n) {
+    if n == 0 {
+        return false
+    }
+    return isEven(n - 1)
+}
+fn inLoop(n) {
+    while true {
+        return count(n, 0)
+    }
+}
+var total = count(100000, 0)
+var even = isEven(100001)
+var looped = inLoop(5)
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("total"), super::Rc::new(super::Value::Int(100000)));
+        assert_eq!(get("even"), super::Rc::new(super::Value::Boolean(false)));
+        assert_eq!(get("looped"), super::Rc::new(super::Value::Int(5)));
+    }
+    #[test]
     fn fails_on_extra_else() {
         let code = r#"
 if true {
@@ -1133,7 +2913,7 @@ if true {
         }
         let a_name = "a".to_string();
         let a = scope_chain.get_variable(&a_name, 0).unwrap();
-        assert_eq!(a, super::Rc::new(super::Value::Number(1.0)));
+        assert_eq!(a, super::Rc::new(super::Value::Int(1)));
     }
     #[test]
     fn interpret_if_else() {
@@ -1155,7 +2935,7 @@ if false {
         }
         let a_name = "a".to_string();
         let a = scope_chain.get_variable(&a_name, 0).unwrap();
-        assert_eq!(a, super::Rc::new(super::Value::Number(2.0)));
+        assert_eq!(a, super::Rc::new(super::Value::Int(2)));
     }
     #[test]
     fn interpret_if_else2() {
@@ -1178,7 +2958,7 @@ if b {
         }
         let a_name = "a".to_string();
         let a = scope_chain.get_variable(&a_name, 0).unwrap();
-        assert_eq!(a, super::Rc::new(super::Value::Number(1.0)));
+        assert_eq!(a, super::Rc::new(super::Value::Int(1)));
     }
     #[test]
     fn function_pass_by_reference() {
@@ -1200,7 +2980,7 @@ add_one(a)
         }
         let a_name = "a".to_string();
         let a = scope_chain.get_variable(&a_name, 0).unwrap();
-        assert_eq!(a, super::Rc::new(super::Value::Number(1.0)));
+        assert_eq!(a, super::Rc::new(super::Value::Int(1)));
     }
     #[test]
     fn first_eq_by_reference() {
@@ -1221,7 +3001,7 @@ b = 10
         }
         let a_name = "a".to_string();
         let a = scope_chain.get_variable(&a_name, 0).unwrap();
-        assert_eq!(a, super::Rc::new(super::Value::Number(10.0)));
+        assert_eq!(a, super::Rc::new(super::Value::Int(10)));
     }
     #[test]
     fn second_eq_by_copy() {
@@ -1239,6 +3019,517 @@ b = 10
         let a_name = "a".to_string();
         let a = scope_chain.get_variable(&a_name, 0).unwrap();
         println!("{:?}", a);
-        assert_eq!(a, super::Rc::new(super::Value::Number(0.0)));
+        assert_eq!(a, super::Rc::new(super::Value::Int(0)));
+    }
+    fn run(code: &str, scope_chain: &mut super::ScopeChain) -> Result<(), String> {
+        crate::builtins::create_builtins(scope_chain).map_err(|e| e.to_string())?;
+        let mut ast = super::Parser::new(code.to_string())
+            .parse(true)
+            .map_err(|e| e.to_string())?;
+        crate::resolver::resolve(&mut ast, scope_chain);
+        for stmt in ast.iter() {
+            stmt.interpret(scope_chain).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+    #[test]
+    fn const_object_is_frozen() {
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("const o = { x = 1 }\no.x = 2\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Cannot assign to o.x because o is frozen"), "{}", err);
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("const o = { x = 1 }\no.y = 2\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("because o is frozen"), "{}", err);
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("const o = { x = 1 }\no.x += 2\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("because o is frozen"), "{}", err);
+    }
+    #[test]
+    fn const_freezes_nested_objects() {
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run(
+            "const o = { inner = { arr = [1, 2] } }\no.inner.arr[0] = 5\n",
+            &mut scope_chain,
+        )
+        .unwrap_err();
+        assert!(
+            err.contains("Cannot assign to o.inner.arr[0] because o.inner.arr is frozen"),
+            "{}",
+            err
+        );
+    }
+    #[test]
+    fn const_copy_leaves_original_mutable() {
+        let code = "
+var a = { x = 1 }
+const b = a
+a.x = 2
+var y = b.x
+y = 10
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let b = scope_chain.get_variable(&"b".to_string(), 0).unwrap();
+        let a = scope_chain.get_variable(&"a".to_string(), 0).unwrap();
+        match (a.as_ref(), b.as_ref()) {
+            (super::Value::Object(a), super::Value::Object(b)) => {
+                assert!(!a.frozen);
+                assert!(b.frozen);
+                assert_eq!(a.fields[0].1, super::Rc::new(super::Value::Int(2)));
+                assert_eq!(b.fields[0].1, super::Rc::new(super::Value::Int(1)));
+            }
+            _ => panic!("expected objects"),
+        }
+    }
+    #[test]
+    fn frozen_fields_keep_their_identity() {
+        let code = "
+const o = { inner = { v = 1 }, n = 1, f = fn() { return 1 } }
+var same = std.ref.same(o.inner, o.inner) && o.f == o.f
+var z = o.n
+z = 5
+var m = o.n
+m += 2
+var p = { k = o.n }
+p.k = 9
+p.k += 1
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("same"), super::Rc::new(super::Value::Boolean(true)));
+        // writing to something that holds a field never reaches the frozen object
+        assert_eq!(get("z"), super::Rc::new(super::Value::Int(5)));
+        assert_eq!(get("m"), super::Rc::new(super::Value::Int(3)));
+        match get("o").as_ref() {
+            super::Value::Object(o) => {
+                assert_eq!(o.fields[1].1, super::Rc::new(super::Value::Int(1)));
+            }
+            _ => panic!("expected an object"),
+        }
+    }
+    #[test]
+    fn freeze_in_place() {
+        let code = "
+var a = { x = 1, inner = { y = 2 } }
+var inner = a.inner
+std.obj.freeze(a)
+var frozen = std.obj.isFrozen(inner)
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let frozen = scope_chain.get_variable(&"frozen".to_string(), 0).unwrap();
+        assert_eq!(frozen, super::Rc::new(super::Value::Boolean(true)));
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run(
+            "var a = { inner = {} }\nvar inner = a.inner\nstd.obj.freeze(a)\ninner.z = 1\n",
+            &mut scope_chain,
+        )
+        .unwrap_err();
+        assert!(err.contains("because inner is frozen"), "{}", err);
+
+        // rebinding a variable that holds a frozen object is still allowed
+        let mut scope_chain = super::ScopeChain::new();
+        run(
+            "var a = std.obj.freeze({ x = 1 })\nvar b = a\na = 5\n",
+            &mut scope_chain,
+        )
+        .unwrap();
+        let b = scope_chain.get_variable(&"b".to_string(), 0).unwrap();
+        assert!(b.is_frozen());
+    }
+    #[test]
+    fn freeze_cyclic_object() {
+        let code = "
+var a = { x = 1 }
+a.me = a
+const b = a
+std.obj.freeze(a)
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+    }
+    #[test]
+    fn int_arithmetic_is_exact() {
+        let code = "
+var big = 9007199254740993
+var a = big + 2
+var b = 7 / 2
+var c = 3 * 1.5
+var d = 0xFF - 0b11
+var same = 1 == 1.0
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("a"), super::Rc::new(super::Value::Int(9007199254740995)));
+        assert_eq!(get("b"), super::Rc::new(super::Value::Number(3.5)));
+        assert_eq!(get("c"), super::Rc::new(super::Value::Number(4.5)));
+        assert_eq!(get("d"), super::Rc::new(super::Value::Int(252)));
+        assert_eq!(get("same"), super::Rc::new(super::Value::Boolean(true)));
+    }
+    #[test]
+    fn int_overflow_is_an_error() {
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var a = 9223372036854775807\na += 1\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Integer overflow"), "{}", err);
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var a = 4611686018427387904 * 2\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Integer overflow"), "{}", err);
+
+        // the smallest int can be written, but its digits alone are too big
+        let mut scope_chain = super::ScopeChain::new();
+        run("var min = -9223372036854775808\nvar max = -(min + 1)\n", &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("min"), super::Rc::new(super::Value::Int(i64::MIN)));
+        assert_eq!(get("max"), super::Rc::new(super::Value::Int(i64::MAX)));
+        for code in ["var a = 9223372036854775808\n", "var a = 1 + 9223372036854775808\n"] {
+            let err = super::Parser::new(code.to_string()).parse(true).unwrap_err();
+            assert!(err.get_msg().contains("does not fit in 64 bits"), "{}", err.get_msg());
+        }
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("std.time.sleepNanos(-1)\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("negative number of nanoseconds"), "{}", err);
+    }
+    #[test]
+    fn int_float_conversions() {
+        let code = "
+var a = std.math.toInt(-2.7)
+var b = std.math.toFloat(3)
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("a"), super::Rc::new(super::Value::Int(-2)));
+        assert_eq!(get("b"), super::Rc::new(super::Value::Number(3.0)));
+        assert_eq!(
+            get("a").pretty_type(&scope_chain, 0),
+            "int".to_string()
+        );
+        assert_eq!(
+            get("b").pretty_type(&scope_chain, 0),
+            "float".to_string()
+        );
+    }
+    #[test]
+    fn big_numbers_use_operators() {
+        let code = "
+var price = std.big.dec(\"19.99\")
+var total = price * 3 + std.big.dec(\"0.03\")
+var text = std.str.from(total)
+var huge = std.big.int(9223372036854775807) + 1
+var cheaper = price < std.big.dec(20)
+var share = std.big.div(total, 7, 2)
+var rounded = std.big.round(std.big.dec(\"2.345\"), 2)
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("text"), super::Rc::new(super::Value::String("60.00".to_string())));
+        assert_eq!(
+            get("huge").pretty_type(&scope_chain, 0),
+            "bigint".to_string()
+        );
+        match get("huge").as_ref() {
+            super::Value::BigInt(n) => assert_eq!(n.to_string(), "9223372036854775808"),
+            v => panic!("expected a bigint, got {:?}", v),
+        }
+        assert_eq!(get("cheaper"), super::Rc::new(super::Value::Boolean(true)));
+        match (get("share").as_ref(), get("rounded").as_ref()) {
+            (super::Value::BigDecimal(share), super::Value::BigDecimal(rounded)) => {
+                assert_eq!(share.to_string(), "8.57");
+                assert_eq!(rounded.to_string(), "2.34");
+            }
+            _ => panic!("expected decimals"),
+        }
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var a = std.big.dec(1) + 0.5\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Cannot add types decimal and float"), "{}", err);
+    }
+    #[test]
+    fn labeled_break_and_continue() {
+        let code = "
+var found = 0
+var skipped = 0
+var i = 0
+outer: while i < 10 {
+    i += 1
+    var j = 0
+    while j < 10 {
+        j += 1
+        if j == 2 {
+            continue outer
+        }
+        if i == 5 {
+            found = i * 10 + j
+            break outer
+        }
+        skipped += 1
+    }
+}
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("found"), super::Rc::new(super::Value::Int(51)));
+        assert_eq!(get("skipped"), super::Rc::new(super::Value::Int(4)));
+        assert_eq!(get("i"), super::Rc::new(super::Value::Int(5)));
+    }
+    #[test]
+    fn loop_labels_are_validated() {
+        let unknown = "while true {\n    break outer\n}\n";
+        assert!(super::Parser::new(unknown.to_string()).parse(true).is_err());
+
+        let duplicate = "a: while true {\n    a: while true {\n        break a\n    }\n}\n";
+        assert!(super::Parser::new(duplicate.to_string()).parse(true).is_err());
+
+        let not_a_loop = "a: if true {\n}\n";
+        assert!(super::Parser::new(not_a_loop.to_string()).parse(true).is_err());
+
+        // functions can't break out of the loop they are declared in
+        let in_fn = "a: while true {\n    fn f() {\n        break a\n    }\n}\n";
+        assert!(super::Parser::new(in_fn.to_string()).parse(true).is_err());
+
+        let sibling = "a: while false {\n}\na: while false {\n    continue a\n}\n";
+        assert!(super::Parser::new(sibling.to_string()).parse(true).is_ok());
+    }
+    #[test]
+    fn operator_overloading() {
+        let code = "
+fn vec(x, y) {
+    return {
+        x = x,
+        y = y,
+        __add = fn(a, b) {
+            return vec(a.x + b.x, a.y + b.y)
+        },
+        __mul = fn(a, b) {
+            return vec(a.x * b, a.y * b)
+        },
+        __neg = fn(a) {
+            return vec(-a.x, -a.y)
+        },
+        __eq = fn(a, b) {
+            return a.x == b.x && a.y == b.y
+        },
+        __lt = fn(a, b) {
+            return a.x < b.x
+        },
+        __index = fn(a, i) {
+            if i == 0 {
+                return a.x
+            }
+            return a.y
+        }
+    }
+}
+var sum = vec(1, 2) + vec(3, 4)
+var scaled = vec(1, 2) * 3
+var neg = -vec(1, 2)
+var eq = vec(1, 2) == vec(1, 2)
+var noteq = vec(1, 2) != vec(1, 2)
+var lt = vec(1, 2) < vec(2, 0)
+var second = sum[1]
+sum[0] = 3
+sum[0] += 1
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        let field = |name: &str, key: &str| match get(name).as_ref() {
+            super::Value::Object(obj) => obj
+                .get(super::ObjectKey::String(key.to_string()), 0)
+                .unwrap(),
+            v => panic!("expected an object, got {:?}", v),
+        };
+        assert_eq!(field("sum", "x"), super::Rc::new(super::Value::Int(4)));
+        assert_eq!(field("scaled", "y"), super::Rc::new(super::Value::Int(6)));
+        assert_eq!(field("neg", "x"), super::Rc::new(super::Value::Int(-1)));
+        assert_eq!(get("eq"), super::Rc::new(super::Value::Boolean(true)));
+        assert_eq!(get("noteq"), super::Rc::new(super::Value::Boolean(false)));
+        assert_eq!(get("lt"), super::Rc::new(super::Value::Boolean(true)));
+        assert_eq!(get("second"), super::Rc::new(super::Value::Int(6)));
+        // assigning through brackets sets the field instead of calling __index
+        match get("sum").as_ref() {
+            super::Value::Object(obj) => assert_eq!(
+                obj.get(super::ObjectKey::Number(0.0), 0).unwrap(),
+                super::Rc::new(super::Value::Int(4))
+            ),
+            v => panic!("expected an object, got {:?}", v),
+        }
+
+        // == and != both need __eq to give back a boolean
+        for op in ["==", "!="] {
+            let mut scope_chain = super::ScopeChain::new();
+            let code = format!("var a = {{ __eq = fn(a, b) {{ return 1 }} }}\nvar b = a {} a\n", op);
+            let err = run(&code, &mut scope_chain).unwrap_err();
+            assert!(err.contains("Expected __eq to return a boolean, got int"), "{}", err);
+        }
+
+        // objects without the method still give the normal type error
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var a = {} + {}\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("Cannot add types object and object"), "{}", err);
+    }
+    #[test]
+    fn macros_expand_quoted_code() {
+        let code = "
+macro unless(cond, body) {
+    return quote {
+        if !(unquote(cond)) {
+            unquote(body)
+        }
+    }
+}
+macro twice(expr) {
+    return quote {
+        var tmp = unquote(expr)
+        tmp + tmp
+    }
+}
+var hits = 0
+unless(hits > 5, hits += 1)
+unless(hits < 5, hits += 10)
+var tmp = 10
+var doubled = twice(tmp + 1)
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("hits"), super::Rc::new(super::Value::Int(1)));
+        // the tmp inside the quote is renamed, so it doesn't clash with the one here
+        assert_eq!(get("tmp"), super::Rc::new(super::Value::Int(10)));
+        assert_eq!(get("doubled"), super::Rc::new(super::Value::Int(22)));
+    }
+    #[test]
+    fn macro_errors_point_at_the_call() {
+        let code = "macro bad(x) {\n    return 1 + \"a\"\n}\n\nbad(2)\n";
+        let err = super::Parser::new(code.to_string()).parse(true).unwrap_err();
+        assert_eq!(err.get_line(), 5);
+        assert!(err.to_string().contains("Error while expanding macro bad"), "{}", err);
+
+        let code = "macro func() {\n    return std.str.len\n}\nfunc()\n";
+        let err = super::Parser::new(code.to_string()).parse(true).unwrap_err();
+        assert_eq!(err.get_line(), 4);
+        assert!(err.to_string().contains("Cannot turn type builtin_function(<1>) into code"), "{}", err);
+
+        let mut scope_chain = super::ScopeChain::new();
+        let err = run("var x = unquote(1)\n", &mut scope_chain).unwrap_err();
+        assert!(err.contains("unquote can only be used inside of quote"), "{}", err);
+    }
+    #[test]
+    fn comptime_folds_into_literals() {
+        let code = "
+var squares = comptime {
+    var table = []
+    var i = 0
+    while i < 4 {
+        table[i] = i * i
+        i += 1
+    }
+    return table
+}
+var size = comptime 2 + 3
+var nothing = comptime {
+    std.assert(std.str.len(\"abc\") == 3, \"len is wrong\")
+}
+";
+        let ast = super::Parser::new(code.to_string()).parse(true).unwrap();
+        match &ast[1].t {
+            super::ASTType::OpEq(_, value) => match &value.t {
+                super::ASTType::Comptime(lit) => {
+                    assert_eq!(lit.value.t, super::ASTType::IntLiteral(5))
+                }
+                t => panic!("expected comptime, got {:?}", t),
+            },
+            t => panic!("expected an assignment, got {:?}", t),
+        }
+
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        match get("squares").as_ref() {
+            super::Value::Object(obj) => assert_eq!(
+                obj.get(super::ObjectKey::Number(3.0), 0).unwrap(),
+                super::Rc::new(super::Value::Int(9))
+            ),
+            v => panic!("expected an object, got {:?}", v),
+        }
+        assert_eq!(get("nothing"), super::Rc::new(super::Value::Undefined));
+    }
+    #[test]
+    fn comptime_errors_stop_parsing() {
+        let parse_err = |code: &str| {
+            super::Parser::new(code.to_string())
+                .parse(true)
+                .unwrap_err()
+                .to_string()
+        };
+        let err = parse_err("var x = 1\ncomptime {\n    std.assert(false, \"bad config\")\n}\n");
+        assert!(err.contains("line 3: Error in comptime: Assertion failed: bad config"), "{}", err);
+        // runtime variables don't exist yet
+        let err = parse_err("var x = 1\nvar y = comptime x\n");
+        assert!(err.contains("Variable x not found"), "{}", err);
+        let err = parse_err("var f = comptime fn() {\n    return 1\n}\n");
+        assert!(err.contains("Cannot turn type function into code"), "{}", err);
+    }
+    #[test]
+    fn loop_and_do_while() {
+        let code = "
+var i = 0
+loop {
+    i += 1
+    if i == 3 {
+        break
+    }
+}
+var runs = 0
+do {
+    runs += 1
+} while false
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("i"), super::Rc::new(super::Value::Int(3)));
+        assert_eq!(get("runs"), super::Rc::new(super::Value::Int(1)));
+    }
+    #[test]
+    fn loop_else_runs_without_break() {
+        let code = "
+var searched = 0
+var missing = false
+while searched < 3 {
+    searched += 1
+} else {
+    missing = true
+}
+var broke = true
+var j = 0
+do {
+    j += 1
+    if j == 2 {
+        break
+    }
+} while j < 5
+else {
+    broke = false
+}
+";
+        let mut scope_chain = super::ScopeChain::new();
+        run(code, &mut scope_chain).unwrap();
+        let get = |name: &str| scope_chain.get_variable(&name.to_string(), 0).unwrap();
+        assert_eq!(get("missing"), super::Rc::new(super::Value::Boolean(true)));
+        assert_eq!(get("broke"), super::Rc::new(super::Value::Boolean(true)));
+        assert_eq!(get("j"), super::Rc::new(super::Value::Int(2)));
+
+        let infinite_else = "loop {\n    break\n} else {\n}\n";
+        assert!(super::Parser::new(infinite_else.to_string()).parse(true).is_err());
     }
 }
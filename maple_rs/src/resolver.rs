@@ -0,0 +1,258 @@
+// works out where every variable lives before the program runs, so looking one up can go
+//  straight to its slot instead of searching every scope by name. scopes are laid out the
+//  same way the runtime makes them. names that can't be found are left for the runtime,
+//  which searches for them and reports the ones that really are missing when (and if)
+//  they're reached
+use crate::ast::{ASTType, Block, FieldKey, FunctionLiteral, MatchLiteral, AST};
+use crate::scopechain::{Address, ScopeChain};
+
+struct Scope {
+    names: Vec<String>,
+    is_fn: bool,
+}
+
+struct Resolver {
+    // the scopes of the function being resolved, or of the top level. the first one is the
+    //  global scope, which only fills in as the top level goes
+    scopes: Vec<Scope>,
+    // every name the global scope ends up with, functions can use globals made after them
+    globals: Vec<String>,
+    in_fn: bool,
+}
+
+// fills in the address of every variable in block, the globals the scope chain already has
+//  (the builtins) come first in the global scope
+pub fn resolve(block: &mut Block, scope_chain: &ScopeChain) {
+    let builtins = scope_chain.global_names();
+    // the first pass is only there to find every global
+    let mut first = Resolver::new(builtins.clone(), vec![]);
+    first.block(block);
+    let globals = first.scopes.swap_remove(0).names;
+    let mut second = Resolver::new(builtins, globals);
+    second.block(block);
+}
+
+impl Resolver {
+    fn new(builtins: Vec<String>, globals: Vec<String>) -> Resolver {
+        Resolver {
+            scopes: vec![Scope {
+                names: builtins,
+                is_fn: false,
+            }],
+            globals,
+            in_fn: false,
+        }
+    }
+    fn declare(&mut self, name: &str) {
+        self.scopes.last_mut().unwrap().names.push(name.to_string());
+    }
+    // searches like ScopeChain::get_variable does, up to the scope the function starts in
+    //  and then the global scope
+    fn lookup(&self, name: &str) -> Option<Address> {
+        let top = self.scopes.len() - 1;
+        for i in (1..=top).rev() {
+            let scope = &self.scopes[i];
+            if let Some(slot) = scope.names.iter().position(|n| n == name) {
+                return Some(Address::Local {
+                    depth: top - i,
+                    slot,
+                });
+            }
+            if scope.is_fn {
+                break;
+            }
+        }
+        let globals = match self.in_fn {
+            true => &self.globals,
+            false => &self.scopes[0].names,
+        };
+        globals
+            .iter()
+            .position(|n| n == name)
+            .map(|slot| Address::Global { slot })
+    }
+    fn block(&mut self, block: &mut Block) {
+        for ast in block.iter_mut() {
+            self.expr(ast);
+        }
+    }
+    fn scoped(&mut self, names: Vec<String>, block: &mut Block) {
+        self.scopes.push(Scope {
+            names,
+            is_fn: false,
+        });
+        self.block(block);
+        self.scopes.pop();
+    }
+    fn function(&mut self, f: &mut FunctionLiteral) {
+        // the closure is a copy of the scopes back to the function or global scope around
+        //  it, as they are when the function is made
+        let mut closure = vec![];
+        for scope in self.scopes[1..].iter().rev() {
+            if scope.is_fn {
+                break;
+            }
+            closure.push(scope.names.clone());
+        }
+        closure.reverse();
+        let mut frame = vec![Scope {
+            names: vec![],
+            is_fn: false,
+        }];
+        let has_closure = !closure.is_empty();
+        for (i, names) in closure.into_iter().enumerate() {
+            frame.push(Scope {
+                names,
+                is_fn: i == 0,
+            });
+        }
+        frame.push(Scope {
+            names: f.params.iter().map(|param| param.name.clone()).collect(),
+            is_fn: !has_closure,
+        });
+        let outer = std::mem::replace(&mut self.scopes, frame);
+        let outer_in_fn = std::mem::replace(&mut self.in_fn, true);
+        self.block(&mut f.body);
+        self.scopes = outer;
+        self.in_fn = outer_in_fn;
+    }
+    // the names a pattern binds, the rest of it gets looked up like any other expression
+    fn pattern(&mut self, pattern: &mut AST, is_field: bool, binds: &mut Vec<String>) {
+        match &mut pattern.t {
+            ASTType::VariableAccess(name, _) if name == "_" => (),
+            ASTType::VariableAccess(name, _) if is_field => binds.push(name.clone()),
+            ASTType::FunctionCall(callee, args) => {
+                self.expr(callee);
+                for arg in args.iter_mut() {
+                    self.pattern(arg, true, binds);
+                }
+            }
+            _ => self.expr(pattern),
+        }
+    }
+    fn match_lit(&mut self, match_lit: &mut MatchLiteral) {
+        self.expr(&mut match_lit.value);
+        for arm in match_lit.arms.iter_mut() {
+            let mut binds = vec![];
+            self.pattern(&mut arm.pattern, false, &mut binds);
+            self.scoped(binds, &mut arm.body);
+        }
+    }
+    fn expr(&mut self, ast: &mut AST) {
+        match &mut ast.t {
+            ASTType::VariableAccess(name, address) => {
+                *address = self.lookup(name).unwrap_or_default();
+            }
+            ASTType::VariableDeclaration(name, _) => self.declare(name),
+            ASTType::Enum(enum_lit) => self.declare(&enum_lit.name),
+            ASTType::Struct(struct_lit) => self.declare(&struct_lit.name),
+            ASTType::FunctionLiteral(f) => self.function(f),
+            ASTType::ArrayLiteral(values) => self.block(values),
+            ASTType::ObjectLiteral(fields) => {
+                for (key, value) in fields.iter_mut() {
+                    if let FieldKey::Computed(key) = key {
+                        self.expr(key);
+                    }
+                    self.expr(value);
+                }
+            }
+            ASTType::DotAccess(v, _)
+            | ASTType::Paren(v)
+            | ASTType::OpMnsPrefix(v)
+            | ASTType::OpNot(v)
+            | ASTType::Return(v)
+            | ASTType::Await(v)
+            | ASTType::Delete(v) => self.expr(v),
+            // the key is evaluated before the value being indexed
+            ASTType::BracketAccess(left, key) => {
+                self.expr(key);
+                self.expr(left);
+            }
+            ASTType::Slice(v, start, end) => {
+                self.expr(v);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expr(bound);
+                }
+            }
+            ASTType::Range(range) => {
+                self.expr(&mut range.start);
+                self.expr(&mut range.end);
+                if let Some(step) = &mut range.step {
+                    self.expr(step);
+                }
+            }
+            ASTType::OpPls(l, r)
+            | ASTType::OpMns(l, r)
+            | ASTType::OpTimes(l, r)
+            | ASTType::OpDiv(l, r)
+            | ASTType::OpEq(l, r)
+            | ASTType::OpEqEq(l, r)
+            | ASTType::OpPlsEq(l, r)
+            | ASTType::OpNotEq(l, r)
+            | ASTType::OpAndAnd(l, r)
+            | ASTType::OpOrOr(l, r)
+            | ASTType::OpGt(l, r)
+            | ASTType::OpLt(l, r)
+            | ASTType::OpGtEq(l, r)
+            | ASTType::OpLtEq(l, r)
+            | ASTType::OpIn(l, r) => {
+                self.expr(l);
+                self.expr(r);
+            }
+            ASTType::FunctionCall(callee, args) => {
+                self.expr(callee);
+                self.block(args);
+            }
+            ASTType::If(if_lit) => {
+                self.expr(&mut if_lit.cond);
+                self.scoped(vec![], &mut if_lit.body);
+                for (cond, body) in if_lit.elseifs.iter_mut() {
+                    self.expr(cond);
+                    self.scoped(vec![], body);
+                }
+                if let Some(else_body) = &mut if_lit.else_body {
+                    self.scoped(vec![], else_body);
+                }
+            }
+            ASTType::While(while_lit) => {
+                // a do while runs its body before the condition
+                if !while_lit.is_do_while {
+                    if let Some(cond) = &mut while_lit.cond {
+                        self.expr(cond);
+                    }
+                }
+                self.scoped(vec![], &mut while_lit.body);
+                if while_lit.is_do_while {
+                    if let Some(cond) = &mut while_lit.cond {
+                        self.expr(cond);
+                    }
+                }
+                if let Some(else_body) = &mut while_lit.else_body {
+                    self.scoped(vec![], else_body);
+                }
+            }
+            ASTType::For(for_lit) => {
+                self.expr(&mut for_lit.iter);
+                self.scoped(vec![for_lit.var.clone()], &mut for_lit.body);
+            }
+            ASTType::Match(match_lit) => self.match_lit(match_lit),
+            // the statements of an expanded macro run right in the scope of the call
+            ASTType::Splice(block) => self.block(block),
+            ASTType::MacroCall(_, expansion) => self.expr(expansion),
+            ASTType::Comptime(lit) => self.expr(&mut lit.value),
+            // quoted code is only a value, and macros and comptime code already ran while
+            //  parsing
+            ASTType::Quote(_)
+            | ASTType::Unquote(_)
+            | ASTType::Macro(_, _)
+            | ASTType::Import(_)
+            | ASTType::CharacterLiteral(_)
+            | ASTType::StringLiteral(_)
+            | ASTType::NumberLiteral(_)
+            | ASTType::IntLiteral(_)
+            | ASTType::BooleanLiteral(_)
+            | ASTType::Break(_)
+            | ASTType::Continue(_) => (),
+        }
+    }
+}
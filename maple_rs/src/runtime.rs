@@ -1,11 +1,27 @@
+use crate::ast::ASTType;
 use crate::ast::Block;
 use crate::ast::ConvertScopeErrorResult;
+use crate::ast::EnumLiteral;
+use crate::ast::FieldKey;
+use crate::ast::ForLiteral;
 use crate::ast::IfLiteral;
+use crate::ast::MatchLiteral;
+use crate::ast::RangeLiteral;
+use crate::ast::StructLiteral;
+use crate::ast::WhileLiteral;
+use crate::big::BigPair;
+use crate::event_loop::await_future;
+use crate::lexer::Token;
+use crate::parser::EnumVariant;
 use crate::parser::Object;
+use crate::parser::ObjectKey;
+use crate::parser::Range;
+use crate::parser::StructDef;
 use crate::parser::Unpack;
 use crate::{error::RuntimeError, scopechain::ReturnType};
-use std::fs;
+use std::cmp::Ordering;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
 
 use crate::{ast::AST, parser::Value, scopechain::ScopeChain};
 
@@ -13,77 +29,117 @@ pub fn eval_op_andand(
     left: &Box<AST>,
     right: &Box<AST>,
     scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    eval_logic("&&", left, right, scope_chain)
+}
+pub fn eval_op_oror(
+    left: &Box<AST>,
+    right: &Box<AST>,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    eval_logic("||", left, right, scope_chain)
+}
+fn eval_logic(
+    op: &str,
+    left: &AST,
+    right: &AST,
+    scope_chain: &mut ScopeChain,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     let left_val =
         left.get_value(scope_chain)?
             .unpack_and_transform(scope_chain, left.get_line(), left)?;
-
-    match left_val.as_ref() {
-        Value::Boolean(false) => return Ok(Rc::new(Value::Boolean(false))),
-        Value::Boolean(true) => {}
-        _ => {
-            return Err(Box::new(RuntimeError::new(
-                format!(
-                    "Cannot apply operator && to left side type of {}",
-                    left_val.pretty_type(scope_chain, left.get_line())
-                ),
-                left.get_line(),
-            )))
-        }
-    };
+    if let Some(value) = logic_left(op, &left_val, left.get_line(), scope_chain)? {
+        return Ok(value);
+    }
     let right_val =
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
-    match right_val.as_ref() {
-        Value::Boolean(false) => Ok(Rc::new(Value::Boolean(false))),
-        Value::Boolean(true) => Ok(Rc::new(Value::Boolean(true))),
+    logic_right(
+        op,
+        &left_val,
+        &right_val,
+        left.get_line(),
+        right.get_line(),
+        scope_chain,
+    )
+}
+// the left side of && or ||, gives back the result if it decides it without the right side
+pub fn logic_left(
+    op: &str,
+    left_val: &Rc<Value>,
+    line: usize,
+    scope_chain: &ScopeChain,
+) -> Result<Option<Rc<Value>>, Box<RuntimeError>> {
+    match (op, left_val.as_ref()) {
+        ("&&", Value::Boolean(false)) => Ok(Some(Rc::new(Value::Boolean(false)))),
+        ("||", Value::Boolean(true)) => Ok(Some(Rc::new(Value::Boolean(true)))),
+        (_, Value::Boolean(_)) => Ok(None),
         _ => Err(Box::new(RuntimeError::new(
             format!(
-                "Cannot && types {} and {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-                right_val.pretty_type(scope_chain, right.get_line())
+                "Cannot apply operator {} to left side type of {}",
+                op,
+                left_val.pretty_type(scope_chain, line)
             ),
-            right.get_line(),
+            line,
         ))),
     }
 }
-pub fn eval_op_oror(
-    left: &Box<AST>,
-    right: &Box<AST>,
-    scope_chain: &mut ScopeChain,
+pub fn logic_right(
+    op: &str,
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    left_line: usize,
+    right_line: usize,
+    scope_chain: &ScopeChain,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
-    let left_val =
-        left.get_value(scope_chain)?
-            .unpack_and_transform(scope_chain, left.get_line(), left)?;
-
-    match left_val.as_ref() {
-        Value::Boolean(true) => return Ok(Rc::new(Value::Boolean(true))),
-        Value::Boolean(false) => {}
-        _ => {
-            return Err(Box::new(RuntimeError::new(
-                format!(
-                    "Cannot apply operator || to left side type of {}",
-                    left_val.pretty_type(scope_chain, left.get_line())
-                ),
-                left.get_line(),
-            )))
-        }
-    };
-    let right_val =
-        right
-            .get_value(scope_chain)?
-            .unpack_and_transform(scope_chain, right.get_line(), right)?;
     match right_val.as_ref() {
-        Value::Boolean(false) => Ok(Rc::new(Value::Boolean(false))),
-        Value::Boolean(true) => Ok(Rc::new(Value::Boolean(true))),
+        Value::Boolean(b) => Ok(Rc::new(Value::Boolean(*b))),
         _ => Err(Box::new(RuntimeError::new(
             format!(
-                "Cannot || types {} and {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-                right_val.pretty_type(scope_chain, right.get_line())
+                "Cannot {} types {} and {}",
+                op,
+                left_val.pretty_type(scope_chain, left_line),
+                right_val.pretty_type(scope_chain, right_line)
+            ),
+            right_line,
+        ))),
+    }
+}
+// objects can overload an operator by defining a method such as __add, the method is
+//  looked up on the left operand first, then the right, and gets both operands in order
+pub fn call_overload(
+    name: &str,
+    left: &Rc<Value>,
+    right: Option<&Rc<Value>>,
+    scope_chain: &mut ScopeChain,
+    line: usize,
+) -> Result<Option<Rc<Value>>, Box<RuntimeError>> {
+    let key = ObjectKey::String(name.to_string());
+    let method = [Some(left), right]
+        .into_iter()
+        .flatten()
+        .find_map(|v| match v.as_ref() {
+            Value::Object(obj) => obj.get_ref(&key, line).ok(),
+            _ => None,
+        });
+    let method = match method {
+        Some(method) => method,
+        None => return Ok(None),
+    };
+    let mut args = vec![left.clone()];
+    if let Some(right) = right {
+        args.push(right.clone());
+    }
+    match method.as_ref() {
+        Value::Function(func) => Ok(Some(func.call_values(scope_chain, args, line)?)),
+        v => Err(Box::new(RuntimeError::new(
+            format!(
+                "Expected {} to be a function, got {}",
+                name,
+                v.pretty_type(scope_chain, line)
             ),
-            right.get_line(),
+            line,
         ))),
     }
 }
@@ -94,16 +150,37 @@ pub fn eval_op_mns_prefix(
     let left_val =
         left.get_value(scope_chain)?
             .unpack_and_transform(scope_chain, left.get_line(), left)?;
+    neg_value(&left_val, left.get_line(), scope_chain)
+}
+pub fn neg_value(
+    left_val: &Rc<Value>,
+    line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
     match left_val.as_ref() {
         Value::Number(left) => Ok(Rc::new(Value::Number(-left))),
+        Value::BigInt(n) => Ok(Rc::new(Value::BigInt(n.neg()))),
+        Value::BigDecimal(n) => Ok(Rc::new(Value::BigDecimal(n.neg()))),
+        Value::Int(n) => match n.checked_neg() {
+            Some(n) => Ok(Rc::new(Value::Int(n))),
+            None => Err(Box::new(RuntimeError::new(
+                format!("Integer overflow in -{}", n),
+                line,
+            ))),
+        },
         Value::Char(left) => Ok(Rc::new(Value::Number(-(*left as i32 as f64)))),
-        _ => Err(Box::new(RuntimeError::new(
-            format!(
-                "Cannot negate type {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-            ),
-            left.get_line(),
-        ))),
+        _ => {
+            if let Some(value) = call_overload("__neg", left_val, None, scope_chain, line)? {
+                return Ok(value);
+            }
+            Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot negate type {}",
+                    left_val.pretty_type(scope_chain, line),
+                ),
+                line,
+            )))
+        }
     }
 }
 pub fn eval_op_not(
@@ -113,16 +190,40 @@ pub fn eval_op_not(
     let left_val =
         left.get_value(scope_chain)?
             .unpack_and_transform(scope_chain, left.get_line(), left)?;
+    not_value(&left_val, left.get_line(), scope_chain)
+}
+pub fn not_value(
+    left_val: &Rc<Value>,
+    line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
     match left_val.as_ref() {
         Value::Number(left) => Ok(Rc::new(Value::Boolean(*left == 0.0))),
+        Value::Int(left) => Ok(Rc::new(Value::Boolean(*left == 0))),
         Value::Char(left) => Ok(Rc::new(Value::Boolean(*left == '\0'))),
         Value::Boolean(left) => Ok(Rc::new(Value::Boolean(!*left))),
         _ => Err(Box::new(RuntimeError::new(
             format!(
                 "Cannot negate type {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
+                left_val.pretty_type(scope_chain, line),
             ),
-            left.get_line(),
+            line,
+        ))),
+    }
+}
+// int arithmetic is exact, so running out of bits is an error instead of a silent float
+fn checked_int(
+    result: Option<i64>,
+    op: &str,
+    left: i64,
+    right: i64,
+    line: usize,
+) -> Result<Value, Box<RuntimeError>> {
+    match result {
+        Some(n) => Ok(Value::Int(n)),
+        None => Err(Box::new(RuntimeError::new(
+            format!("Integer overflow in {} {} {}", left, op, right),
+            line,
         ))),
     }
 }
@@ -138,6 +239,24 @@ pub fn eval_op_mns(
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
+    sub_values(
+        &left_val,
+        &right_val,
+        left.get_line(),
+        right.get_line(),
+        scope_chain,
+    )
+}
+pub fn sub_values(
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    left_line: usize,
+    right_line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if let Some(pair) = BigPair::from_values(left_val, right_val) {
+        return Ok(Rc::new(pair.sub()));
+    }
     match (left_val.as_ref(), right_val.as_ref()) {
         (Value::Number(left), Value::Number(right)) => Ok(Rc::new(Value::Number(left - right))),
         (Value::Char(left), Value::Char(right)) => Ok(Rc::new(Value::Number(
@@ -149,14 +268,46 @@ pub fn eval_op_mns(
         (Value::Char(left), Value::Number(right)) => {
             Ok(Rc::new(Value::Number(*left as i32 as f64 - *right)))
         }
-        _ => Err(Box::new(RuntimeError::new(
-            format!(
-                "Cannot subtract types {} and {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-                right_val.pretty_type(scope_chain, right.get_line())
-            ),
-            left.get_line(),
-        ))),
+        (Value::Int(left), Value::Int(right)) => {
+            checked_int(left.checked_sub(*right), "-", *left, *right, left_line).map(Rc::new)
+        }
+        (Value::Int(left), Value::Number(right)) => {
+            Ok(Rc::new(Value::Number(*left as f64 - *right)))
+        }
+        (Value::Number(left), Value::Int(right)) => {
+            Ok(Rc::new(Value::Number(*left - *right as f64)))
+        }
+        (Value::Int(left), Value::Char(right)) => checked_int(
+            left.checked_sub(*right as i64),
+            "-",
+            *left,
+            *right as i64,
+            left_line,
+        )
+        .map(Rc::new),
+        (Value::Char(left), Value::Int(right)) => checked_int(
+            (*left as i64).checked_sub(*right),
+            "-",
+            *left as i64,
+            *right,
+            left_line,
+        )
+        .map(Rc::new),
+        _ => {
+            if let Some(value) =
+                call_overload("__sub", left_val, Some(right_val), scope_chain, left_line)?
+            {
+                return Ok(value);
+            }
+            Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot subtract types {} and {}",
+                    left_val.pretty_type(scope_chain, left_line),
+                    right_val.pretty_type(scope_chain, right_line)
+                ),
+                left_line,
+            )))
+        }
     }
 }
 pub fn eval_op_times(
@@ -171,6 +322,24 @@ pub fn eval_op_times(
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
+    mul_values(
+        &left_val,
+        &right_val,
+        left.get_line(),
+        right.get_line(),
+        scope_chain,
+    )
+}
+pub fn mul_values(
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    left_line: usize,
+    right_line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if let Some(pair) = BigPair::from_values(left_val, right_val) {
+        return Ok(Rc::new(pair.mul()));
+    }
     match (left_val.as_ref(), right_val.as_ref()) {
         (Value::Number(left), Value::Number(right)) => Ok(Rc::new(Value::Number(left * right))),
         (Value::Char(left), Value::Char(right)) => Ok(Rc::new(Value::Number(
@@ -182,14 +351,46 @@ pub fn eval_op_times(
         (Value::Char(left), Value::Number(right)) => {
             Ok(Rc::new(Value::Number(*left as i32 as f64 * *right)))
         }
-        _ => Err(Box::new(RuntimeError::new(
-            format!(
-                "Cannot multiply types {} and {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-                right_val.pretty_type(scope_chain, right.get_line())
-            ),
-            left.get_line(),
-        ))),
+        (Value::Int(left), Value::Int(right)) => {
+            checked_int(left.checked_mul(*right), "*", *left, *right, left_line).map(Rc::new)
+        }
+        (Value::Int(left), Value::Number(right)) => {
+            Ok(Rc::new(Value::Number(*left as f64 * *right)))
+        }
+        (Value::Number(left), Value::Int(right)) => {
+            Ok(Rc::new(Value::Number(*left * *right as f64)))
+        }
+        (Value::Int(left), Value::Char(right)) => checked_int(
+            left.checked_mul(*right as i64),
+            "*",
+            *left,
+            *right as i64,
+            left_line,
+        )
+        .map(Rc::new),
+        (Value::Char(left), Value::Int(right)) => checked_int(
+            (*left as i64).checked_mul(*right),
+            "*",
+            *left as i64,
+            *right,
+            left_line,
+        )
+        .map(Rc::new),
+        _ => {
+            if let Some(value) =
+                call_overload("__mul", left_val, Some(right_val), scope_chain, left_line)?
+            {
+                return Ok(value);
+            }
+            Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot multiply types {} and {}",
+                    left_val.pretty_type(scope_chain, left_line),
+                    right_val.pretty_type(scope_chain, right_line)
+                ),
+                left_line,
+            )))
+        }
     }
 }
 pub fn eval_op_div(
@@ -204,6 +405,30 @@ pub fn eval_op_div(
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
+    div_values(
+        &left_val,
+        &right_val,
+        left.get_line(),
+        right.get_line(),
+        scope_chain,
+    )
+}
+pub fn div_values(
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    left_line: usize,
+    right_line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if let Some(pair) = BigPair::from_values(left_val, right_val) {
+        return match pair.div() {
+            Some(value) => Ok(Rc::new(value)),
+            None => Err(Box::new(RuntimeError::new(
+                "Cannot divide a big number by zero".into(),
+                left_line,
+            ))),
+        };
+    }
     match (left_val.as_ref(), right_val.as_ref()) {
         (Value::Number(left), Value::Number(right)) => Ok(Rc::new(Value::Number(left / right))),
         (Value::Char(left), Value::Char(right)) => Ok(Rc::new(Value::Number(
@@ -215,14 +440,37 @@ pub fn eval_op_div(
         (Value::Char(left), Value::Number(right)) => {
             Ok(Rc::new(Value::Number(*left as i32 as f64 / *right)))
         }
-        _ => Err(Box::new(RuntimeError::new(
-            format!(
-                "Cannot divide types {} and {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-                right_val.pretty_type(scope_chain, right.get_line())
-            ),
-            left.get_line(),
-        ))),
+        // dividing ints always gives a float, use std.math.toInt to get back to an int
+        (Value::Int(left), Value::Int(right)) => {
+            Ok(Rc::new(Value::Number(*left as f64 / *right as f64)))
+        }
+        (Value::Int(left), Value::Number(right)) => {
+            Ok(Rc::new(Value::Number(*left as f64 / *right)))
+        }
+        (Value::Number(left), Value::Int(right)) => {
+            Ok(Rc::new(Value::Number(*left / *right as f64)))
+        }
+        (Value::Int(left), Value::Char(right)) => {
+            Ok(Rc::new(Value::Number(*left as f64 / *right as i32 as f64)))
+        }
+        (Value::Char(left), Value::Int(right)) => {
+            Ok(Rc::new(Value::Number(*left as i32 as f64 / *right as f64)))
+        }
+        _ => {
+            if let Some(value) =
+                call_overload("__div", left_val, Some(right_val), scope_chain, left_line)?
+            {
+                return Ok(value);
+            }
+            Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot divide types {} and {}",
+                    left_val.pretty_type(scope_chain, left_line),
+                    right_val.pretty_type(scope_chain, right_line)
+                ),
+                left_line,
+            )))
+        }
     }
 }
 pub fn eval_op_pls(
@@ -237,6 +485,24 @@ pub fn eval_op_pls(
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
+    add_values(
+        &left_val,
+        &right_val,
+        left.get_line(),
+        right.get_line(),
+        scope_chain,
+    )
+}
+pub fn add_values(
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    left_line: usize,
+    right_line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if let Some(pair) = BigPair::from_values(left_val, right_val) {
+        return Ok(Rc::new(pair.add()));
+    }
     match (left_val.as_ref(), right_val.as_ref()) {
         (Value::Number(left), Value::Number(right)) => Ok(Rc::new(Value::Number(left + right))),
         (Value::Char(left), Value::Char(right)) => Ok(Rc::new(Value::Number(
@@ -248,6 +514,31 @@ pub fn eval_op_pls(
         (Value::Char(left), Value::Number(right)) => {
             Ok(Rc::new(Value::Number(*left as i32 as f64 + *right)))
         }
+        (Value::Int(left), Value::Int(right)) => {
+            checked_int(left.checked_add(*right), "+", *left, *right, left_line).map(Rc::new)
+        }
+        (Value::Int(left), Value::Number(right)) => {
+            Ok(Rc::new(Value::Number(*left as f64 + *right)))
+        }
+        (Value::Number(left), Value::Int(right)) => {
+            Ok(Rc::new(Value::Number(*left + *right as f64)))
+        }
+        (Value::Int(left), Value::Char(right)) => checked_int(
+            left.checked_add(*right as i64),
+            "+",
+            *left,
+            *right as i64,
+            left_line,
+        )
+        .map(Rc::new),
+        (Value::Char(left), Value::Int(right)) => checked_int(
+            (*left as i64).checked_add(*right),
+            "+",
+            *left as i64,
+            *right,
+            left_line,
+        )
+        .map(Rc::new),
         (Value::String(left), Value::String(right)) => {
             Ok(Rc::new(Value::String(format!("{}{}", left, right))))
         }
@@ -257,14 +548,73 @@ pub fn eval_op_pls(
         (Value::Char(left), Value::String(right)) => {
             Ok(Rc::new(Value::String(format!("{}{}", left, right))))
         }
-        _ => Err(Box::new(RuntimeError::new(
-            format!(
-                "Cannot add types {} and {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-                right_val.pretty_type(scope_chain, right.get_line())
-            ),
-            left.get_line(),
-        ))),
+        _ => {
+            if let Some(value) =
+                call_overload("__add", left_val, Some(right_val), scope_chain, left_line)?
+            {
+                return Ok(value);
+            }
+            Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot add types {} and {}",
+                    left_val.pretty_type(scope_chain, left_line),
+                    right_val.pretty_type(scope_chain, right_line)
+                ),
+                left_line,
+            )))
+        }
+    }
+}
+// ints and floats compare by value, so 1 == 1.0, big numbers compare by value too. objects
+//  are equal when they have the same keys with equal values, in any order
+fn values_eq(left: &Value, right: &Value) -> bool {
+    values_eq_seen(left, right, &mut vec![])
+}
+// seen holds the pairs of objects that are being compared further up, running into one of
+//  them again means both sides loop back the same way, so that part counts as equal
+fn values_eq_seen(
+    left: &Value,
+    right: &Value,
+    seen: &mut Vec<(*const Object, *const Object)>,
+) -> bool {
+    if let Some(pair) = BigPair::from_values(left, right) {
+        return pair.cmp() == Ordering::Equal;
+    }
+    match (left, right) {
+        (Value::Int(left), Value::Number(right)) => *left as f64 == *right,
+        (Value::Number(left), Value::Int(right)) => *left == *right as f64,
+        (Value::Object(l), Value::Object(r)) => {
+            let pair = (l as *const Object, r as *const Object);
+            if std::ptr::eq(l, r) || seen.contains(&pair) {
+                return true;
+            }
+            if l.struct_def != r.struct_def || l.fields.len() != r.fields.len() {
+                return false;
+            }
+            seen.push(pair);
+            // the fields are looked at directly, going through get would copy the fields of a
+            //  frozen object and lose track of the cycles
+            let eq =
+                l.fields
+                    .iter()
+                    .all(|(key, lv)| match r.fields.iter().find(|(k, _)| k == key) {
+                        Some((_, rv)) => values_eq_seen(lv, rv, seen),
+                        None => false,
+                    });
+            seen.pop();
+            eq
+        }
+        (Value::Enum(left, left_fields), Value::Enum(right, right_fields)) => {
+            left == right
+                && left_fields.len() == right_fields.len()
+                && left_fields
+                    .iter()
+                    .zip(right_fields.iter())
+                    .all(|(l, r)| values_eq_seen(l, r, seen))
+        }
+        // functions are only equal to themselves, two functions with the same code aren't
+        (Value::Function(_), Value::Function(_)) => std::ptr::eq(left, right),
+        _ => left == right,
     }
 }
 pub fn eval_op_noteq(
@@ -279,7 +629,41 @@ pub fn eval_op_noteq(
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
-    Ok(Rc::new(Value::Boolean(left_val != right_val)))
+    noteq_values(&left_val, &right_val, left.get_line(), scope_chain)
+}
+pub fn noteq_values(
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if let Some(eq) = overloaded_bool("__eq", left_val, right_val, line, scope_chain)? {
+        return Ok(Rc::new(Value::Boolean(!eq)));
+    }
+    Ok(Rc::new(Value::Boolean(!values_eq(left_val, right_val))))
+}
+// calls an overload like __eq that has to give back a boolean, if either side has it
+fn overloaded_bool(
+    name: &str,
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Option<bool>, Box<RuntimeError>> {
+    match call_overload(name, left_val, Some(right_val), scope_chain, line)? {
+        None => Ok(None),
+        Some(value) => match value.as_ref() {
+            Value::Boolean(b) => Ok(Some(*b)),
+            v => Err(Box::new(RuntimeError::new(
+                format!(
+                    "Expected {} to return a boolean, got {}",
+                    name,
+                    v.pretty_type(scope_chain, line)
+                ),
+                line,
+            ))),
+        },
+    }
 }
 pub fn eval_op_eqeq(
     left: &Box<AST>,
@@ -293,22 +677,58 @@ pub fn eval_op_eqeq(
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
-    Ok(Rc::new(Value::Boolean(left_val == right_val)))
+    eq_values(&left_val, &right_val, left.get_line(), scope_chain)
+}
+pub fn eq_values(
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if let Some(eq) = overloaded_bool("__eq", left_val, right_val, line, scope_chain)? {
+        return Ok(Rc::new(Value::Boolean(eq)));
+    }
+    Ok(Rc::new(Value::Boolean(values_eq(left_val, right_val))))
+}
+// names both the field being changed and the frozen object it belongs to
+fn frozen_error(left: &AST, action: &str) -> Box<RuntimeError> {
+    let obj = match &left.t {
+        ASTType::DotAccess(obj, _) | ASTType::BracketAccess(obj, _) => obj.pretty_print(),
+        _ => left.pretty_print(),
+    };
+    Box::new(RuntimeError::new(
+        format!(
+            "Cannot {} {} because {} is frozen",
+            action,
+            left.pretty_print(),
+            obj
+        ),
+        left.get_line(),
+    ))
 }
 pub fn eval_op_eq(
     left: &Box<AST>,
     right: &Box<AST>,
     scope_chain: &mut ScopeChain,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
-    let left_val = left.get_value(scope_chain)?;
+    let left_val = left.get_target(scope_chain)?;
 
     let right_val =
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
+    assign_value(left, left_val, right_val, scope_chain)
+}
+// stores the value in whatever the left side of an = evaluated to
+pub fn assign_value(
+    left: &AST,
+    left_val: Rc<Value>,
+    right_val: Rc<Value>,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
     match left_val.as_ref() {
-        Value::Variable(name) => {
-            match scope_chain.set_variable(&name, right_val, left.get_line()) {
+        Value::Variable(name, address) => {
+            match scope_chain.set_variable_at(name, *address, right_val, left.get_line()) {
                 Ok(_) => (),
                 Err(e) => return Err(Box::new(e.to_runtime_error())),
             };
@@ -316,6 +736,12 @@ pub fn eval_op_eq(
         }
         Value::ObjectAccess(orgobj, key) => {
             let obj = match orgobj.as_ref() {
+                Value::Object(obj) if obj.frozen => return Err(frozen_error(left, "assign to")),
+                // a struct can't gain fields, so a typo can't quietly add one
+                Value::Object(obj) if obj.struct_def.is_some() => {
+                    obj.get_ref(key, left.get_line())?;
+                    obj as *const Object as *mut Object
+                }
                 Value::Object(obj) => obj as *const Object as *mut Object,
                 _ => {
                     return Err(Box::new(RuntimeError::new(
@@ -337,9 +763,10 @@ pub fn eval_op_eq(
         ))),
     }
 }
-pub fn eval_op_gteq(
-    left: &Box<AST>,
-    right: &Box<AST>,
+// key in obj, value in arr, n in range and substring in str
+pub fn eval_op_in(
+    left: &AST,
+    right: &AST,
     scope_chain: &mut ScopeChain,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     let left_val =
@@ -349,31 +776,203 @@ pub fn eval_op_gteq(
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
-    match (left_val.as_ref(), right_val.as_ref()) {
-        (Value::Number(left), Value::Number(right)) => Ok(Rc::new(Value::Boolean(left >= right))),
-        (Value::Char(left), Value::Char(right)) => Ok(Rc::new(Value::Boolean(
-            *left as i32 as f64 >= *right as i32 as f64,
-        ))),
-        (Value::Number(left), Value::Char(right)) => {
-            Ok(Rc::new(Value::Boolean(*left >= *right as i32 as f64)))
+    in_values(&left_val, &right_val, left.get_line(), scope_chain)
+}
+pub fn in_values(
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let found = match (left_val.as_ref(), right_val.as_ref()) {
+        (Value::String(sub), Value::String(s)) => s.contains(sub.as_str()),
+        (Value::Char(c), Value::String(s)) => s.contains(*c),
+        (v, Value::String(_)) => {
+            return Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot look for type {} in a string",
+                    v.pretty_type(scope_chain, line)
+                ),
+                line,
+            )))
         }
-        (Value::Char(left), Value::Number(right)) => {
-            Ok(Rc::new(Value::Boolean(*left as i32 as f64 >= *right)))
+        (Value::Int(n), Value::Range(range)) => range.contains(*n),
+        (_, Value::Range(_)) => false,
+        (_, Value::Object(obj)) if obj.is_array() => {
+            obj.fields.iter().any(|(_, v)| values_eq(left_val, v))
         }
-        _ => Err(Box::new(RuntimeError::new(
-            format!(
-                "Cannot compare types {} and {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-                right_val.pretty_type(scope_chain, right.get_line())
-            ),
-            left.get_line(),
+        (_, Value::Object(obj)) => {
+            let key = value_to_key(left_val, scope_chain, line)?;
+            obj.fields.iter().any(|(k, _)| k == &key)
+        }
+        (_, v) => {
+            return Err(Box::new(RuntimeError::new(
+                format!("Cannot use in on type {}", v.pretty_type(scope_chain, line)),
+                line,
+            )))
+        }
+    };
+    Ok(Rc::new(Value::Boolean(found)))
+}
+// takes a field out of an object
+pub fn eval_delete(
+    field: &AST,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let line = field.get_line();
+    let field_val = field.get_value(scope_chain)?;
+    let (orgobj, key) = match field_val.as_ref() {
+        Value::ObjectAccess(obj, key) => (obj, key),
+        _ => {
+            return Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot delete {}, it isn't an object field",
+                    field.pretty_print()
+                ),
+                line,
+            )))
+        }
+    };
+    let obj = match orgobj.as_ref() {
+        Value::Object(obj) if obj.frozen => return Err(frozen_error(field, "delete")),
+        Value::Object(obj) if obj.struct_def.is_some() => {
+            return Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot delete {} because {} is a struct, its fields are fixed",
+                    field.pretty_print(),
+                    obj.struct_def.as_ref().unwrap().name
+                ),
+                line,
+            )))
+        }
+        Value::Object(obj) => obj as *const Object as *mut Object,
+        _ => {
+            return Err(Box::new(RuntimeError::new(
+                "Cannot delete field of non-object".into(),
+                line,
+            )))
+        }
+    };
+    let removed = unsafe {
+        let obj = obj.as_mut().expect("the pointer came from a reference");
+        match key {
+            ObjectKey::Number(n) if obj.is_array() => obj.remove_element(*n),
+            _ => obj.remove(key),
+        }
+    };
+    match removed {
+        Some(_) => Ok(Rc::new(Value::Undefined)),
+        None => Err(Box::new(RuntimeError::new(
+            format!("Object does not have key {}", key),
+            line,
         ))),
     }
 }
-pub fn eval_op_lteq(
-    left: &Box<AST>,
-    right: &Box<AST>,
+// the order of two values, None when they can't be compared. strings compare by their
+//  chars and arrays element by element, a shorter array that matches the start of a longer
+//  one comes first
+pub fn compare_values(left: &Value, right: &Value) -> Option<Ordering> {
+    if let Some(pair) = BigPair::from_values(left, right) {
+        return Some(pair.cmp());
+    }
+    match (left, right) {
+        (Value::Int(left), Value::Int(right)) => Some(left.cmp(right)),
+        (Value::Int(left), Value::Number(right)) => (*left as f64).partial_cmp(right),
+        (Value::Number(left), Value::Int(right)) => left.partial_cmp(&(*right as f64)),
+        (Value::Int(left), Value::Char(right)) => Some(left.cmp(&(*right as i64))),
+        (Value::Char(left), Value::Int(right)) => Some((*left as i64).cmp(right)),
+        (Value::Number(left), Value::Number(right)) => left.partial_cmp(right),
+        (Value::Char(left), Value::Char(right)) => Some(left.cmp(right)),
+        (Value::Number(left), Value::Char(right)) => left.partial_cmp(&(*right as i32 as f64)),
+        (Value::Char(left), Value::Number(right)) => (*left as i32 as f64).partial_cmp(right),
+        (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+        (Value::Object(left), Value::Object(right))
+            if is_plain_array(left) && is_plain_array(right) =>
+        {
+            let (left_len, right_len) = (left.array_len(), right.array_len());
+            for i in 0..left_len.min(right_len) {
+                let key = ObjectKey::Number(i as f64);
+                let l = left.get_ref(&key, 0).ok()?;
+                let r = right.get_ref(&key, 0).ok()?;
+                match compare_values(&l, &r)? {
+                    Ordering::Equal => (),
+                    ord => return Some(ord),
+                }
+            }
+            Some(left_len.cmp(&right_len))
+        }
+        _ => None,
+    }
+}
+// compare_values, except that arrays of objects and the objects themselves can be ordered
+//  with __lt or __gt. the error names the innermost pair that couldn't be ordered
+pub fn order_values(
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    line: usize,
+    scope_chain: &mut ScopeChain,
+) -> Result<Ordering, Box<RuntimeError>> {
+    if let Some(ord) = compare_values(left_val, right_val) {
+        return Ok(ord);
+    }
+    match (left_val.as_ref(), right_val.as_ref()) {
+        (Value::Object(left), Value::Object(right))
+            if is_plain_array(left) && is_plain_array(right) =>
+        {
+            let (left_len, right_len) = (left.array_len(), right.array_len());
+            for i in 0..left_len.min(right_len) {
+                let key = ObjectKey::Number(i as f64);
+                let l = left.get_ref(&key, line)?;
+                let r = right.get_ref(&key, line)?;
+                match order_values(&l, &r, line, scope_chain)? {
+                    Ordering::Equal => (),
+                    ord => return Ok(ord),
+                }
+            }
+            Ok(left_len.cmp(&right_len))
+        }
+        // NaN is a number like any other, it just isn't more or less than anything
+        (Value::Number(n), _) | (_, Value::Number(n)) if n.is_nan() => Err(Box::new(
+            RuntimeError::new("Cannot put NaN in order".into(), line),
+        )),
+        _ => {
+            for (name, ord) in [("__lt", Ordering::Less), ("__gt", Ordering::Greater)] {
+                match overloaded_bool(name, left_val, right_val, line, scope_chain)? {
+                    None => continue,
+                    Some(true) => return Ok(ord),
+                    // neither way around means the two are equal
+                    Some(false) => {
+                        let reverse =
+                            overloaded_bool(name, right_val, left_val, line, scope_chain)?;
+                        return Ok(match reverse {
+                            Some(true) => ord.reverse(),
+                            _ => Ordering::Equal,
+                        });
+                    }
+                }
+            }
+            Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot compare types {} and {}",
+                    left_val.pretty_type(scope_chain, line),
+                    right_val.pretty_type(scope_chain, line)
+                ),
+                line,
+            )))
+        }
+    }
+}
+// an array or an empty object, which is also how an empty array looks
+fn is_plain_array(obj: &Object) -> bool {
+    obj.struct_def.is_none() && obj.array_len() == obj.fields.len()
+}
+// <, >, <= and >=, holds says which orders make the comparison true
+fn eval_comparison(
+    left: &AST,
+    right: &AST,
     scope_chain: &mut ScopeChain,
+    overload: &str,
+    holds: fn(Ordering) -> bool,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
     let left_val =
         left.get_value(scope_chain)?
@@ -382,103 +981,114 @@ pub fn eval_op_lteq(
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
+    comparison_values(
+        &left_val,
+        &right_val,
+        left.get_line(),
+        scope_chain,
+        overload,
+        holds,
+    )
+}
+pub fn comparison_values(
+    left_val: &Rc<Value>,
+    right_val: &Rc<Value>,
+    left_line: usize,
+    scope_chain: &mut ScopeChain,
+    overload: &str,
+    holds: fn(Ordering) -> bool,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if let Some(ord) = compare_values(left_val, right_val) {
+        return Ok(Rc::new(Value::Boolean(holds(ord))));
+    }
     match (left_val.as_ref(), right_val.as_ref()) {
-        (Value::Number(left), Value::Number(right)) => Ok(Rc::new(Value::Boolean(left <= right))),
-        (Value::Char(left), Value::Char(right)) => Ok(Rc::new(Value::Boolean(*left <= *right))),
-        (Value::Number(left), Value::Char(right)) => {
-            Ok(Rc::new(Value::Boolean(*left <= *right as i32 as f64)))
-        }
-        (Value::Char(left), Value::Number(right)) => {
-            Ok(Rc::new(Value::Boolean(*left as i32 as f64 <= *right)))
+        // NaN isn't ordered against anything, so every comparison with it is false
+        (
+            Value::Int(_) | Value::Number(_) | Value::Char(_),
+            Value::Int(_) | Value::Number(_) | Value::Char(_),
+        ) => Ok(Rc::new(Value::Boolean(false))),
+        _ => {
+            if let Some(value) =
+                call_overload(overload, left_val, Some(right_val), scope_chain, left_line)?
+            {
+                return Ok(value);
+            }
+            let ord = order_values(left_val, right_val, left_line, scope_chain)?;
+            Ok(Rc::new(Value::Boolean(holds(ord))))
         }
-        _ => Err(Box::new(RuntimeError::new(
-            format!(
-                "Cannot compare types {} and {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-                right_val.pretty_type(scope_chain, right.get_line())
-            ),
-            left.get_line(),
-        ))),
     }
 }
+pub fn eval_op_gteq(
+    left: &Box<AST>,
+    right: &Box<AST>,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    eval_comparison(left, right, scope_chain, "__ge", |ord| {
+        ord != Ordering::Less
+    })
+}
+pub fn eval_op_lteq(
+    left: &Box<AST>,
+    right: &Box<AST>,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    eval_comparison(left, right, scope_chain, "__le", |ord| {
+        ord != Ordering::Greater
+    })
+}
 pub fn eval_op_gt(
     left: &Box<AST>,
     right: &Box<AST>,
     scope_chain: &mut ScopeChain,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
-    let left_val =
-        left.get_value(scope_chain)?
-            .unpack_and_transform(scope_chain, left.get_line(), left)?;
-    let right_val =
-        right
-            .get_value(scope_chain)?
-            .unpack_and_transform(scope_chain, right.get_line(), right)?;
-    match (left_val.as_ref(), right_val.as_ref()) {
-        (Value::Number(left), Value::Number(right)) => Ok(Rc::new(Value::Boolean(left > right))),
-        (Value::Char(left), Value::Char(right)) => Ok(Rc::new(Value::Boolean(*left > *right))),
-        (Value::Number(left), Value::Char(right)) => {
-            Ok(Rc::new(Value::Boolean(*left > *right as i32 as f64)))
-        }
-        (Value::Char(left), Value::Number(right)) => {
-            Ok(Rc::new(Value::Boolean(*left as i32 as f64 > *right)))
-        }
-        _ => Err(Box::new(RuntimeError::new(
-            format!(
-                "Cannot compare types {} and {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-                right_val.pretty_type(scope_chain, right.get_line())
-            ),
-            left.get_line(),
-        ))),
-    }
+    eval_comparison(left, right, scope_chain, "__gt", |ord| {
+        ord == Ordering::Greater
+    })
 }
 pub fn eval_op_lt(
     left: &Box<AST>,
     right: &Box<AST>,
     scope_chain: &mut ScopeChain,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
-    let left_val =
-        left.get_value(scope_chain)?
-            .unpack_and_transform(scope_chain, left.get_line(), left)?;
+    eval_comparison(left, right, scope_chain, "__lt", |ord| {
+        ord == Ordering::Less
+    })
+}
+pub fn eval_op_plseq(
+    left: &Box<AST>,
+    right: &Box<AST>,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let left_val = left.get_target(scope_chain)?;
+    check_plseq_target(left, &left_val)?;
     let right_val =
         right
             .get_value(scope_chain)?
             .unpack_and_transform(scope_chain, right.get_line(), right)?;
-    match (left_val.as_ref(), right_val.as_ref()) {
-        (Value::Number(left), Value::Number(right)) => Ok(Rc::new(Value::Boolean(left < right))),
-        (Value::Char(left), Value::Char(right)) => Ok(Rc::new(Value::Boolean(
-            (*left as i32 as f64) < (*right as i32 as f64),
-        ))),
-        (Value::Number(left), Value::Char(right)) => {
-            Ok(Rc::new(Value::Boolean(*left < *right as i32 as f64)))
-        }
-        (Value::Char(left), Value::Number(right)) => {
-            Ok(Rc::new(Value::Boolean((*left as i32 as f64) < *right)))
-        }
+    plseq_value(left, right, left_val, right_val, scope_chain)
+}
+// the right side of a += is only evaluated once the left side is known to be assignable
+pub fn check_plseq_target(left: &AST, left_val: &Rc<Value>) -> Result<(), Box<RuntimeError>> {
+    match left_val.as_ref() {
+        Value::ObjectAccess(_, _) | Value::Variable(_, _) => Ok(()),
         _ => Err(Box::new(RuntimeError::new(
-            format!(
-                "Cannot compare types {} and {}",
-                left_val.pretty_type(scope_chain, left.get_line()),
-                right_val.pretty_type(scope_chain, right.get_line())
-            ),
+            "Cannot assign to a non-variable in +=".into(),
             left.get_line(),
         ))),
     }
 }
-pub fn eval_op_plseq(
-    left: &Box<AST>,
-    right: &Box<AST>,
+// adds the value into whatever the left side of a += evaluated to, in place
+pub fn plseq_value(
+    left: &AST,
+    right: &AST,
+    left_val: Rc<Value>,
+    right_val: Rc<Value>,
     scope_chain: &mut ScopeChain,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
-    let left_val = left.get_value(scope_chain)?;
     match left_val.as_ref() {
         Value::ObjectAccess(obj, key) => {
-            let right_val = right.get_value(scope_chain)?.unpack_and_transform(
-                scope_chain,
-                right.get_line(),
-                right,
-            )?;
             let o = match obj.as_ref() {
+                Value::Object(obj) if obj.frozen => return Err(frozen_error(left, "assign to")),
                 Value::Object(obj) => obj,
                 _ => {
                     return Err(Box::new(RuntimeError::new(
@@ -487,51 +1097,21 @@ pub fn eval_op_plseq(
                     )))
                 }
             };
-            let a = (*o).get(key.clone(), left.get_line())?;
-            let a_ptr = Rc::<Value>::as_ptr(&a) as *mut Value;
+            let mut a = (*o).get(key.clone(), left.get_line())?;
             let b = right_val.unpack_and_transform(scope_chain, right.get_line(), right)?;
-            match (a.as_ref(), b.as_ref()) {
-                (Value::Number(a), Value::Number(b)) => unsafe {
-                    *a_ptr = Value::Number(*a + *b);
-                },
-                (Value::Char(a), Value::Char(b)) => unsafe {
-                    *a_ptr = Value::Number(*a as i32 as f64 + *b as i32 as f64);
-                },
-                (Value::Number(a), Value::Char(b)) => unsafe {
-                    *a_ptr = Value::Number(*a + *b as i32 as f64);
-                },
-                (Value::Char(a), Value::Number(b)) => unsafe {
-                    *a_ptr = Value::Number(*a as i32 as f64 + *b);
-                },
-                (Value::String(a), Value::String(b)) => unsafe {
-                    *a_ptr = Value::String(format!("{}{}", a, b));
-                },
-                (Value::String(a), Value::Char(b)) => unsafe {
-                    *a_ptr = Value::String(format!("{}{}", a, b));
-                },
-                (Value::Char(a), Value::String(b)) => unsafe {
-                    *a_ptr = Value::String(format!("{}{}", a, b));
-                },
-                _ => {
-                    return Err(Box::new(RuntimeError::new(
-                        format!(
-                            "Cannot add types {} and {}",
-                            a.pretty_type(scope_chain, left.get_line()),
-                            b.pretty_type(scope_chain, right.get_line())
-                        ),
-                        left.get_line(),
-                    )));
+            // the field is shared with a frozen object, so it gets a copy of its own to add into
+            if Value::is_held_frozen(&a) {
+                a = Rc::new(a.as_ref().clone());
+                let o_ptr = o as *const Object as *mut Object;
+                unsafe {
+                    (*o_ptr).set_ref(key, a.clone());
                 }
-            };
+            }
+            add_in_place(&a, &b, left.get_line(), right.get_line(), scope_chain)?;
             Ok(left_val)
         }
-        Value::Variable(name) => {
-            let right_val = right.get_value(scope_chain)?.unpack_and_transform(
-                scope_chain,
-                right.get_line(),
-                right,
-            )?;
-            if match scope_chain.is_const(&name, left.get_line()) {
+        Value::Variable(name, address) => {
+            if match scope_chain.is_const_at(name, *address, left.get_line()) {
                 Ok(v) => v,
                 Err(e) => return Err(Box::new(e.to_runtime_error())),
             } {
@@ -540,46 +1120,22 @@ pub fn eval_op_plseq(
                     left.get_line(),
                 )));
             }
-            let a = match scope_chain.get_variable(&name, left.get_line()) {
+            let mut a = match scope_chain.get_variable_at(name, *address, left.get_line()) {
                 Ok(v) => v,
                 Err(e) => return Err(Box::new(e.to_runtime_error())),
             };
-            let a_ptr = Rc::<Value>::as_ptr(&a) as *mut Value;
             let b = right_val.unpack_and_transform(scope_chain, right.get_line(), right)?;
-            match (a.as_ref(), b.as_ref()) {
-                (Value::Number(a), Value::Number(b)) => unsafe {
-                    *a_ptr = Value::Number(*a + *b);
-                },
-                (Value::Char(a), Value::Char(b)) => unsafe {
-                    *a_ptr = Value::Number(*a as i32 as f64 + *b as i32 as f64);
-                },
-                (Value::Number(a), Value::Char(b)) => unsafe {
-                    *a_ptr = Value::Number(*a + *b as i32 as f64);
-                },
-                (Value::Char(a), Value::Number(b)) => unsafe {
-                    *a_ptr = Value::Number(*a as i32 as f64 + *b);
-                },
-                (Value::String(a), Value::String(b)) => unsafe {
-                    *a_ptr = Value::String(format!("{}{}", a, b));
-                },
-                (Value::String(a), Value::Char(b)) => unsafe {
-                    *a_ptr = Value::String(format!("{}{}", a, b));
-                },
-                (Value::Char(a), Value::String(b)) => unsafe {
-                    *a_ptr = Value::String(format!("{}{}", a, b));
-                },
-                _ => {
-                    return Err(Box::new(RuntimeError::new(
-                        format!(
-                            "Cannot add types {} and {}",
-                            a.pretty_type(scope_chain, left.get_line()),
-                            b.pretty_type(scope_chain, right.get_line())
-                        ),
-                        left.get_line(),
-                    )));
+            // same as above, the variable is given its own copy of a frozen value
+            if Value::is_held_frozen(&a) {
+                a = Rc::new(a.as_ref().clone());
+                if let Err(e) =
+                    scope_chain.set_variable_at(name, *address, a.clone(), left.get_line())
+                {
+                    return Err(Box::new(e.to_runtime_error()));
                 }
-            };
-            Ok(Rc::new(Value::Variable(name.clone())))
+            }
+            add_in_place(&a, &b, left.get_line(), right.get_line(), scope_chain)?;
+            Ok(Rc::new(Value::Variable(name.clone(), *address)))
         }
         _ => Err(Box::new(RuntimeError::new(
             "Cannot assign to a non-variable in +=".into(),
@@ -587,6 +1143,71 @@ pub fn eval_op_plseq(
         ))),
     }
 }
+// writes a + b into a, so that everything holding a sees the new value
+fn add_in_place(
+    a: &Rc<Value>,
+    b: &Rc<Value>,
+    left_line: usize,
+    right_line: usize,
+    scope_chain: &ScopeChain,
+) -> Result<(), Box<RuntimeError>> {
+    let a_ptr = Rc::<Value>::as_ptr(a) as *mut Value;
+    if let Some(pair) = BigPair::from_values(a, b) {
+        unsafe {
+            *a_ptr = pair.add();
+        }
+        return Ok(());
+    }
+    match (a.as_ref(), b.as_ref()) {
+        (Value::Number(a), Value::Number(b)) => unsafe {
+            *a_ptr = Value::Number(*a + *b);
+        },
+        (Value::Char(a), Value::Char(b)) => unsafe {
+            *a_ptr = Value::Number(*a as i32 as f64 + *b as i32 as f64);
+        },
+        (Value::Number(a), Value::Char(b)) => unsafe {
+            *a_ptr = Value::Number(*a + *b as i32 as f64);
+        },
+        (Value::Char(a), Value::Number(b)) => unsafe {
+            *a_ptr = Value::Number(*a as i32 as f64 + *b);
+        },
+        (Value::Int(a), Value::Int(b)) => unsafe {
+            *a_ptr = checked_int(a.checked_add(*b), "+", *a, *b, left_line)?;
+        },
+        (Value::Int(a), Value::Number(b)) => unsafe {
+            *a_ptr = Value::Number(*a as f64 + *b);
+        },
+        (Value::Number(a), Value::Int(b)) => unsafe {
+            *a_ptr = Value::Number(*a + *b as f64);
+        },
+        (Value::Int(a), Value::Char(b)) => unsafe {
+            *a_ptr = checked_int(a.checked_add(*b as i64), "+", *a, *b as i64, left_line)?;
+        },
+        (Value::Char(a), Value::Int(b)) => unsafe {
+            *a_ptr = checked_int((*a as i64).checked_add(*b), "+", *a as i64, *b, left_line)?;
+        },
+        (Value::String(a), Value::String(b)) => unsafe {
+            *a_ptr = Value::String(format!("{}{}", a, b));
+        },
+        (Value::String(a), Value::Char(b)) => unsafe {
+            *a_ptr = Value::String(format!("{}{}", a, b));
+        },
+        (Value::Char(a), Value::String(b)) => unsafe {
+            *a_ptr = Value::String(format!("{}{}", a, b));
+        },
+        _ => {
+            return Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot add types {} and {}",
+                    a.pretty_type(scope_chain, left_line),
+                    b.pretty_type(scope_chain, right_line)
+                ),
+                left_line,
+            )));
+        }
+    };
+    Ok(())
+}
 pub fn eval_if(
     if_lit: &IfLiteral,
     if_lit_ast: &AST,
@@ -663,55 +1284,925 @@ pub fn eval_if(
     }
     return Ok(Rc::new(Value::Undefined));
 }
-pub fn eval_while(
-    cond: &Box<AST>,
-    block: &Block,
+// declares the enum as a const object holding a constructor for each variant, variants
+//  without fields are values right away
+pub fn eval_enum(
+    enum_lit: &EnumLiteral,
     scope_chain: &mut ScopeChain,
+    line: usize,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
-    loop {
-        let cond_val = cond.get_value(scope_chain)?.unpack_and_transform(
+    let mut obj = Object::new();
+    for (name, fields) in enum_lit.variants.iter() {
+        let variant = Rc::new(EnumVariant {
+            enum_name: enum_lit.name.clone(),
+            name: name.clone(),
+            fields: fields.clone(),
+        });
+        let value = if fields.is_empty() {
+            Value::Enum(variant, vec![])
+        } else {
+            Value::EnumConstructor(variant)
+        };
+        obj.set(ObjectKey::String(name.clone()), Rc::new(value));
+    }
+    scope_chain
+        .add_variable(&enum_lit.name, true, line)
+        .to_runtime_error()?;
+    scope_chain
+        .set_variable(&enum_lit.name, Rc::new(Value::Object(obj)), line)
+        .to_runtime_error()?;
+    Ok(Rc::new(Value::Undefined))
+}
+pub fn construct_enum(
+    variant: &Rc<EnumVariant>,
+    params: &Block,
+    scope_chain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if params.len() != variant.fields.len() {
+        return Err(Box::new(RuntimeError::new(
+            format!(
+                "{}.{} expects {} fields, got {}",
+                variant.enum_name,
+                variant.name,
+                variant.fields.len(),
+                params.len()
+            ),
+            line,
+        )));
+    }
+    let fields = params
+        .iter()
+        .map(|ast| {
+            ast.get_value(scope_chain)
+                .unpack_and_transform(scope_chain, ast.get_line(), ast)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    construct_enum_values(variant, fields, line)
+}
+// the same with fields that have already been evaluated
+pub fn construct_enum_values(
+    variant: &Rc<EnumVariant>,
+    fields: Vec<Rc<Value>>,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if fields.len() != variant.fields.len() {
+        return Err(Box::new(RuntimeError::new(
+            format!(
+                "{}.{} expects {} fields, got {}",
+                variant.enum_name,
+                variant.name,
+                variant.fields.len(),
+                fields.len()
+            ),
+            line,
+        )));
+    }
+    Ok(Rc::new(Value::Enum(variant.clone(), fields)))
+}
+// the variables a match arm adds, along with their values
+type Bindings = Vec<(String, Rc<Value>)>;
+// declares the struct as a const constructor, the fields are given in the order they
+//  were declared
+pub fn eval_struct(
+    struct_lit: &StructLiteral,
+    scope_chain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let def = Rc::new(StructDef {
+        name: struct_lit.name.clone(),
+        fields: struct_lit.fields.clone(),
+    });
+    scope_chain
+        .add_variable(&struct_lit.name, true, line)
+        .to_runtime_error()?;
+    scope_chain
+        .set_variable(
+            &struct_lit.name,
+            Rc::new(Value::StructConstructor(def)),
+            line,
+        )
+        .to_runtime_error()?;
+    Ok(Rc::new(Value::Undefined))
+}
+pub fn construct_struct(
+    def: &Rc<StructDef>,
+    params: &Block,
+    scope_chain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if params.len() != def.fields.len() {
+        return Err(Box::new(RuntimeError::new(
+            format!(
+                "{} expects {} fields, got {}",
+                def.name,
+                def.fields.len(),
+                params.len()
+            ),
+            line,
+        )));
+    }
+    let mut values = vec![];
+    for ast in params.iter() {
+        values.push(ast.get_value(scope_chain)?.unpack_and_transform(
             scope_chain,
-            cond.get_line(),
-            cond,
-        )?;
-        match cond_val.as_ref() {
-            Value::Boolean(true) => {
-                scope_chain.add_scope().to_runtime_error()?;
-                for ast in block.iter() {
-                    ast.get_value(scope_chain)?;
-                    match scope_chain.get_return_register() {
-                        ReturnType::None => (),
-                        _ => break,
+            ast.get_line(),
+            ast,
+        )?);
+    }
+    construct_struct_values(def, values, line)
+}
+// the same with fields that have already been evaluated
+pub fn construct_struct_values(
+    def: &Rc<StructDef>,
+    values: Vec<Rc<Value>>,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if values.len() != def.fields.len() {
+        return Err(Box::new(RuntimeError::new(
+            format!(
+                "{} expects {} fields, got {}",
+                def.name,
+                def.fields.len(),
+                values.len()
+            ),
+            line,
+        )));
+    }
+    let mut obj = Object::new();
+    for (field, value) in def.fields.iter().zip(values) {
+        obj.set(ObjectKey::String(field.clone()), value);
+    }
+    obj.struct_def = Some(def.clone());
+    Ok(Rc::new(Value::Object(obj)))
+}
+// gives back the variables a pattern binds if the value fits it, names only bind inside
+//  of a variant pattern, anywhere else they get compared like any other expression
+pub fn match_pattern(
+    pattern: &AST,
+    value: &Rc<Value>,
+    is_field: bool,
+    scope_chain: &mut ScopeChain,
+) -> Result<Option<Bindings>, Box<RuntimeError>> {
+    let line = pattern.get_line();
+    match &pattern.t {
+        ASTType::VariableAccess(name, _) if name == "_" => return Ok(Some(vec![])),
+        ASTType::VariableAccess(name, _) if is_field => {
+            return Ok(Some(vec![(name.clone(), value.clone())]))
+        }
+        ASTType::FunctionCall(callee, args) => {
+            let callee_val =
+                callee
+                    .get_value(scope_chain)?
+                    .unpack_and_transform(scope_chain, line, callee)?;
+            if let Value::EnumConstructor(variant) = callee_val.as_ref() {
+                if args.len() != variant.fields.len() {
+                    return Err(Box::new(RuntimeError::new(
+                        format!(
+                            "Pattern for {}.{} has {} fields, expected {}",
+                            variant.enum_name,
+                            variant.name,
+                            args.len(),
+                            variant.fields.len()
+                        ),
+                        line,
+                    )));
+                }
+                let fields = match value.as_ref() {
+                    Value::Enum(v, fields) if v == variant => fields,
+                    _ => return Ok(None),
+                };
+                let mut bindings = vec![];
+                for (arg, field) in args.iter().zip(fields.iter()) {
+                    match match_pattern(arg, field, true, scope_chain)? {
+                        Some(b) => bindings.extend(b),
+                        None => return Ok(None),
                     }
                 }
-                scope_chain.pop_scope().to_runtime_error()?;
+                return Ok(Some(bindings));
+            }
+        }
+        _ => (),
+    }
+    let pattern_val =
+        pattern
+            .get_value(scope_chain)?
+            .unpack_and_transform(scope_chain, line, pattern)?;
+    if values_eq(value, &pattern_val) {
+        Ok(Some(vec![]))
+    } else {
+        Ok(None)
+    }
+}
+// runs the first arm whose pattern fits, an expression arm gives back its value and a
+//  block arm works like the body of an if
+pub fn eval_match(
+    match_lit: &MatchLiteral,
+    match_ast: &AST,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let line = match_ast.get_line();
+    let value = match_lit
+        .value
+        .get_value(scope_chain)?
+        .unpack_and_transform(scope_chain, line, &match_lit.value)?;
+    for arm in match_lit.arms.iter() {
+        let bindings = match match_pattern(&arm.pattern, &value, false, scope_chain)? {
+            Some(bindings) => bindings,
+            None => continue,
+        };
+        scope_chain.add_scope().to_runtime_error()?;
+        for (name, v) in bindings {
+            scope_chain
+                .add_variable(&name, false, arm.pattern.get_line())
+                .to_runtime_error()?;
+            scope_chain
+                .set_variable(&name, v, arm.pattern.get_line())
+                .to_runtime_error()?;
+        }
+        let mut ret = Rc::new(Value::Undefined);
+        if arm.is_block {
+            for ast in arm.body.iter() {
+                ast.get_value(scope_chain)?;
                 match scope_chain.get_return_register() {
                     ReturnType::None => (),
-                    ReturnType::Break => {
-                        scope_chain.eat_return_register();
-                        return Ok(Rc::new(Value::Undefined));
-                    }
-                    ReturnType::Continue => {
-                        scope_chain.eat_return_register();
-                    }
-                    ReturnType::Return(v) => return Ok(v),
+                    _ => break,
                 }
             }
-            Value::Boolean(false) => break,
-            _ => {
-                return Err(Box::new(RuntimeError::new(
-                    "While condition must be a boolean".into(),
+        } else {
+            let body = &arm.body[0];
+            ret = body.get_value(scope_chain)?.unpack_and_transform(
+                scope_chain,
+                body.get_line(),
+                body,
+            )?;
+        }
+        scope_chain.pop_scope().to_runtime_error()?;
+        return Ok(ret);
+    }
+    Err(no_arm_error(&value, scope_chain, line))
+}
+pub fn no_arm_error(value: &Value, scope_chain: &ScopeChain, line: usize) -> Box<RuntimeError> {
+    Box::new(RuntimeError::new(
+        format!(
+            "No arm of the match matched a value of type {}",
+            value.pretty_type(scope_chain, line)
+        ),
+        line,
+    ))
+}
+pub fn eval_while(
+    while_lit: &WhileLiteral,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    // do while skips the condition the first time around
+    let mut check_cond = !while_lit.is_do_while;
+    loop {
+        match &while_lit.cond {
+            Some(cond) if check_cond => {
+                let cond_val = cond.get_value(scope_chain)?.unpack_and_transform(
+                    scope_chain,
                     cond.get_line(),
-                )))
+                    cond,
+                )?;
+                match cond_val.as_ref() {
+                    Value::Boolean(true) => (),
+                    Value::Boolean(false) => break,
+                    _ => {
+                        return Err(Box::new(RuntimeError::new(
+                            "While condition must be a boolean".into(),
+                            cond.get_line(),
+                        )))
+                    }
+                }
+            }
+            _ => (),
+        }
+        check_cond = true;
+        scope_chain.add_scope().to_runtime_error()?;
+        for ast in while_lit.body.iter() {
+            ast.get_value(scope_chain)?;
+            match scope_chain.get_return_register() {
+                ReturnType::None => (),
+                _ => break,
+            }
+        }
+        scope_chain.pop_scope().to_runtime_error()?;
+        match scope_chain.get_return_register() {
+            ReturnType::None => (),
+            ReturnType::Break(target) if target.is_none() || target == while_lit.label => {
+                scope_chain.eat_return_register();
+                return Ok(Rc::new(Value::Undefined));
             }
+            ReturnType::Continue(target) if target.is_none() || target == while_lit.label => {
+                scope_chain.eat_return_register();
+            }
+            ReturnType::Return(v) => return Ok(v),
+            // a labeled break or continue for an outer loop, leave it in the register
+            _ => return Ok(Rc::new(Value::Undefined)),
         }
     }
+    // the condition ended the loop rather than a break, so the else block runs
+    if let Some(else_body) = &while_lit.else_body {
+        scope_chain.add_scope().to_runtime_error()?;
+        for ast in else_body.iter() {
+            ast.get_value(scope_chain)?;
+            match scope_chain.get_return_register() {
+                ReturnType::None => (),
+                _ => break,
+            }
+        }
+        scope_chain.pop_scope().to_runtime_error()?;
+    }
     Ok(Rc::new(Value::Undefined))
 }
+// numbers the names that quote renames, so that every expansion gets its own
+static QUOTE_COUNT: AtomicUsize = AtomicUsize::new(0);
+pub fn eval_range(
+    range_lit: &RangeLiteral,
+    scope_chain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let mut bound = |ast: &AST, what: &str| -> Result<i64, Box<RuntimeError>> {
+        let value =
+            ast.get_value(scope_chain)?
+                .unpack_and_transform(scope_chain, ast.get_line(), ast)?;
+        range_bound(&value, what, scope_chain, line)
+    };
+    let start = bound(&range_lit.start, "start")?;
+    let end = bound(&range_lit.end, "end")?;
+    let step = match &range_lit.step {
+        Some(step) => bound(step, "step")?,
+        None => 1,
+    };
+    make_range(start, end, step, range_lit.inclusive, line)
+}
+// a range from bounds that have already been evaluated
+pub fn range_values(
+    start: &Value,
+    end: &Value,
+    step: Option<&Value>,
+    inclusive: bool,
+    scope_chain: &ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let start = range_bound(start, "start", scope_chain, line)?;
+    let end = range_bound(end, "end", scope_chain, line)?;
+    let step = match step {
+        Some(step) => range_bound(step, "step", scope_chain, line)?,
+        None => 1,
+    };
+    make_range(start, end, step, inclusive, line)
+}
+fn range_bound(
+    value: &Value,
+    what: &str,
+    scope_chain: &ScopeChain,
+    line: usize,
+) -> Result<i64, Box<RuntimeError>> {
+    match value {
+        Value::Int(n) => Ok(*n),
+        v => Err(Box::new(RuntimeError::new(
+            format!(
+                "The {} of a range must be an int, got {}",
+                what,
+                v.pretty_type(scope_chain, line)
+            ),
+            line,
+        ))),
+    }
+}
+fn make_range(
+    start: i64,
+    end: i64,
+    step: i64,
+    inclusive: bool,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if step == 0 {
+        return Err(Box::new(RuntimeError::new(
+            "The step of a range can't be 0".into(),
+            line,
+        )));
+    }
+    Ok(Rc::new(Value::Range(Range {
+        start,
+        end,
+        step,
+        inclusive,
+    })))
+}
+// the values a for loop goes over, ranges are never turned into an array
+pub fn iter_values(
+    value: &Rc<Value>,
+    scope_chain: &ScopeChain,
+    line: usize,
+) -> Result<Box<dyn Iterator<Item = Rc<Value>>>, Box<RuntimeError>> {
+    match value.as_ref() {
+        Value::Range(range) => Ok(Box::new(range.iter().map(|n| Rc::new(Value::Int(n))))),
+        // only arrays, an object's fields aren't in any order that's worth looping over
+        Value::Object(obj) if is_plain_array(obj) => {
+            let values = (0..obj.array_len())
+                .map(|i| obj.get(ObjectKey::Number(i as f64), line))
+                .collect::<Result<Vec<Rc<Value>>, Box<RuntimeError>>>()?;
+            Ok(Box::new(values.into_iter()))
+        }
+        Value::String(s) => Ok(Box::new(
+            s.chars()
+                .collect::<Vec<char>>()
+                .into_iter()
+                .map(|c| Rc::new(Value::Char(c))),
+        )),
+        v => Err(Box::new(RuntimeError::new(
+            format!(
+                "Cannot loop over a value of type {}",
+                v.pretty_type(scope_chain, line)
+            ),
+            line,
+        ))),
+    }
+}
+pub fn eval_for(
+    for_lit: &ForLiteral,
+    scope_chain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let iter = for_lit.iter.get_value(scope_chain)?.unpack_and_transform(
+        scope_chain,
+        line,
+        &for_lit.iter,
+    )?;
+    for value in iter_values(&iter, scope_chain, line)? {
+        scope_chain.add_scope().to_runtime_error()?;
+        scope_chain
+            .add_variable(&for_lit.var, false, line)
+            .to_runtime_error()?;
+        scope_chain
+            .set_variable(&for_lit.var, value, line)
+            .to_runtime_error()?;
+        for ast in for_lit.body.iter() {
+            ast.get_value(scope_chain)?;
+            match scope_chain.get_return_register() {
+                ReturnType::None => (),
+                _ => break,
+            }
+        }
+        scope_chain.pop_scope().to_runtime_error()?;
+        match scope_chain.get_return_register() {
+            ReturnType::None => (),
+            ReturnType::Break(target) if target.is_none() || target == for_lit.label => {
+                scope_chain.eat_return_register();
+                break;
+            }
+            ReturnType::Continue(target) if target.is_none() || target == for_lit.label => {
+                scope_chain.eat_return_register();
+            }
+            // a return or a labeled break or continue for an outer loop, leave it in the
+            //  register
+            _ => break,
+        }
+    }
+    Ok(Rc::new(Value::Undefined))
+}
+// value[key] once both sides are evaluated, objects get an ObjectAccess so that the
+//  field can be assigned to, which is also why the left side of an = or += (is_target)
+//  skips __index
+pub fn index_value(
+    left_val: Rc<Value>,
+    val: &Rc<Value>,
+    is_target: bool,
+    scope_chain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if !is_target {
+        if let Some(value) = call_overload("__index", &left_val, Some(val), scope_chain, line)? {
+            return Ok(value);
+        }
+    }
+    if let Value::Range(range) = val.as_ref() {
+        return slice_value(&left_val, range, scope_chain, line);
+    }
+    if let Value::String(s) = left_val.as_ref() {
+        return index_string(s, val, scope_chain, line);
+    }
+    let key = value_to_key(val, scope_chain, line)?;
+    let key = resolve_negative_key(&left_val, key, line)?;
+    Ok(Rc::new(Value::ObjectAccess(left_val, key)))
+}
+// arr[range] and s[range], the range has to fit inside of the array or string
+pub fn slice_value(
+    value: &Rc<Value>,
+    range: &Range,
+    scope_chain: &ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let check = |len: usize| -> Result<(), Box<RuntimeError>> {
+        if range.is_empty() {
+            return Ok(());
+        }
+        for i in [range.get(0), range.get(range.len() - 1)] {
+            if i < 0 || i as usize >= len {
+                return Err(Box::new(RuntimeError::new(
+                    format!("Index {} is out of range for length {}", i, len),
+                    line,
+                )));
+            }
+        }
+        Ok(())
+    };
+    match value.as_ref() {
+        Value::Object(obj) if obj.struct_def.is_none() => {
+            check(obj.array_len())?;
+            let mut ret = Object::new();
+            for (i, n) in range.iter().enumerate() {
+                let v = obj.get(ObjectKey::Number(n as f64), line)?;
+                ret.set(ObjectKey::Number(i as f64), Rc::new(v.as_ref().clone()));
+            }
+            Ok(Rc::new(Value::Object(ret)))
+        }
+        Value::String(s) => {
+            let chars = s.chars().collect::<Vec<char>>();
+            check(chars.len())?;
+            Ok(Rc::new(Value::String(
+                range.iter().map(|n| chars[n as usize]).collect(),
+            )))
+        }
+        v => Err(Box::new(RuntimeError::new(
+            format!(
+                "Cannot slice a value of type {}",
+                v.pretty_type(scope_chain, line)
+            ),
+            line,
+        ))),
+    }
+}
+// the key that obj[value] looks up
+pub fn value_to_key(
+    value: &Value,
+    scope_chain: &ScopeChain,
+    line: usize,
+) -> Result<ObjectKey, Box<RuntimeError>> {
+    match value {
+        Value::Number(n) => Ok(ObjectKey::Number(*n)),
+        Value::Int(n) => Ok(ObjectKey::Number(*n as f64)),
+        Value::String(s) => Ok(ObjectKey::String(s.clone())),
+        Value::Char(c) => Ok(ObjectKey::String(c.to_string())),
+        v => Err(Box::new(RuntimeError::new(
+            format!(
+                "Cannot use type {} as an object key, can only use char, string, or number types",
+                v.pretty_type(scope_chain, line)
+            ),
+            line,
+        ))),
+    }
+}
+// an index from the end like -1 becomes the index it points to, positive indexes are left as
+//  they are
+fn resolve_index(index: i64, len: usize, line: usize) -> Result<usize, Box<RuntimeError>> {
+    let resolved = if index < 0 { index + len as i64 } else { index };
+    if resolved < 0 || resolved as usize > len {
+        return Err(Box::new(RuntimeError::new(
+            format!("Index {} is out of range for length {}", index, len),
+            line,
+        )));
+    }
+    Ok(resolved as usize)
+}
+// arr[-1] counts from the end of the array, unless the object really has a key -1
+pub fn resolve_negative_key(
+    value: &Value,
+    key: ObjectKey,
+    line: usize,
+) -> Result<ObjectKey, Box<RuntimeError>> {
+    match (value, &key) {
+        (Value::Object(obj), ObjectKey::Number(n))
+            if *n < 0.0 && !obj.fields.iter().any(|(k, _)| k == &key) =>
+        {
+            let len = obj.array_len();
+            if n.fract() != 0.0 || *n + (len as f64) < 0.0 {
+                return Err(Box::new(RuntimeError::new(
+                    format!("Index {} is out of range for length {}", n, len),
+                    line,
+                )));
+            }
+            Ok(ObjectKey::Number(*n + len as f64))
+        }
+        _ => Ok(key),
+    }
+}
+// s[i] gives back the char at i
+pub fn index_string(
+    s: &str,
+    index: &Value,
+    scope_chain: &ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let index = match index {
+        Value::Int(i) => *i,
+        v => {
+            return Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot index a string with type {}, it has to be an int",
+                    v.pretty_type(scope_chain, line)
+                ),
+                line,
+            )))
+        }
+    };
+    let chars = s.chars().collect::<Vec<char>>();
+    let i = resolve_index(index, chars.len(), line)?;
+    match chars.get(i) {
+        Some(c) => Ok(Rc::new(Value::Char(*c))),
+        None => Err(Box::new(RuntimeError::new(
+            format!("Index {} is out of range for length {}", index, chars.len()),
+            line,
+        ))),
+    }
+}
+// value[start:end], a left out start is 0 and a left out end is the length
+pub fn eval_slice(
+    value: &AST,
+    start: &Option<Box<AST>>,
+    end: &Option<Box<AST>>,
+    scope_chain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let value = value
+        .get_value(scope_chain)?
+        .unpack_and_transform(scope_chain, line, value)?;
+    let len = match value.as_ref() {
+        Value::String(s) => s.chars().count(),
+        Value::Object(obj) if obj.struct_def.is_none() => obj.array_len(),
+        v => {
+            return Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot slice a value of type {}",
+                    v.pretty_type(scope_chain, line)
+                ),
+                line,
+            )))
+        }
+    };
+    let mut bound = |ast: &Option<Box<AST>>, default: usize| -> Result<usize, Box<RuntimeError>> {
+        let ast = match ast {
+            Some(ast) => ast,
+            None => return Ok(default),
+        };
+        let index =
+            ast.get_value(scope_chain)?
+                .unpack_and_transform(scope_chain, ast.get_line(), ast)?;
+        match index.as_ref() {
+            Value::Int(i) => resolve_index(*i, len, line),
+            v => Err(Box::new(RuntimeError::new(
+                format!(
+                    "The ends of a slice must be ints, got {}",
+                    v.pretty_type(scope_chain, line)
+                ),
+                line,
+            ))),
+        }
+    };
+    let start = bound(start, 0)?;
+    let end = bound(end, len)?;
+    let range = Range {
+        start: start as i64,
+        // a slice that ends before it starts is empty
+        end: end.max(start) as i64,
+        step: 1,
+        inclusive: false,
+    };
+    slice_value(&value, &range, scope_chain, line)
+}
+pub fn eval_quote(
+    block: &Block,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let mut block = block.clone();
+    // variables declared inside the quote get renamed so they can't clash with the code
+    //  the quote is expanded into, the name isn't something the lexer could ever make
+    let mut declared = vec![];
+    for ast in block.iter_mut() {
+        collect_declarations(ast, &mut declared);
+    }
+    if !declared.is_empty() {
+        let id = QUOTE_COUNT.fetch_add(1, AtomicOrdering::Relaxed);
+        for ast in block.iter_mut() {
+            rename_declarations(ast, &declared, id);
+        }
+    }
+    for ast in block.iter_mut() {
+        splice_unquotes(ast, scope_chain)?;
+    }
+    Ok(Rc::new(Value::Quote(block)))
+}
+fn collect_declarations(ast: &mut AST, declared: &mut Vec<String>) {
+    match &ast.t {
+        ASTType::VariableDeclaration(name, _) => declared.push(name.clone()),
+        ASTType::FunctionLiteral(func) => {
+            declared.extend(func.params.iter().map(|p| p.name.clone()))
+        }
+        // unquoted code belongs to whoever called the macro, nested quotes rename their own
+        ASTType::Unquote(_) | ASTType::Quote(_) => return,
+        _ => (),
+    }
+    for child in ast.children_mut() {
+        collect_declarations(child, declared);
+    }
+}
+fn rename_declarations(ast: &mut AST, declared: &[String], id: usize) {
+    fn rename(name: &mut String, declared: &[String], id: usize) {
+        if declared.contains(name) {
+            *name = format!("{}#{}", name, id);
+        }
+    }
+    match &mut ast.t {
+        ASTType::VariableDeclaration(name, _) | ASTType::VariableAccess(name, _) => {
+            rename(name, declared, id)
+        }
+        ASTType::FunctionLiteral(func) => {
+            for param in func.params.iter_mut() {
+                rename(&mut param.name, declared, id);
+            }
+        }
+        ASTType::Unquote(_) | ASTType::Quote(_) => return,
+        _ => (),
+    }
+    for child in ast.children_mut() {
+        rename_declarations(child, declared, id);
+    }
+}
+fn splice_unquotes(
+    ast: &mut Box<AST>,
+    scope_chain: &mut ScopeChain,
+) -> Result<(), Box<RuntimeError>> {
+    match &ast.t {
+        ASTType::Unquote(expr) => {
+            let value = expr.get_value(scope_chain)?.unpack_and_transform(
+                scope_chain,
+                expr.get_line(),
+                expr,
+            )?;
+            *ast = value_to_ast(&value, ast.token.clone(), scope_chain)?;
+        }
+        ASTType::Quote(_) => (),
+        _ => {
+            for child in ast.children_mut() {
+                splice_unquotes(child, scope_chain)?;
+            }
+        }
+    }
+    Ok(())
+}
+// turns a value back into code, for unquote, the result of a macro and comptime
+pub fn value_to_ast(
+    value: &Value,
+    token: Token,
+    scope_chain: &ScopeChain,
+) -> Result<Box<AST>, Box<RuntimeError>> {
+    value_to_ast_inner(value, token, scope_chain, &mut vec![])
+}
+fn value_to_ast_inner(
+    value: &Value,
+    token: Token,
+    scope_chain: &ScopeChain,
+    parents: &mut Vec<*const Object>,
+) -> Result<Box<AST>, Box<RuntimeError>> {
+    let line = token.line + 1;
+    let t = match value {
+        Value::Quote(block) if block.len() == 1 => return Ok(block[0].clone()),
+        Value::Quote(block) => ASTType::Splice(block.clone()),
+        Value::Number(n) => ASTType::NumberLiteral(*n),
+        Value::Int(n) => ASTType::IntLiteral(*n),
+        Value::String(s) => ASTType::StringLiteral(s.clone()),
+        Value::Char(c) => ASTType::CharacterLiteral(*c),
+        Value::Boolean(b) => ASTType::BooleanLiteral(*b),
+        // nothing to put in, this just evaluates to undefined
+        Value::Undefined => ASTType::Splice(vec![]),
+        Value::Object(obj) => {
+            let ptr = obj as *const Object;
+            if parents.contains(&ptr) {
+                return Err(Box::new(RuntimeError::new(
+                    "Cannot turn an object that contains itself into code".into(),
+                    line,
+                )));
+            }
+            parents.push(ptr);
+            let mut fields = vec![];
+            for (key, field) in obj.fields.iter() {
+                let field = value_to_ast_inner(field, token.clone(), scope_chain, parents)?;
+                fields.push((FieldKey::Static(key.clone()), field));
+            }
+            parents.pop();
+            ASTType::ObjectLiteral(fields)
+        }
+        v => {
+            return Err(Box::new(RuntimeError::new(
+                format!(
+                    "Cannot turn type {} into code",
+                    v.pretty_type(scope_chain, line)
+                ),
+                line,
+            )))
+        }
+    };
+    Ok(Box::new(AST { t, token }))
+}
+// runs the code from an expanded macro without a new scope, giving back the last value
+pub fn eval_splice(
+    block: &Block,
+    scope_chain: &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let mut ret = Rc::new(Value::Undefined);
+    for ast in block.iter() {
+        ret = ast.get_value(scope_chain)?;
+        if scope_chain.get_return_register() != ReturnType::None {
+            break;
+        }
+    }
+    Ok(ret)
+}
+// waits on a future, anything else is already done so it just gives it back
+pub fn eval_await(v: &AST, scope_chain: &mut ScopeChain) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let value = v
+        .get_value(scope_chain)?
+        .unpack_and_transform(scope_chain, v.get_line(), v)?;
+    match value.as_ref() {
+        Value::Future(future) => await_future(future, scope_chain),
+        _ => Ok(value),
+    }
+}
+// calls a value that has already been evaluated with the arguments of a call
+pub fn call_value(
+    func: Rc<Value>,
+    params: &Block,
+    call: &AST,
+    scope_chain: &mut ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    match func.as_ref() {
+        Value::Function(func) => func.call(scope_chain, params, line),
+        Value::EnumConstructor(variant) => construct_enum(variant, params, scope_chain, line),
+        Value::StructConstructor(def) => construct_struct(def, params, scope_chain, line),
+        Value::BuiltinFunction(f, arg_len) => {
+            if arg_len != &params.len() {
+                return Err(Box::new(RuntimeError::new(
+                    format!("Expected {} arguments, got {}", arg_len, params.len()),
+                    line,
+                )));
+            }
+            // convert params to values
+            let mut actual_params = vec![];
+            for ast in params.iter() {
+                actual_params.push(ast.get_value(scope_chain).unpack_and_transform(
+                    scope_chain,
+                    ast.get_line(),
+                    ast,
+                )?);
+            }
+            Ok(f(actual_params, call, scope_chain, call.get_line())?)
+        }
+        _ => Err(Box::new(RuntimeError::new(
+            "Cannot call a non-function".into(),
+            line,
+        ))),
+    }
+}
 pub fn eval_return(
     v: &Box<AST>,
     scope_chain: &mut ScopeChain,
 ) -> Result<Rc<Value>, Box<RuntimeError>> {
+    if let ASTType::FunctionCall(callee, args) = &v.t {
+        let func =
+            callee
+                .get_value(scope_chain)?
+                .unpack_and_transform(scope_chain, v.get_line(), v)?;
+        match func.as_ref() {
+            Value::Function(f) if !f.is_async => {
+                let args = args
+                    .iter()
+                    .map(|ast| {
+                        ast.get_value(scope_chain).unpack_and_transform(
+                            scope_chain,
+                            ast.get_line(),
+                            ast,
+                        )
+                    })
+                    .collect::<Result<Vec<Rc<Value>>, Box<RuntimeError>>>()?;
+                scope_chain
+                    .set_return_register(ReturnType::TailCall(func, args))
+                    .to_runtime_error()?;
+                return Ok(Rc::new(Value::Undefined));
+            }
+            _ => {
+                let v_val = call_value(func, args, v, scope_chain, v.get_line())?
+                    .unpack_and_transform(scope_chain, v.get_line(), v)?;
+                scope_chain
+                    .set_return_register(ReturnType::Return(v_val))
+                    .to_runtime_error()?;
+                return Ok(Rc::new(Value::Undefined));
+            }
+        }
+    }
     let v_val = v
         .get_value(scope_chain)?
         .unpack_and_transform(scope_chain, v.get_line(), v)?;
@@ -720,57 +2211,64 @@ pub fn eval_return(
         .to_runtime_error()?;
     Ok(Rc::new(Value::Undefined))
 }
-pub fn eval_import(filename: String) -> Result<Rc<Value>, Box<RuntimeError>> {
-    let contents = match fs::read_to_string(filename.clone()) {
+pub fn eval_import(
+    filename: String,
+    scope_chain: &ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
+    let modules = scope_chain.get_modules();
+    let key = match modules.resolve(&filename) {
         Ok(v) => v,
-        Err(e) => {
-            return Err(Box::new(RuntimeError::new(
-                format!("Cannot read file {}: {}", filename, e),
-                0,
-            )))
-        }
+        Err(msg) => return Err(Box::new(RuntimeError::new(msg, line))),
     };
+    // every module only runs once per interpreter, later imports get the same exports
+    if let Some(exports) = modules.get_cached(&key) {
+        return Ok(exports);
+    }
+    if let Err(msg) = modules.begin_loading(&key) {
+        return Err(Box::new(RuntimeError::new(msg, line)));
+    }
+    let ret = match modules.load(&key) {
+        Ok(contents) => run_module(&filename, contents, scope_chain.for_module(), line),
+        Err(msg) => Err(Box::new(RuntimeError::new(msg, line))),
+    };
+    modules.finish_loading(&key, ret.as_ref().ok().cloned());
+    ret
+}
+fn run_module(
+    filename: &str,
+    contents: String,
+    mut scope_chain: ScopeChain,
+    line: usize,
+) -> Result<Rc<Value>, Box<RuntimeError>> {
     let mut parser = crate::parser::Parser::new(contents);
-    let mut scope_chain: ScopeChain = ScopeChain::new();
 
     match crate::builtins::create_builtins(&mut scope_chain) {
         Ok(_) => {}
         Err(e) => {
             return Err(Box::new(RuntimeError::new(
                 format!("Error creating builtins: {}", e),
-                0,
+                line,
             )));
         }
     };
-    let ast = match parser.parse(true) {
+    let mut ast = match parser.parse(true) {
         Ok(ast) => ast,
         Err(e) => {
             return Err(Box::new(RuntimeError::new(
                 format!("Error parsing file {}: {}", filename, e),
-                0,
+                line,
             )));
         }
     };
+    crate::resolver::resolve(&mut ast, &scope_chain);
 
     // for (_, stmt) in ast.iter().enumerate() {
     //     println!("{}", stmt.pretty_print());
     // }
     //
-    let mut ret = Rc::new(Value::Undefined);
-    for (_, stmt) in ast.iter().enumerate() {
-        match stmt.interpret(&mut scope_chain) {
-            Ok(ReturnType::None) => {}
-            Ok(ReturnType::Return(v)) => {
-                ret = v;
-                break;
-            }
-            Ok(_) => {
-                break;
-            }
-            Err(e) => {
-                return Err(e);
-            }
-        };
+    match crate::vm::interpret(&ast, &mut scope_chain)? {
+        ReturnType::Return(v) => Ok(v),
+        _ => Ok(Rc::new(Value::Undefined)),
     }
-    return Ok(ret);
 }
@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 use crate::error::ScopeError;
+use crate::event_loop::EventLoop;
+use crate::modules::ModuleRegistry;
 use crate::parser::Value;
 use crate::parser::Variable;
 
@@ -31,25 +33,37 @@ impl Scope {
     fn set_variable(&mut self, name: &String, value: Rc<Value>) -> Result<bool, Box<dyn Error>> {
         for var in self.variables.iter_mut() {
             if var.name == *name {
-                if var.is_const && *var.value != Value::Undefined {
-                    return Ok(false);
-                }
-
-                // if the variable has been defined, the copy the given value into the variable
-                // this preserves the reference keeping behavior of += and other mutate operators
-                if *var.value != Value::Undefined {
-                    let val_ptr = Rc::<Value>::as_ptr(&var.value) as *mut Value;
-                    unsafe {
-                        *val_ptr = value.as_ref().clone();
-                    }
-                } else {
-                    var.value = value.clone();
-                }
-                return Ok(true);
+                return Ok(Scope::assign(var, value));
             }
         }
         Err(format!("Variable {} not found", name).into())
     }
+    // gives back false if the variable is a const that already has a value
+    fn assign(var: &mut Variable, value: Rc<Value>) -> bool {
+        if var.is_const && *var.value != Value::Undefined {
+            return false;
+        }
+
+        // const variables hold a frozen copy so that nothing inside them can change
+        if var.is_const {
+            var.value = Value::frozen_copy(&value);
+            return true;
+        }
+
+        // if the variable has been defined, the copy the given value into the variable
+        // this preserves the reference keeping behavior of += and other mutate operators
+        // frozen objects and their fields are swapped out instead so that they aren't
+        //  overwritten
+        if *var.value != Value::Undefined && !Value::is_held_frozen(&var.value) {
+            let val_ptr = Rc::<Value>::as_ptr(&var.value) as *mut Value;
+            unsafe {
+                *val_ptr = value.as_ref().clone();
+            }
+        } else {
+            var.value = value.clone();
+        }
+        true
+    }
     fn is_const(&self, name: &String) -> Result<bool, Box<dyn Error>> {
         for var in self.variables.iter() {
             if var.name == *name {
@@ -71,7 +85,7 @@ impl Scope {
         name: &String,
         is_const: bool,
         line: usize,
-    ) -> Result<(), ScopeError> {
+    ) -> Result<usize, ScopeError> {
         for var in self.variables.iter() {
             if var.name == *name {
                 return Err(ScopeError::new(
@@ -85,7 +99,7 @@ impl Scope {
             value: Rc::new(Value::Undefined),
             is_const,
         });
-        Ok(())
+        Ok(self.variables.len() - 1)
     }
     fn has_variable(&self, name: &String) -> bool {
         for var in self.variables.iter() {
@@ -98,24 +112,81 @@ impl Scope {
 }
 #[derive(Debug, Clone, PartialEq)]
 pub enum ReturnType {
-    Continue,
-    Break,
+    // the label of the loop to continue or break, or None for the innermost loop
+    Continue(Option<String>),
+    Break(Option<String>),
     Return(Rc<Value>),
+    // a `return f(x)`, the function and its arguments are run by the caller once the
+    //  current frame is popped
+    TailCall(Rc<Value>, Vec<Rc<Value>>),
     None,
 }
+// where the resolver worked out a variable lives, so it can be found without searching
+//  every scope by name. the name is still checked before the slot is used, anything the
+//  resolver couldn't see coming just falls back to the search
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Address {
+    #[default]
+    Unresolved,
+    // `depth` scopes out from the innermost one, never past the scope its function starts in
+    Local {
+        depth: usize,
+        slot: usize,
+    },
+    Global {
+        slot: usize,
+    },
+}
+// what runs the program, walking the ast or compiling it to bytecode for the vm
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Engine {
+    Tree,
+    Vm,
+}
 #[derive(Debug, Clone, PartialEq)]
 pub struct ScopeChain {
     scopes: Vec<Scope>,
     return_register: ReturnType,
+    modules: ModuleRegistry,
+    event_loop: EventLoop,
+    // functions and imported modules run on the same engine as the code that started them
+    engine: Engine,
 }
 impl ScopeChain {
     pub fn new() -> ScopeChain {
+        ScopeChain::with_modules(ModuleRegistry::new())
+    }
+    // a fresh global scope that shares its module cache with another interpreter
+    pub fn with_modules(modules: ModuleRegistry) -> ScopeChain {
         // initialize with a global scope
         ScopeChain {
             scopes: vec![Scope::global()],
             return_register: ReturnType::None,
+            modules,
+            event_loop: EventLoop::new(),
+            engine: Engine::Tree,
         }
     }
+    // a fresh global scope for an imported module, it shares the module cache and the event
+    //  loop with this one
+    pub fn for_module(&self) -> ScopeChain {
+        let mut ret = ScopeChain::with_modules(self.get_modules());
+        ret.event_loop = self.get_event_loop();
+        ret.engine = self.engine;
+        ret
+    }
+    pub fn get_modules(&self) -> ModuleRegistry {
+        self.modules.clone()
+    }
+    pub fn get_event_loop(&self) -> EventLoop {
+        self.event_loop.clone()
+    }
+    pub fn get_engine(&self) -> Engine {
+        self.engine
+    }
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+    }
     pub fn get_closure(&self) -> ScopeChain {
         let mut scopes = vec![];
         for scope in self.scopes.iter().rev() {
@@ -128,6 +199,9 @@ impl ScopeChain {
         ScopeChain {
             scopes,
             return_register: ReturnType::None,
+            modules: self.modules.clone(),
+            event_loop: self.event_loop.clone(),
+            engine: self.engine,
         }
     }
     pub fn add_fn_scope(&mut self, closure: &ScopeChain) {
@@ -154,6 +228,20 @@ impl ScopeChain {
         self.scopes.pop();
         Ok(())
     }
+    // trades global scopes with another chain, a task runs on a chain of its own that
+    //  borrows the global scope of whatever is running the event loop
+    pub fn swap_globals(&mut self, other: &mut ScopeChain) {
+        std::mem::swap(&mut self.scopes[0], &mut other.scopes[0]);
+    }
+    pub fn depth(&self) -> usize {
+        self.scopes.len()
+    }
+    // drops everything a call that errored left behind, used when the program keeps going
+    //  after the error
+    pub fn unwind_to(&mut self, depth: usize) {
+        self.scopes.truncate(depth);
+        self.return_register = ReturnType::None;
+    }
     pub fn pop_fn_scope(&mut self, line: usize) -> Result<(), ScopeError> {
         if self.scopes.len() == 1 {
             return Err(ScopeError::new("Cannot pop global scope".into(), line));
@@ -203,6 +291,74 @@ impl ScopeChain {
             line,
         ))
     }
+    // the scope and slot of the variable at address, if it really is the one called name
+    fn find_at(&self, name: &String, address: Address) -> Option<(usize, usize)> {
+        let (i, slot) = match address {
+            Address::Unresolved => return None,
+            Address::Global { slot } => (0, slot),
+            Address::Local { depth, slot } => {
+                let i = self.scopes.len().checked_sub(depth + 1)?;
+                // searching stops at the scope a function starts in, so this has to as well
+                if self.scopes[i + 1..].iter().any(|scope| scope.is_fn) {
+                    return None;
+                }
+                (i, slot)
+            }
+        };
+        match self.scopes[i].variables.get(slot) {
+            Some(var) if var.name == *name => Some((i, slot)),
+            _ => None,
+        }
+    }
+    pub fn get_variable_at(
+        &self,
+        name: &String,
+        address: Address,
+        line: usize,
+    ) -> Result<Rc<Value>, ScopeError> {
+        match self.find_at(name, address) {
+            Some((i, slot)) => Ok(self.scopes[i].variables[slot].value.clone()),
+            None => self.get_variable(name, line),
+        }
+    }
+    pub fn set_variable_at(
+        &mut self,
+        name: &String,
+        address: Address,
+        value: Rc<Value>,
+        line: usize,
+    ) -> Result<(), ScopeError> {
+        let (i, slot) = match self.find_at(name, address) {
+            Some(found) => found,
+            None => return self.set_variable(name, value, line),
+        };
+        if !Scope::assign(&mut self.scopes[i].variables[slot], value) {
+            return Err(ScopeError::new(
+                format!("Cannot change const variable {}", name),
+                line,
+            ));
+        }
+        Ok(())
+    }
+    pub fn is_const_at(
+        &self,
+        name: &String,
+        address: Address,
+        line: usize,
+    ) -> Result<bool, ScopeError> {
+        match self.find_at(name, address) {
+            Some((i, slot)) => Ok(self.scopes[i].variables[slot].is_const),
+            None => self.is_const(name, line),
+        }
+    }
+    // the names in the global scope so far, in the order they were added
+    pub fn global_names(&self) -> Vec<String> {
+        self.scopes[0]
+            .variables
+            .iter()
+            .map(|var| var.name.clone())
+            .collect()
+    }
     pub fn is_const(&self, name: &String, line: usize) -> Result<bool, ScopeError> {
         let mut i = self.scopes.len() - 1;
         loop {
@@ -224,17 +380,17 @@ impl ScopeChain {
             line,
         ))
     }
+    // gives back the slot the variable went in, in the innermost scope
     pub fn add_variable(
         &mut self,
         name: &String,
         is_const: bool,
         line: usize,
-    ) -> Result<(), ScopeError> {
+    ) -> Result<usize, ScopeError> {
         match self.scopes.last_mut() {
-            Some(scope) => scope.add_variable(&name, is_const, line)?,
-            None => return Err(ScopeError::new("No scope to add variable to".into(), 0)),
+            Some(scope) => scope.add_variable(&name, is_const, line),
+            None => Err(ScopeError::new("No scope to add variable to".into(), 0)),
         }
-        Ok(())
     }
     pub fn set_variable(
         &mut self,
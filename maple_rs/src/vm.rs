@@ -0,0 +1,1551 @@
+// compiles blocks to flat bytecode and runs them on a stack, instead of walking the ast.
+//  anything the compiler doesn't lower itself is kept as an Eval of its ast, so the vm
+//  always does exactly what the tree walker would, errors included
+use std::cell::OnceCell;
+use std::cmp::Ordering;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::ast::{
+    ASTType, Block, ConvertScopeErrorResult, FieldKey, ForLiteral, IfLiteral, MatchLiteral,
+    WhileLiteral, AST,
+};
+use crate::error::RuntimeError;
+use crate::event_loop::{await_future, poll, stuck_error, Future};
+use crate::parser::{Object, ObjectKey, Unpack, Value};
+use crate::runtime::*;
+use crate::scopechain::{Address, Engine, ReturnType, ScopeChain};
+
+type BinaryFn = fn(
+    &Rc<Value>,
+    &Rc<Value>,
+    usize,
+    usize,
+    &mut ScopeChain,
+) -> Result<Rc<Value>, Box<RuntimeError>>;
+type ValueIter = Box<dyn Iterator<Item = Rc<Value>>>;
+
+enum Op {
+    // literals are made again every time they run, since assignments write into values
+    //  in place
+    Int(i64),
+    Number(f64),
+    Bool(bool),
+    Char(char),
+    Str(String),
+    // the value of a variable, unpacked the way the code using it would
+    LoadVar {
+        name: String,
+        address: Address,
+        line: usize,
+        site: usize,
+    },
+    // the variable itself, for the left side of an assignment
+    Var(String, Address),
+    // declarations and break and continue don't add their own ast to errors, so the
+    //  trace starts at the node around them
+    Declare {
+        name: String,
+        is_const: bool,
+        line: usize,
+        site: Option<usize>,
+    },
+    Unpack {
+        line: usize,
+        site: usize,
+    },
+    // functions unpack their arguments with the line of the call, everything else with the
+    //  line of the argument
+    UnpackArg {
+        index: usize,
+        call_line: usize,
+        arg_line: usize,
+        site: usize,
+    },
+    Field(String),
+    Index {
+        line: usize,
+        site: usize,
+        is_target: bool,
+    },
+    Binary {
+        f: BinaryFn,
+        left_line: usize,
+        right_line: usize,
+        site: usize,
+    },
+    Neg {
+        line: usize,
+        site: usize,
+    },
+    Not {
+        line: usize,
+        site: usize,
+    },
+    // && and ||, the left side jumps past the right side when it decides the result
+    LogicLeft {
+        op: &'static str,
+        line: usize,
+        site: usize,
+        end: usize,
+    },
+    LogicRight {
+        op: &'static str,
+        left_line: usize,
+        right_line: usize,
+        site: usize,
+    },
+    Assign {
+        target: usize,
+        site: usize,
+    },
+    PlsEqTarget {
+        target: usize,
+        site: usize,
+    },
+    PlsEq {
+        target: usize,
+        right: usize,
+        site: usize,
+    },
+    Array(usize),
+    Function(usize),
+    // looks at the callee before the arguments run. constructors and things that can't be
+    //  called go through call_value with the argument asts and jump to end, unless an
+    //  argument awaits, then the arguments run here and Call builds the value
+    PrepCall {
+        call: usize,
+        argc: usize,
+        line: usize,
+        site: usize,
+        end: usize,
+        eager: bool,
+    },
+    Call {
+        argc: usize,
+        call: usize,
+        line: usize,
+        site: usize,
+    },
+    // return f(x), only plain functions become a tail call, anything else is called right
+    //  away and returned from ret
+    PrepTail {
+        call: usize,
+        line: usize,
+        site: usize,
+        ret: usize,
+    },
+    TailCall {
+        argc: usize,
+        site: usize,
+        ctx: usize,
+    },
+    Return {
+        site: usize,
+        ctx: usize,
+    },
+    // break and continue
+    Signal {
+        signal: ReturnType,
+        site: Option<usize>,
+        ctx: usize,
+    },
+    // sends whatever is in the return register to the loop or exit it's meant for
+    Settle(usize),
+    // jumps to target when the condition is false
+    Branch {
+        target: usize,
+        msg: &'static str,
+        line: usize,
+        site: usize,
+    },
+    Jump(usize),
+    Pop,
+    PushScope,
+    PopScope,
+    Iter {
+        line: usize,
+        site: usize,
+    },
+    Next {
+        var: String,
+        line: usize,
+        site: usize,
+        end: usize,
+    },
+    PopIter,
+    // the object literal at the node, from its computed keys and values on the stack
+    Object(usize),
+    Range {
+        has_step: bool,
+        inclusive: bool,
+        line: usize,
+        site: usize,
+    },
+    // waits on the value if it's a future. a task stops here and runs the op again once
+    //  the future resolves
+    Await {
+        site: usize,
+    },
+    // tests the value being matched against the pattern at the node, an arm that fits
+    //  gets a scope with what the pattern binds and anything else jumps to next
+    Arm {
+        pattern: usize,
+        line: usize,
+        site: usize,
+        next: usize,
+    },
+    // swaps the value being matched for what the arm gave back, block arms give back
+    //  undefined
+    EndArm {
+        is_block: bool,
+    },
+    NoArm {
+        line: usize,
+        site: usize,
+    },
+    Eval(usize),
+    // the end of a top level statement, does what AST::interpret does with the register
+    TopLevel(usize),
+}
+
+// the ast that an op came from and the node around it. an error adds every ast from its
+//  node out to the statement, the same ones get_value would have added on the way up
+struct Node {
+    ast: *const AST,
+    parent: Option<usize>,
+}
+
+// where a signal in the return register goes, loops catch their break and continue and
+//  anything else leaves through the root
+enum Exit {
+    Loop {
+        label: Option<String>,
+        cont: usize,
+        brk: usize,
+        // how many scopes and iterators are open around the loop body
+        scopes: usize,
+        iters: usize,
+        parent: usize,
+    },
+    Leave(usize),
+    // a block arm of a match stops at any signal and leaves it to the statement around the
+    //  match, the way eval_match does
+    Arm {
+        end: usize,
+        scopes: usize,
+    },
+}
+
+pub struct Chunk {
+    ops: Vec<Op>,
+    nodes: Vec<Node>,
+    exits: Vec<Exit>,
+    // the body a function chunk was compiled from, only kept since nodes point into it
+    _code: Block,
+}
+
+// the compiled body of a function, shared by every closure made from the same literal.
+//  a cloned ast gets an empty cache since macros can change the copy
+#[derive(Default)]
+pub struct ChunkCache(Rc<OnceCell<Chunk>>);
+
+impl ChunkCache {
+    pub fn share(&self) -> ChunkCache {
+        ChunkCache(self.0.clone())
+    }
+    // awaits is set for the body of an async fn, the only kind that can have one
+    pub fn get(&self, body: &Block, awaits: bool) -> &Chunk {
+        self.0.get_or_init(|| Chunk::function(body, awaits))
+    }
+}
+
+impl Clone for ChunkCache {
+    fn clone(&self) -> Self {
+        ChunkCache::default()
+    }
+}
+
+impl PartialEq for ChunkCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for ChunkCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self.0.get() {
+            Some(_) => "ChunkCache(compiled)",
+            None => "ChunkCache",
+        })
+    }
+}
+
+// runs a whole program or module on the engine the scope chain is set to, giving back
+//  what the first statement that returned returned
+pub fn interpret(
+    block: &Block,
+    scope_chain: &mut ScopeChain,
+) -> Result<ReturnType, Box<RuntimeError>> {
+    match scope_chain.get_engine() {
+        Engine::Vm => run(block, scope_chain),
+        Engine::Tree => {
+            for stmt in block.iter() {
+                match stmt.interpret(scope_chain)? {
+                    ReturnType::None => {}
+                    ret => return Ok(ret),
+                }
+            }
+            Ok(ReturnType::None)
+        }
+    }
+}
+
+// compiles the block and runs it on the vm, like calling interpret on every statement
+pub fn run(block: &Block, scope_chain: &mut ScopeChain) -> Result<ReturnType, Box<RuntimeError>> {
+    let mut compiler = Compiler::new(true);
+    for stmt in block.iter() {
+        let exit = compiler.exits.len();
+        compiler.exits.push(Exit::Leave(0));
+        compiler.ctx = exit;
+        compiler.stmt(stmt, None);
+        let node = compiler.node(stmt, None);
+        compiler.exits[exit] = Exit::Leave(compiler.ops.len());
+        compiler.emit(Op::TopLevel(node));
+    }
+    // the nodes point into block, which outlives the chunk
+    compiler.finish(vec![]).execute(scope_chain)
+}
+
+struct Compiler {
+    ops: Vec<Op>,
+    nodes: Vec<Node>,
+    exits: Vec<Exit>,
+    ctx: usize,
+    scopes: usize,
+    iters: usize,
+    // whether the code can have an await in it, nothing else needs to look for one
+    awaits: bool,
+}
+
+impl Compiler {
+    fn new(awaits: bool) -> Compiler {
+        Compiler {
+            ops: vec![],
+            nodes: vec![],
+            exits: vec![],
+            ctx: 0,
+            scopes: 0,
+            iters: 0,
+            awaits,
+        }
+    }
+    fn finish(self, code: Block) -> Chunk {
+        Chunk {
+            ops: self.ops,
+            nodes: self.nodes,
+            exits: self.exits,
+            _code: code,
+        }
+    }
+    fn emit(&mut self, op: Op) -> usize {
+        self.ops.push(op);
+        self.ops.len() - 1
+    }
+    fn here(&self) -> usize {
+        self.ops.len()
+    }
+    // points a jump that was emitted before its target was known at the current op
+    fn patch(&mut self, at: usize) {
+        let here = self.here();
+        match &mut self.ops[at] {
+            Op::Jump(target)
+            | Op::Branch { target, .. }
+            | Op::LogicLeft { end: target, .. }
+            | Op::PrepCall { end: target, .. }
+            | Op::PrepTail { ret: target, .. }
+            | Op::Next { end: target, .. }
+            | Op::Arm { next: target, .. } => *target = here,
+            _ => unreachable!("only jumps get patched"),
+        }
+    }
+    // whether running ast could get to an await. an await has to be lowered for a task to
+    //  stop at it, so anything holding one is compiled instead of kept as an Eval
+    fn has_await(&self, ast: &AST) -> bool {
+        self.awaits && contains_await(&mut ast.clone())
+    }
+    fn node(&mut self, ast: &AST, parent: Option<usize>) -> usize {
+        self.nodes.push(Node {
+            ast: ast as *const AST,
+            parent,
+        });
+        self.nodes.len() - 1
+    }
+    fn block(&mut self, block: &Block, parent: Option<usize>) {
+        for stmt in block.iter() {
+            self.stmt(stmt, parent);
+            self.emit(Op::Settle(self.ctx));
+        }
+    }
+    // the body of an if or a loop in a scope of its own
+    fn scoped_block(&mut self, block: &Block, parent: usize) {
+        self.emit(Op::PushScope);
+        self.scopes += 1;
+        self.block(block, Some(parent));
+        self.scopes -= 1;
+        self.emit(Op::PopScope);
+    }
+    fn stmt(&mut self, ast: &AST, parent: Option<usize>) {
+        match &ast.t {
+            ASTType::If(if_lit) => self.if_stmt(if_lit, ast, parent),
+            ASTType::While(while_lit) => self.while_stmt(while_lit, ast, parent),
+            ASTType::For(for_lit) => self.for_stmt(for_lit, ast, parent),
+            ASTType::Return(v) => self.return_stmt(v, ast, parent),
+            ASTType::Break(label) => {
+                self.emit(Op::Signal {
+                    signal: ReturnType::Break(label.clone()),
+                    site: parent,
+                    ctx: self.ctx,
+                });
+            }
+            ASTType::Continue(label) => {
+                self.emit(Op::Signal {
+                    signal: ReturnType::Continue(label.clone()),
+                    site: parent,
+                    ctx: self.ctx,
+                });
+            }
+            _ => {
+                self.expr(ast, parent);
+                self.emit(Op::Pop);
+            }
+        }
+    }
+    fn if_stmt(&mut self, if_lit: &IfLiteral, ast: &AST, parent: Option<usize>) {
+        let node = self.node(ast, parent);
+        let mut ends = vec![];
+        let conds = std::iter::once((&if_lit.cond, &if_lit.body))
+            .chain(if_lit.elseifs.iter().map(|(cond, body)| (cond, body)));
+        for (i, (cond, body)) in conds.enumerate() {
+            // the first condition is unpacked as the if itself, the elseifs as themselves
+            if i == 0 {
+                self.unpacked(cond, Some(node), ast.get_line(), ast, Some(node));
+            } else {
+                self.unpacked(cond, Some(node), cond.get_line(), cond, Some(node));
+            }
+            let site = self.node(cond, Some(node));
+            let branch = self.emit(Op::Branch {
+                target: 0,
+                msg: "If condition must be a boolean",
+                line: cond.get_line(),
+                site,
+            });
+            self.scoped_block(body, node);
+            ends.push(self.emit(Op::Jump(0)));
+            self.patch(branch);
+        }
+        if let Some(else_body) = &if_lit.else_body {
+            self.scoped_block(else_body, node);
+        }
+        for end in ends {
+            self.patch(end);
+        }
+    }
+    fn while_stmt(&mut self, while_lit: &WhileLiteral, ast: &AST, parent: Option<usize>) {
+        let node = self.node(ast, parent);
+        let outer = self.ctx;
+        let ctx = self.exits.len();
+        self.exits.push(Exit::Loop {
+            label: while_lit.label.clone(),
+            cont: 0,
+            brk: 0,
+            scopes: self.scopes,
+            iters: self.iters,
+            parent: outer,
+        });
+        // do while skips the condition the first time around
+        let skip_cond = while_lit.is_do_while.then(|| self.emit(Op::Jump(0)));
+        let cond_start = self.here();
+        let branch = while_lit.cond.as_ref().map(|cond| {
+            self.unpacked(cond, Some(node), cond.get_line(), cond, Some(node));
+            self.emit(Op::Branch {
+                target: 0,
+                msg: "While condition must be a boolean",
+                line: cond.get_line(),
+                site: node,
+            })
+        });
+        if let Some(skip_cond) = skip_cond {
+            self.patch(skip_cond);
+        }
+        self.ctx = ctx;
+        self.scoped_block(&while_lit.body, node);
+        self.emit(Op::Settle(ctx));
+        self.ctx = outer;
+        self.emit(Op::Jump(cond_start));
+        // the condition ended the loop rather than a break, so the else block runs
+        if let Some(branch) = branch {
+            self.patch(branch);
+        }
+        if let Some(else_body) = &while_lit.else_body {
+            self.scoped_block(else_body, node);
+        }
+        let end = self.here();
+        if let Exit::Loop { cont, brk, .. } = &mut self.exits[ctx] {
+            *cont = cond_start;
+            *brk = end;
+        }
+    }
+    fn for_stmt(&mut self, for_lit: &ForLiteral, ast: &AST, parent: Option<usize>) {
+        let node = self.node(ast, parent);
+        let line = ast.get_line();
+        self.unpacked(&for_lit.iter, Some(node), line, &for_lit.iter, Some(node));
+        self.emit(Op::Iter { line, site: node });
+        self.iters += 1;
+        let outer = self.ctx;
+        let ctx = self.exits.len();
+        self.exits.push(Exit::Loop {
+            label: for_lit.label.clone(),
+            cont: 0,
+            brk: 0,
+            scopes: self.scopes,
+            iters: self.iters,
+            parent: outer,
+        });
+        let next = self.emit(Op::Next {
+            var: for_lit.var.clone(),
+            line,
+            site: node,
+            end: 0,
+        });
+        // Next opens the scope that the variable lives in
+        self.scopes += 1;
+        self.ctx = ctx;
+        self.block(&for_lit.body, Some(node));
+        self.scopes -= 1;
+        self.emit(Op::PopScope);
+        self.emit(Op::Settle(ctx));
+        self.ctx = outer;
+        self.emit(Op::Jump(next));
+        self.patch(next);
+        let end = self.emit(Op::PopIter);
+        self.iters -= 1;
+        if let Exit::Loop { cont, brk, .. } = &mut self.exits[ctx] {
+            *cont = next;
+            *brk = end;
+        }
+    }
+    fn return_stmt(&mut self, v: &AST, ast: &AST, parent: Option<usize>) {
+        let node = self.node(ast, parent);
+        let line = v.get_line();
+        let tail = match &v.t {
+            // arguments that await run before the call, so it can't be a tail call
+            ASTType::FunctionCall(callee, args) if !self.has_await(v) => Some((callee, args)),
+            _ => None,
+        };
+        if let Some((callee, args)) = tail {
+            self.unpacked(callee, Some(node), line, v, Some(node));
+            let call = self.node(v, Some(node));
+            let prep = self.emit(Op::PrepTail {
+                call,
+                line,
+                site: node,
+                ret: 0,
+            });
+            for arg in args.iter() {
+                self.unpacked(arg, Some(node), arg.get_line(), arg, Some(node));
+            }
+            self.emit(Op::TailCall {
+                argc: args.len(),
+                site: node,
+                ctx: self.ctx,
+            });
+            self.patch(prep);
+        } else {
+            self.unpacked(v, Some(node), line, v, Some(node));
+        }
+        self.emit(Op::Return {
+            site: node,
+            ctx: self.ctx,
+        });
+    }
+    // leaves the value of ast on the stack after unpacking it the way the code using it
+    //  does, with the line and ast that the unpacking reports
+    fn unpacked(
+        &mut self,
+        ast: &AST,
+        parent: Option<usize>,
+        line: usize,
+        site_ast: &AST,
+        site_parent: Option<usize>,
+    ) {
+        let site = self.node(site_ast, site_parent);
+        if let ASTType::VariableAccess(name, address) = &ast.t {
+            self.emit(Op::LoadVar {
+                name: name.clone(),
+                address: *address,
+                line,
+                site,
+            });
+            return;
+        }
+        if self.expr(ast, parent) {
+            self.emit(Op::Unpack { line, site });
+        }
+    }
+    // the operands of a binary operator, each unpacked as itself
+    fn operands(&mut self, left: &AST, right: &AST, node: usize) {
+        self.unpacked(left, Some(node), left.get_line(), left, Some(node));
+        self.unpacked(right, Some(node), right.get_line(), right, Some(node));
+    }
+    fn binary(&mut self, left: &AST, right: &AST, node: usize, f: BinaryFn) {
+        self.operands(left, right, node);
+        self.emit(Op::Binary {
+            f,
+            left_line: left.get_line(),
+            right_line: right.get_line(),
+            site: node,
+        });
+    }
+    // leaves what get_value would give back on the stack. gives back false when that can
+    //  only be a plain value, so unpacking it would do nothing
+    // the left side of an = or +=, indexing it skips __index like AST::get_target does
+    fn target(&mut self, ast: &AST, parent: Option<usize>) {
+        let ASTType::BracketAccess(left, key) = &ast.t else {
+            self.expr(ast, parent);
+            return;
+        };
+        let node = self.node(ast, parent);
+        let line = ast.get_line();
+        self.unpacked(key, parent, key.get_line(), key, parent);
+        self.unpacked(left, parent, line, ast, parent);
+        self.emit(Op::Index {
+            line,
+            site: node,
+            is_target: true,
+        });
+    }
+    fn expr(&mut self, ast: &AST, parent: Option<usize>) -> bool {
+        let node = self.node(ast, parent);
+        let line = ast.get_line();
+        match &ast.t {
+            ASTType::IntLiteral(n) => self.emit(Op::Int(*n)),
+            ASTType::NumberLiteral(n) => self.emit(Op::Number(*n)),
+            ASTType::BooleanLiteral(b) => self.emit(Op::Bool(*b)),
+            ASTType::CharacterLiteral(c) => self.emit(Op::Char(*c)),
+            ASTType::StringLiteral(s) => self.emit(Op::Str(s.clone())),
+            ASTType::VariableAccess(name, address) => {
+                self.emit(Op::Var(name.clone(), *address));
+                return true;
+            }
+            ASTType::VariableDeclaration(name, is_const) => {
+                self.emit(Op::Declare {
+                    name: name.clone(),
+                    is_const: *is_const,
+                    line,
+                    site: parent,
+                });
+                return true;
+            }
+            ASTType::Paren(inner) => return self.expr(inner, Some(node)),
+            // errors from the parts of these go straight up past them, like the ? in their
+            //  get_value arms
+            ASTType::DotAccess(left, name) => {
+                self.unpacked(left, parent, line, ast, parent);
+                self.emit(Op::Field(name.clone()));
+                return true;
+            }
+            ASTType::BracketAccess(left, key) => {
+                // the key is evaluated before the value being indexed
+                self.unpacked(key, parent, key.get_line(), key, parent);
+                self.unpacked(left, parent, line, ast, parent);
+                self.emit(Op::Index {
+                    line,
+                    site: node,
+                    is_target: false,
+                });
+                return true;
+            }
+            ASTType::ArrayLiteral(values) => {
+                for value in values.iter() {
+                    self.unpacked(value, parent, value.get_line(), value, parent);
+                }
+                self.emit(Op::Array(values.len()))
+            }
+            ASTType::FunctionLiteral(_) => self.emit(Op::Function(node)),
+            ASTType::FunctionCall(callee, params) => {
+                let eager = params.iter().any(|param| self.has_await(param));
+                self.unpacked(callee, parent, line, ast, parent);
+                let prep = self.emit(Op::PrepCall {
+                    call: node,
+                    argc: params.len(),
+                    line,
+                    site: node,
+                    end: 0,
+                    eager,
+                });
+                for (index, param) in params.iter().enumerate() {
+                    let site = self.node(param, Some(node));
+                    if self.expr(param, Some(node)) {
+                        self.emit(Op::UnpackArg {
+                            index,
+                            call_line: line,
+                            arg_line: param.get_line(),
+                            site,
+                        });
+                    }
+                }
+                self.emit(Op::Call {
+                    argc: params.len(),
+                    call: node,
+                    line,
+                    site: node,
+                });
+                self.patch(prep);
+                return true;
+            }
+            ASTType::OpPls(left, right) => {
+                self.binary(left, right, node, add_values);
+                return false;
+            }
+            ASTType::OpMns(left, right) => {
+                self.binary(left, right, node, sub_values);
+                return false;
+            }
+            ASTType::OpTimes(left, right) => {
+                self.binary(left, right, node, mul_values);
+                return false;
+            }
+            ASTType::OpDiv(left, right) => {
+                self.binary(left, right, node, div_values);
+                return false;
+            }
+            ASTType::OpEqEq(left, right) => {
+                self.binary(left, right, node, |l, r, line, _, scope_chain| {
+                    eq_values(l, r, line, scope_chain)
+                });
+                return false;
+            }
+            ASTType::OpNotEq(left, right) => {
+                self.binary(left, right, node, |l, r, line, _, scope_chain| {
+                    noteq_values(l, r, line, scope_chain)
+                });
+                return false;
+            }
+            ASTType::OpGt(left, right) => {
+                self.binary(left, right, node, |l, r, ll, _, scope_chain| {
+                    comparison_values(l, r, ll, scope_chain, "__gt", |ord| {
+                        ord == Ordering::Greater
+                    })
+                });
+                return false;
+            }
+            ASTType::OpLt(left, right) => {
+                self.binary(left, right, node, |l, r, ll, _, scope_chain| {
+                    comparison_values(l, r, ll, scope_chain, "__lt", |ord| ord == Ordering::Less)
+                });
+                return false;
+            }
+            ASTType::OpGtEq(left, right) => {
+                self.binary(left, right, node, |l, r, ll, _, scope_chain| {
+                    comparison_values(l, r, ll, scope_chain, "__ge", |ord| ord != Ordering::Less)
+                });
+                return false;
+            }
+            ASTType::OpLtEq(left, right) => {
+                self.binary(left, right, node, |l, r, ll, _, scope_chain| {
+                    comparison_values(l, r, ll, scope_chain, "__le", |ord| {
+                        ord != Ordering::Greater
+                    })
+                });
+                return false;
+            }
+            ASTType::OpAndAnd(left, right) | ASTType::OpOrOr(left, right) => {
+                let op = match &ast.t {
+                    ASTType::OpAndAnd(_, _) => "&&",
+                    _ => "||",
+                };
+                self.unpacked(left, Some(node), left.get_line(), left, Some(node));
+                let short = self.emit(Op::LogicLeft {
+                    op,
+                    line: left.get_line(),
+                    site: node,
+                    end: 0,
+                });
+                self.unpacked(right, Some(node), right.get_line(), right, Some(node));
+                self.emit(Op::LogicRight {
+                    op,
+                    left_line: left.get_line(),
+                    right_line: right.get_line(),
+                    site: node,
+                });
+                self.patch(short);
+                return false;
+            }
+            ASTType::OpMnsPrefix(v) => {
+                self.unpacked(v, Some(node), v.get_line(), v, Some(node));
+                self.emit(Op::Neg {
+                    line: v.get_line(),
+                    site: node,
+                });
+                return false;
+            }
+            ASTType::OpNot(v) => {
+                self.unpacked(v, Some(node), v.get_line(), v, Some(node));
+                self.emit(Op::Not {
+                    line: v.get_line(),
+                    site: node,
+                });
+                return false;
+            }
+            ASTType::OpEq(left, right) => {
+                let target = self.node(left, Some(node));
+                self.target(left, Some(node));
+                self.unpacked(right, Some(node), right.get_line(), right, Some(node));
+                self.emit(Op::Assign { target, site: node });
+                return true;
+            }
+            ASTType::OpPlsEq(left, right) => {
+                let target = self.node(left, Some(node));
+                let right_node = self.node(right, Some(node));
+                self.target(left, Some(node));
+                self.emit(Op::PlsEqTarget { target, site: node });
+                self.unpacked(right, Some(node), right.get_line(), right, Some(node));
+                self.emit(Op::PlsEq {
+                    target,
+                    right: right_node,
+                    site: node,
+                });
+                return true;
+            }
+            ASTType::Await(v) => {
+                self.unpacked(v, Some(node), v.get_line(), v, Some(node));
+                self.emit(Op::Await { site: node });
+                return false;
+            }
+            ASTType::ObjectLiteral(fields) if self.has_await(ast) => {
+                for (key, value) in fields.iter() {
+                    if let FieldKey::Computed(key) = key {
+                        self.unpacked(key, Some(node), key.get_line(), key, Some(node));
+                    }
+                    self.unpacked(value, Some(node), value.get_line(), value, Some(node));
+                }
+                self.emit(Op::Object(node))
+            }
+            ASTType::Range(range_lit) if self.has_await(ast) => {
+                let bounds = [&range_lit.start, &range_lit.end]
+                    .into_iter()
+                    .chain(range_lit.step.iter());
+                for bound in bounds {
+                    self.unpacked(bound, Some(node), bound.get_line(), bound, Some(node));
+                }
+                self.emit(Op::Range {
+                    has_step: range_lit.step.is_some(),
+                    inclusive: range_lit.inclusive,
+                    line,
+                    site: node,
+                })
+            }
+            ASTType::OpIn(left, right) if self.has_await(ast) => {
+                self.binary(left, right, node, |l, r, line, _, scope_chain| {
+                    in_values(l, r, line, scope_chain)
+                });
+                return false;
+            }
+            ASTType::Match(match_lit) if self.has_await(ast) => {
+                self.match_expr(match_lit, ast, node);
+                return false;
+            }
+            _ => {
+                self.emit(Op::Eval(node));
+                return true;
+            }
+        };
+        false
+    }
+    fn match_expr(&mut self, match_lit: &MatchLiteral, ast: &AST, node: usize) {
+        let line = ast.get_line();
+        let value = &match_lit.value;
+        self.unpacked(value, Some(node), line, value, Some(node));
+        let mut ends = vec![];
+        for arm in match_lit.arms.iter() {
+            let pattern = self.node(&arm.pattern, Some(node));
+            let test = self.emit(Op::Arm {
+                pattern,
+                line: arm.pattern.get_line(),
+                site: node,
+                next: 0,
+            });
+            self.scopes += 1;
+            if arm.is_block {
+                let exit = self.exits.len();
+                self.exits.push(Exit::Arm {
+                    end: 0,
+                    scopes: self.scopes,
+                });
+                let ctx = std::mem::replace(&mut self.ctx, exit);
+                self.block(&arm.body, Some(node));
+                self.ctx = ctx;
+                self.exits[exit] = Exit::Arm {
+                    end: self.here(),
+                    scopes: self.scopes,
+                };
+            } else {
+                let body = &arm.body[0];
+                self.unpacked(body, Some(node), body.get_line(), body, Some(node));
+            }
+            self.scopes -= 1;
+            self.emit(Op::EndArm {
+                is_block: arm.is_block,
+            });
+            ends.push(self.emit(Op::Jump(0)));
+            self.patch(test);
+        }
+        self.emit(Op::NoArm { line, site: node });
+        for end in ends {
+            self.patch(end);
+        }
+    }
+}
+
+fn contains_await(ast: &mut AST) -> bool {
+    match &ast.t {
+        ASTType::Await(_) => true,
+        // these run their contents some other time, if at all
+        ASTType::FunctionLiteral(_) | ASTType::Macro(_, _) | ASTType::Quote(_) => false,
+        _ => ast
+            .children_mut()
+            .into_iter()
+            .any(|child| contains_await(child)),
+    }
+}
+
+// a chunk partway through running. a task keeps its frame while it waits at an await, and
+//  resuming the chunk with it carries on from there
+pub struct Frame {
+    pc: usize,
+    stack: Vec<Rc<Value>>,
+    iters: Vec<ValueIter>,
+    base: usize,
+    // only the body of a task can stop at an await, anything else waits where it is
+    is_task: bool,
+    // set when nothing is left that could finish what the task waits on, the await fails
+    //  instead of stopping again
+    pub cancelled: bool,
+}
+
+impl Frame {
+    fn new(scope_chain: &ScopeChain, is_task: bool) -> Frame {
+        Frame {
+            pc: 0,
+            stack: vec![],
+            iters: vec![],
+            base: scope_chain.depth(),
+            is_task,
+            cancelled: false,
+        }
+    }
+    // a frame for the body of a task, once its scope and parameters are added
+    pub fn task(scope_chain: &ScopeChain) -> Frame {
+        Frame::new(scope_chain, true)
+    }
+}
+
+pub enum Step {
+    Done(ReturnType),
+    // stopped at an await of the future
+    Await(Rc<Future>),
+}
+
+impl Chunk {
+    fn function(body: &Block, awaits: bool) -> Chunk {
+        let code = body.clone();
+        let mut compiler = Compiler::new(awaits);
+        compiler.exits.push(Exit::Leave(0));
+        compiler.block(&code, None);
+        compiler.exits[0] = Exit::Leave(compiler.here());
+        // moving the block doesn't move the boxed asts that the nodes point at
+        compiler.finish(code)
+    }
+    fn ast(&self, node: usize) -> &AST {
+        // nodes point into the code of the chunk or the block run was given, both of which
+        //  outlive the chunk running
+        unsafe { &*self.nodes[node].ast }
+    }
+    // adds the asts from the node out to the statement, like get_value does on the way up
+    fn trace(&self, mut e: Box<RuntimeError>, node: Option<usize>) -> Box<RuntimeError> {
+        let mut node = node;
+        while let Some(i) = node {
+            e.add_base_ast(self.ast(i).clone());
+            node = self.nodes[i].parent;
+        }
+        e
+    }
+    // runs a function body, leaving whatever it returned in the return register
+    pub fn run_body(&self, scope_chain: &mut ScopeChain) -> Result<(), Box<RuntimeError>> {
+        self.execute(scope_chain)?;
+        Ok(())
+    }
+    // where the signal in the return register goes, None if there isn't one
+    fn settle(
+        &self,
+        ctx: usize,
+        base: usize,
+        iters: &mut Vec<ValueIter>,
+        scope_chain: &mut ScopeChain,
+    ) -> Result<Option<usize>, Box<RuntimeError>> {
+        let signal = scope_chain.get_return_register();
+        if signal == ReturnType::None {
+            return Ok(None);
+        }
+        let mut ctx = ctx;
+        loop {
+            let (target, depth) = match &self.exits[ctx] {
+                Exit::Loop {
+                    label,
+                    cont,
+                    brk,
+                    scopes,
+                    iters: open_iters,
+                    parent,
+                } => {
+                    let target = match &signal {
+                        ReturnType::Break(t) if t.is_none() || t == label => *brk,
+                        ReturnType::Continue(t) if t.is_none() || t == label => *cont,
+                        _ => {
+                            ctx = *parent;
+                            continue;
+                        }
+                    };
+                    scope_chain.eat_return_register();
+                    iters.truncate(*open_iters);
+                    (target, base + scopes)
+                }
+                Exit::Leave(target) => (*target, base),
+                Exit::Arm { end, scopes } => (*end, base + scopes),
+            };
+            while scope_chain.depth() > depth {
+                scope_chain.pop_scope().to_runtime_error()?;
+            }
+            return Ok(Some(target));
+        }
+    }
+    fn execute(&self, scope_chain: &mut ScopeChain) -> Result<ReturnType, Box<RuntimeError>> {
+        match self.resume(&mut Frame::new(scope_chain, false), scope_chain)? {
+            Step::Done(ret) => Ok(ret),
+            Step::Await(_) => unreachable!("only a task stops at an await"),
+        }
+    }
+    // runs the chunk from where the frame left off until it finishes or, in a task, until
+    //  it gets to an await of a future that hasn't resolved
+    pub fn resume(
+        &self,
+        frame: &mut Frame,
+        scope_chain: &mut ScopeChain,
+    ) -> Result<Step, Box<RuntimeError>> {
+        let base = frame.base;
+        let stack = &mut frame.stack;
+        let iters = &mut frame.iters;
+        let mut pc = frame.pc;
+        while pc < self.ops.len() {
+            match &self.ops[pc] {
+                Op::Int(n) => stack.push(Rc::new(Value::Int(*n))),
+                Op::Number(n) => stack.push(Rc::new(Value::Number(*n))),
+                Op::Bool(b) => stack.push(Rc::new(Value::Boolean(*b))),
+                Op::Char(c) => stack.push(Rc::new(Value::Char(*c))),
+                Op::Str(s) => stack.push(Rc::new(Value::String(s.clone()))),
+                Op::LoadVar {
+                    name,
+                    address,
+                    line,
+                    site,
+                } => {
+                    let value = scope_chain
+                        .get_variable_at(name, *address, *line)
+                        .and_then(|v| v.unpack(scope_chain, *line))
+                        .map_err(|e| self.trace(Box::new(e.to_runtime_error()), Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::Var(name, address) => {
+                    stack.push(Rc::new(Value::Variable(name.clone(), *address)))
+                }
+                Op::Declare {
+                    name,
+                    is_const,
+                    line,
+                    site,
+                } => {
+                    let slot = scope_chain
+                        .add_variable(name, *is_const, *line)
+                        .map_err(|e| self.trace(Box::new(e.to_runtime_error()), *site))?;
+                    let address = Address::Local { depth: 0, slot };
+                    stack.push(Rc::new(Value::Variable(name.clone(), address)));
+                }
+                Op::Unpack { line, site } => {
+                    let value = stack.pop().unwrap();
+                    let value = value
+                        .unpack(scope_chain, *line)
+                        .map_err(|e| self.trace(Box::new(e.to_runtime_error()), Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::UnpackArg {
+                    index,
+                    call_line,
+                    arg_line,
+                    site,
+                } => {
+                    let callee = &stack[stack.len() - 2 - index];
+                    let line = match callee.as_ref() {
+                        Value::Function(_) => *call_line,
+                        _ => *arg_line,
+                    };
+                    let value = stack.pop().unwrap();
+                    let value = value
+                        .unpack(scope_chain, line)
+                        .map_err(|e| self.trace(Box::new(e.to_runtime_error()), Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::Field(name) => {
+                    let value = stack.pop().unwrap();
+                    stack.push(Rc::new(Value::ObjectAccess(
+                        value,
+                        ObjectKey::String(name.clone()),
+                    )));
+                }
+                Op::Index {
+                    line,
+                    site,
+                    is_target,
+                } => {
+                    let left = stack.pop().unwrap();
+                    let key = stack.pop().unwrap();
+                    let value = index_value(left, &key, *is_target, scope_chain, *line)
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::Binary {
+                    f,
+                    left_line,
+                    right_line,
+                    site,
+                } => {
+                    let right = stack.pop().unwrap();
+                    let left = stack.pop().unwrap();
+                    let value = f(&left, &right, *left_line, *right_line, scope_chain)
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::Neg { line, site } => {
+                    let value = stack.pop().unwrap();
+                    let value = neg_value(&value, *line, scope_chain)
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::Not { line, site } => {
+                    let value = stack.pop().unwrap();
+                    let value = not_value(&value, *line, scope_chain)
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::LogicLeft {
+                    op,
+                    line,
+                    site,
+                    end,
+                } => {
+                    let left = stack.last().unwrap();
+                    if let Some(value) = logic_left(op, left, *line, scope_chain)
+                        .map_err(|e| self.trace(e, Some(*site)))?
+                    {
+                        *stack.last_mut().unwrap() = value;
+                        pc = *end;
+                        continue;
+                    }
+                }
+                Op::LogicRight {
+                    op,
+                    left_line,
+                    right_line,
+                    site,
+                } => {
+                    let right = stack.pop().unwrap();
+                    let left = stack.pop().unwrap();
+                    let value =
+                        logic_right(op, &left, &right, *left_line, *right_line, scope_chain)
+                            .map_err(|e| self.trace(e, Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::Assign { target, site } => {
+                    let right = stack.pop().unwrap();
+                    let left = stack.pop().unwrap();
+                    let value = assign_value(self.ast(*target), left, right, scope_chain)
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::PlsEqTarget { target, site } => {
+                    check_plseq_target(self.ast(*target), stack.last().unwrap())
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                }
+                Op::PlsEq {
+                    target,
+                    right,
+                    site,
+                } => {
+                    let right_val = stack.pop().unwrap();
+                    let left_val = stack.pop().unwrap();
+                    let value = plseq_value(
+                        self.ast(*target),
+                        self.ast(*right),
+                        left_val,
+                        right_val,
+                        scope_chain,
+                    )
+                    .map_err(|e| self.trace(e, Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::Array(len) => {
+                    let mut obj = Object::new();
+                    for (i, value) in stack.drain(stack.len() - len..).enumerate() {
+                        obj.set(ObjectKey::Number(i as f64), value);
+                    }
+                    stack.push(Rc::new(Value::Object(obj)));
+                }
+                Op::Function(node) => {
+                    let f = match &self.ast(*node).t {
+                        ASTType::FunctionLiteral(f) => f.make_real(scope_chain),
+                        _ => unreachable!("Function ops are only made for function literals"),
+                    };
+                    stack.push(Rc::new(Value::Function(f)));
+                }
+                Op::PrepCall {
+                    call,
+                    argc,
+                    line,
+                    site,
+                    end,
+                    eager,
+                } => match stack.last().unwrap().as_ref() {
+                    Value::Function(_) => {}
+                    Value::BuiltinFunction(_, arg_len) => {
+                        if arg_len != argc {
+                            let e = RuntimeError::new(
+                                format!("Expected {} arguments, got {}", arg_len, argc),
+                                *line,
+                            );
+                            return Err(self.trace(Box::new(e), Some(*site)));
+                        }
+                    }
+                    _ if *eager => {}
+                    _ => {
+                        let func = stack.pop().unwrap();
+                        let call_ast = self.ast(*call);
+                        let params = match &call_ast.t {
+                            ASTType::FunctionCall(_, params) => params,
+                            _ => unreachable!("PrepCall ops are only made for calls"),
+                        };
+                        let value = call_value(func, params, call_ast, scope_chain, *line)
+                            .map_err(|e| self.trace(e, Some(*site)))?;
+                        stack.push(value);
+                        pc = *end;
+                        continue;
+                    }
+                },
+                Op::Call {
+                    argc,
+                    call,
+                    line,
+                    site,
+                } => {
+                    let args = stack.split_off(stack.len() - argc);
+                    let func = stack.pop().unwrap();
+                    let value = match func.as_ref() {
+                        Value::Function(f) => f.invoke(scope_chain, args, *line),
+                        Value::BuiltinFunction(f, _) => {
+                            let call_ast = self.ast(*call);
+                            f(args, call_ast, scope_chain, call_ast.get_line())
+                        }
+                        Value::EnumConstructor(variant) => {
+                            construct_enum_values(variant, args, *line)
+                        }
+                        Value::StructConstructor(def) => construct_struct_values(def, args, *line),
+                        _ => Err(Box::new(RuntimeError::new(
+                            "Cannot call a non-function".into(),
+                            *line,
+                        ))),
+                    }
+                    .map_err(|e| self.trace(e, Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::PrepTail {
+                    call,
+                    line,
+                    site,
+                    ret,
+                } => match stack.last().unwrap().as_ref() {
+                    Value::Function(f) if !f.is_async => {}
+                    _ => {
+                        let func = stack.pop().unwrap();
+                        let call_ast = self.ast(*call);
+                        let params = match &call_ast.t {
+                            ASTType::FunctionCall(_, params) => params,
+                            _ => unreachable!("PrepTail ops are only made for calls"),
+                        };
+                        let value = call_value(func, params, call_ast, scope_chain, *line)
+                            .unpack_and_transform(scope_chain, *line, call_ast)
+                            .map_err(|e| self.trace(e, Some(*site)))?;
+                        stack.push(value);
+                        pc = *ret;
+                        continue;
+                    }
+                },
+                Op::TailCall { argc, site, ctx } => {
+                    let args = stack.split_off(stack.len() - argc);
+                    let func = stack.pop().unwrap();
+                    scope_chain
+                        .set_return_register(ReturnType::TailCall(func, args))
+                        .to_runtime_error()
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                    if let Some(target) = self.settle(*ctx, base, iters, scope_chain)? {
+                        pc = target;
+                        continue;
+                    }
+                }
+                Op::Return { site, ctx } => {
+                    let value = stack.pop().unwrap();
+                    scope_chain
+                        .set_return_register(ReturnType::Return(value))
+                        .to_runtime_error()
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                    if let Some(target) = self.settle(*ctx, base, iters, scope_chain)? {
+                        pc = target;
+                        continue;
+                    }
+                }
+                Op::Signal { signal, site, ctx } => {
+                    scope_chain
+                        .set_return_register(signal.clone())
+                        .to_runtime_error()
+                        .map_err(|e| self.trace(e, *site))?;
+                    if let Some(target) = self.settle(*ctx, base, iters, scope_chain)? {
+                        pc = target;
+                        continue;
+                    }
+                }
+                Op::Settle(ctx) => {
+                    if let Some(target) = self.settle(*ctx, base, iters, scope_chain)? {
+                        pc = target;
+                        continue;
+                    }
+                }
+                Op::Branch {
+                    target,
+                    msg,
+                    line,
+                    site,
+                } => match stack.pop().unwrap().as_ref() {
+                    Value::Boolean(true) => {}
+                    Value::Boolean(false) => {
+                        pc = *target;
+                        continue;
+                    }
+                    _ => {
+                        let e = RuntimeError::new(msg.to_string(), *line);
+                        return Err(self.trace(Box::new(e), Some(*site)));
+                    }
+                },
+                Op::Jump(target) => {
+                    pc = *target;
+                    continue;
+                }
+                Op::Pop => {
+                    stack.pop();
+                }
+                Op::PushScope => scope_chain.add_scope().to_runtime_error().map(|_| ())?,
+                Op::PopScope => scope_chain.pop_scope().to_runtime_error().map(|_| ())?,
+                Op::Iter { line, site } => {
+                    let value = stack.pop().unwrap();
+                    let iter = iter_values(&value, scope_chain, *line)
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                    iters.push(iter);
+                }
+                Op::Next {
+                    var,
+                    line,
+                    site,
+                    end,
+                } => {
+                    let value = match iters.last_mut().unwrap().next() {
+                        Some(value) => value,
+                        None => {
+                            pc = *end;
+                            continue;
+                        }
+                    };
+                    scope_chain.add_scope().to_runtime_error()?;
+                    scope_chain
+                        .add_variable(var, false, *line)
+                        .to_runtime_error()
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                    scope_chain
+                        .set_variable(var, value, *line)
+                        .to_runtime_error()
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                }
+                Op::PopIter => {
+                    iters.pop();
+                }
+                Op::Object(node) => {
+                    let ASTType::ObjectLiteral(fields) = &self.ast(*node).t else {
+                        unreachable!("Object ops are only made for object literals")
+                    };
+                    let count = fields
+                        .iter()
+                        .map(|(key, _)| match key {
+                            FieldKey::Computed(_) => 2,
+                            FieldKey::Static(_) => 1,
+                        })
+                        .sum::<usize>();
+                    let mut values = stack.split_off(stack.len() - count).into_iter();
+                    let mut obj = Object::new();
+                    for (key, _) in fields.iter() {
+                        let key = match key {
+                            FieldKey::Static(key) => key.clone(),
+                            FieldKey::Computed(key) => {
+                                value_to_key(&values.next().unwrap(), scope_chain, key.get_line())
+                                    .map_err(|e| self.trace(e, Some(*node)))?
+                            }
+                        };
+                        obj.set(key, values.next().unwrap());
+                    }
+                    stack.push(Rc::new(Value::Object(obj)));
+                }
+                Op::Range {
+                    has_step,
+                    inclusive,
+                    line,
+                    site,
+                } => {
+                    let step = match has_step {
+                        true => Some(stack.pop().unwrap()),
+                        false => None,
+                    };
+                    let end = stack.pop().unwrap();
+                    let start = stack.pop().unwrap();
+                    let value = range_values(
+                        &start,
+                        &end,
+                        step.as_deref(),
+                        *inclusive,
+                        scope_chain,
+                        *line,
+                    )
+                    .map_err(|e| self.trace(e, Some(*site)))?;
+                    stack.push(value);
+                }
+                Op::Await { site } => {
+                    let value = stack.last().unwrap().clone();
+                    if let Value::Future(future) = value.as_ref() {
+                        scope_chain.get_event_loop().mark_awaited(future);
+                        let result = match poll(future) {
+                            Some(result) => result,
+                            None if frame.cancelled => Err(stuck_error(future)),
+                            None if frame.is_task => {
+                                frame.pc = pc;
+                                return Ok(Step::Await(future.clone()));
+                            }
+                            None => await_future(future, scope_chain),
+                        };
+                        stack.pop();
+                        stack.push(result.map_err(|e| self.trace(e, Some(*site)))?);
+                    }
+                }
+                Op::Arm {
+                    pattern,
+                    line,
+                    site,
+                    next,
+                } => {
+                    let value = stack.last().unwrap();
+                    let bindings = match_pattern(self.ast(*pattern), value, false, scope_chain)
+                        .map_err(|e| self.trace(e, Some(*site)))?;
+                    let Some(bindings) = bindings else {
+                        pc = *next;
+                        continue;
+                    };
+                    scope_chain.add_scope().to_runtime_error()?;
+                    for (name, v) in bindings {
+                        scope_chain
+                            .add_variable(&name, false, *line)
+                            .to_runtime_error()
+                            .map_err(|e| self.trace(e, Some(*site)))?;
+                        scope_chain
+                            .set_variable(&name, v, *line)
+                            .to_runtime_error()
+                            .map_err(|e| self.trace(e, Some(*site)))?;
+                    }
+                }
+                Op::EndArm { is_block } => {
+                    let ret = match is_block {
+                        true => Rc::new(Value::Undefined),
+                        false => stack.pop().unwrap(),
+                    };
+                    *stack.last_mut().unwrap() = ret;
+                    scope_chain.pop_scope().to_runtime_error()?;
+                }
+                Op::NoArm { line, site } => {
+                    let value = stack.pop().unwrap();
+                    let e = no_arm_error(&value, scope_chain, *line);
+                    return Err(self.trace(e, Some(*site)));
+                }
+                Op::Eval(node) => {
+                    let value = self
+                        .ast(*node)
+                        .get_value(scope_chain)
+                        .map_err(|e| self.trace(e, self.nodes[*node].parent))?;
+                    stack.push(value);
+                }
+                Op::TopLevel(node) => {
+                    let stmt = self.ast(*node);
+                    match scope_chain.get_return_register() {
+                        ReturnType::None => {}
+                        ReturnType::Continue(_) => {
+                            return Err(Box::new(
+                                RuntimeError::new(
+                                    "Continue statement at top level".into(),
+                                    stmt.get_line(),
+                                )
+                                .add_base_ast(stmt.clone()),
+                            ))
+                        }
+                        ReturnType::Break(_) => {
+                            return Err(Box::new(
+                                RuntimeError::new(
+                                    "Break statement at top level".into(),
+                                    stmt.get_line(),
+                                )
+                                .add_base_ast(stmt.clone()),
+                            ))
+                        }
+                        ReturnType::Return(v) => return Ok(Step::Done(ReturnType::Return(v))),
+                        // there's no frame to reuse at the top level, so the call just runs here
+                        ReturnType::TailCall(func, args) => {
+                            scope_chain.eat_return_register();
+                            let v = match func.as_ref() {
+                                Value::Function(f) => {
+                                    f.call_values(scope_chain, args, stmt.get_line())?
+                                }
+                                _ => Rc::new(Value::Undefined),
+                            };
+                            scope_chain
+                                .set_return_register(ReturnType::Return(v.clone()))
+                                .to_runtime_error()?;
+                            return Ok(Step::Done(ReturnType::Return(v)));
+                        }
+                    }
+                }
+            }
+            pc += 1;
+        }
+        Ok(Step::Done(ReturnType::None))
+    }
+}
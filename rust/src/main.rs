@@ -1,12 +1,31 @@
 use maple_rs::builtins::create_builtins;
-use maple_rs::scopechain::{ReturnType, ScopeChain};
+use maple_rs::modules::FsModuleLoader;
+use maple_rs::scopechain::{Engine, ScopeChain};
 
-use maple_rs::{error::MapleError, parser::Parser};
+use maple_rs::{error::MapleError, parser::Parser, resolver, vm};
 use std::error::Error;
+use std::path::PathBuf;
 
-fn time_interpreter(contents: String, _demo: bool) -> Result<f64, Box<dyn Error>> {
+// module search path: every --module-path given on the command line, then MAPLE_PATH
+fn add_module_paths(scope_chain: &ScopeChain, module_paths: &[PathBuf]) {
+    let mut loader = FsModuleLoader::new();
+    for dir in module_paths {
+        loader.add_search_path(dir.clone());
+    }
+    loader.add_env_search_paths();
+    scope_chain.get_modules().set_loader(loader);
+}
+
+fn time_interpreter(
+    contents: String,
+    _demo: bool,
+    module_paths: &[PathBuf],
+    engine: Engine,
+) -> Result<f64, Box<dyn Error>> {
     let mut parser = Parser::new(contents);
     let mut scope_chain: ScopeChain = ScopeChain::new();
+    scope_chain.set_engine(engine);
+    add_module_paths(&scope_chain, module_paths);
 
     match create_builtins(&mut scope_chain) {
         Ok(_) => {}
@@ -16,25 +35,26 @@ fn time_interpreter(contents: String, _demo: bool) -> Result<f64, Box<dyn Error>
         }
     };
     let timer = std::time::Instant::now();
-    let ast = match parser.parse(true) {
+    let mut ast = match parser.parse(true) {
         Ok(ast) => ast,
         Err(e) => {
             println!("Error: {}", e.get_msg());
             return Result::Err(e.get_msg().into());
         }
     };
+    resolver::resolve(&mut ast, &scope_chain);
 
     // for (_, stmt) in ast.iter().enumerate() {
     //     println!("{}", stmt.pretty_print());
     // }
-    for (_, stmt) in ast.iter().enumerate() {
-        match stmt.interpret(&mut scope_chain) {
-            Ok(_) => {}
-            Err(e) => {
-                println!("Error: {}", e.get_msg());
-                return Result::Err(e.get_msg().into());
-            }
-        };
+    if let Err(e) = vm::interpret(&ast, &mut scope_chain) {
+        println!("Error: {}", e.get_msg());
+        return Result::Err(e.get_msg().into());
+    }
+    // async tasks that nothing awaited still get to finish
+    if let Err(e) = scope_chain.get_event_loop().run(&mut scope_chain) {
+        println!("Error: {}", e.get_msg());
+        return Result::Err(e.get_msg().into());
     }
     // println!("Time: {}us", timer.elapsed().as_micros());
     // if demo {
@@ -70,6 +90,75 @@ impl ToShortDigits<f64> for f64 {
     }
 }
 
+// runs the program over and over for 3 seconds and prints how long it took, giving back
+//  the mean of the fastest 80% of runs
+fn time_engine(contents: &str, module_paths: &[PathBuf], engine: Engine) -> Option<f64> {
+    println!("Timing the {:?} engine...", engine);
+
+    let mut times: Vec<f64> = Vec::new();
+    let mut amount = 0;
+    let timer = std::time::Instant::now();
+    times.reserve(1000000);
+    let start = std::time::Instant::now();
+    while amount < 1000000 {
+        let time = match time_interpreter(contents.to_string(), amount == 0, module_paths, engine) {
+            Ok(time) => time,
+            Err(e) => {
+                println!("Error: {}", e);
+                return None;
+            }
+        };
+        times.push(time);
+        amount += 1;
+
+        if timer.elapsed().as_secs_f64() * 1000.0 >= 3000.0 {
+            break;
+        }
+    }
+    println!("Time: {}ms", start.elapsed().as_millis(),);
+    let mut total: f64 = 0.0;
+    // let times_copy = times.clone();
+    let mut min = times[0];
+    let mut max = times[0];
+    let mut i = 0;
+    let top80 = (amount as f64 * 0.8) as usize;
+    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    for time in &times {
+        let time = *time;
+        if i < top80 {
+            total += time as f64;
+        }
+        if time < min {
+            min = time;
+        }
+        if time > max {
+            max = time;
+        }
+        i += 1;
+    }
+    total /= top80 as f64;
+    let mut std_dev: f64 = 0.0;
+    for time in &times {
+        let time = *time;
+        std_dev += (time as f64 - total).square();
+    }
+    std_dev = (std_dev / amount as f64).sqrt();
+
+    println!("Amount: {}", amount);
+
+    println!(
+        "Mean of top 80%: {}us, 󰘫: {}, min: {}, max: {}",
+        total.to_short_digits(3),
+        std_dev.to_short_digits(3),
+        min,
+        max
+    );
+    // std::fs::write("./times.csv", file_out)?;
+
+    println!("Total time: {}ms", timer.elapsed().as_millis());
+    Some(total)
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let args = std::env::args().collect::<Vec<String>>();
     let filename;
@@ -85,76 +174,56 @@ fn main() -> Result<(), Box<dyn Error>> {
     } else {
         filename = "./maple.mpl".to_string();
     }
-    let time_arg = "--time".to_string();
-    if args.contains(&time_arg) {
-        println!("Timing...");
-        let contents: String = std::fs::read_to_string(filename)?;
-
-        let mut times: Vec<f64> = Vec::new();
-        let mut amount = 0;
-        let timer = std::time::Instant::now();
-        times.reserve(1000000);
-        let start = std::time::Instant::now();
-        while amount < 1000000 {
-            let time = match time_interpreter(contents.clone(), amount == 0) {
-                Ok(time) => time,
-                Err(e) => {
-                    println!("Error: {}", e);
-                    return Ok(());
-                }
-            };
-            times.push(time);
-            amount += 1;
-
-            if timer.elapsed().as_secs_f64() * 1000.0 >= 3000.0 {
-                break;
+    let mut module_paths: Vec<PathBuf> = Vec::new();
+    let module_path_arg = "--module-path".to_string();
+    for (index, arg) in args.iter().enumerate() {
+        if arg != &module_path_arg {
+            continue;
+        }
+        match args.get(index + 1) {
+            Some(paths) => module_paths.extend(std::env::split_paths(paths)),
+            None => {
+                println!("No module path specified");
+                return Ok(());
             }
         }
-        println!("Time: {}ms", start.elapsed().as_millis(),);
-        let mut total: f64 = 0.0;
-        // let times_copy = times.clone();
-        let mut min = times[0];
-        let mut max = times[0];
-        let mut i = 0;
-        let top80 = (amount as f64 * 0.8) as usize;
-        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
-        for time in &times {
-            let time = *time;
-            if i < top80 {
-                total += time as f64;
+    }
+    let engine_arg = "--engine".to_string();
+    let mut engine = None;
+    if let Some(index) = args.iter().position(|x| x == &engine_arg) {
+        engine = match args.get(index + 1).map(|x| x.as_str()) {
+            Some("tree") => Some(Engine::Tree),
+            Some("vm") => Some(Engine::Vm),
+            _ => {
+                println!("--engine must be tree or vm");
+                return Ok(());
             }
-            if time < min {
-                min = time;
+        };
+    }
+    let time_arg = "--time".to_string();
+    if args.contains(&time_arg) {
+        let contents: String = std::fs::read_to_string(filename)?;
+        match engine {
+            Some(engine) => {
+                time_engine(&contents, &module_paths, engine);
             }
-            if time > max {
-                max = time;
+            // without --engine both get timed to see what the vm gains
+            None => {
+                let Some(tree) = time_engine(&contents, &module_paths, Engine::Tree) else {
+                    return Ok(());
+                };
+                let Some(vm) = time_engine(&contents, &module_paths, Engine::Vm) else {
+                    return Ok(());
+                };
+                println!("Vm speedup: {}x", (tree / vm).to_short_digits(2));
             }
-            i += 1;
         }
-        total /= top80 as f64;
-        let mut std_dev: f64 = 0.0;
-        for time in &times {
-            let time = *time;
-            std_dev += (time as f64 - total).square();
-        }
-        std_dev = (std_dev / amount as f64).sqrt();
-
-        println!("Amount: {}", amount);
-
-        println!(
-            "Mean of top 80%: {}us, 󰘫: {}, min: {}, max: {}",
-            total.to_short_digits(3),
-            std_dev.to_short_digits(3),
-            min,
-            max
-        );
-        // std::fs::write("./times.csv", file_out)?;
-
-        println!("Total time: {}ms", timer.elapsed().as_millis());
     } else {
-        let contents: String = std::fs::read_to_string(filename)?;
+        let contents: String = std::fs::read_to_string(&filename)?;
         let mut parser = Parser::new(contents);
         let mut scope_chain: ScopeChain = ScopeChain::new();
+        scope_chain.set_engine(engine.unwrap_or(Engine::Tree));
+        add_module_paths(&scope_chain, &module_paths);
 
         match create_builtins(&mut scope_chain) {
             Ok(_) => {}
@@ -163,29 +232,29 @@ fn main() -> Result<(), Box<dyn Error>> {
                 return Result::Err(e.get_msg().into());
             }
         };
-        let ast = match parser.parse(true) {
+        // the entry file counts as loading so importing it again is reported as a cycle
+        scope_chain
+            .get_modules()
+            .begin_loading(&std::fs::canonicalize(&filename)?.display().to_string())?;
+        let mut ast = match parser.parse(true) {
             Ok(ast) => ast,
             Err(e) => {
                 println!("Error: {}", e.get_msg());
                 return Result::Err(e.get_msg().into());
             }
         };
+        resolver::resolve(&mut ast, &scope_chain);
 
         // for (_, stmt) in ast.iter().enumerate() {
         //     println!("{}", stmt.pretty_print());
         // }
-        for (_, stmt) in ast.iter().enumerate() {
-            match stmt.interpret(&mut scope_chain) {
-                Ok(ReturnType::None) => {}
-                Ok(_) => {
-                    break;
-                }
-                Err(e) => {
-                    println!("Error: {}", e.get_msg());
-                    break;
-                    // return Result::Err(e.get_msg().into());
-                }
-            };
+        if let Err(e) = vm::interpret(&ast, &mut scope_chain) {
+            println!("Error: {}", e.get_msg());
+            return Ok(());
+        }
+        // async tasks that nothing awaited still get to finish
+        if let Err(e) = scope_chain.get_event_loop().run(&mut scope_chain) {
+            println!("Error: {}", e.get_msg());
         }
     }
 